@@ -0,0 +1,584 @@
+//
+// Compares the arena AST with a boxed tree
+//
+//     cargo run --release --example ast_bench [functions]
+//
+// A program with the given number of functions is generated and parsed once.
+// The file the parser builds keeps its nodes in two vectors with interned
+// names; for comparison, it is copied into a tree where every node owns its
+// children and names, the way the AST used to be. Then both forms are copied
+// and walked a number of times, and their sizes in memory are estimated.
+//
+use std::env;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use tlc::ast::*;
+use tlc::intern::*;
+use tlc::parser::parser_new;
+use tlc::visit::*;
+
+const ROUNDS : u32 = 20;
+
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let count : usize = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(2000);
+
+    let source = generate(count);
+    println!("Source: {} functions, {} bytes", count, source.len());
+
+    let start = Instant::now();
+    let mut parser = parser_new("bench.tl".to_string());
+    parser.init_source(&source);
+    parser.run();
+    let file = parser.take_file();
+    println!("Parse: {:?} ({} statements, {} expressions, {} names)",
+        start.elapsed(), file.get_statements().len(), file.get_expressions().len(), file.get_names().len());
+
+    let start = Instant::now();
+    let tree = boxed_file(&file);
+    println!("Box: {:?}", start.elapsed());
+
+    // Both forms have to say the same thing
+    let same = file.get_functions().iter().zip(&tree).all(|(func, boxed)| {
+        file.get_str(func.get_name()) == boxed.name && same_block(&file, func.get_block(), &boxed.block)
+    });
+    if !same || file.get_functions().len() != tree.len() {
+        println!("Error: The boxed tree does not match the arena.");
+        return;
+    }
+
+    println!();
+    println!("{:<8} {:>14} {:>14}", "", "arena", "boxed");
+
+    let arena_copy = time(|| { let _copy = file.clone(); });
+    let tree_copy = time(|| { let _copy = tree.clone(); });
+    println!("{:<8} {:>14?} {:>14?}", "copy", arena_copy, tree_copy);
+
+    let mut arena_nodes = 0;
+    let mut tree_nodes = 0;
+    let arena_walk = time(|| {
+        let mut counter = Counter { nodes : 0 };
+        counter.visit_file(&file);
+        arena_nodes = counter.nodes;
+    });
+    let tree_walk = time(|| {
+        let mut count = 0;
+        for func in &tree {
+            count_block(&func.block, &mut count);
+        }
+        tree_nodes = count;
+    });
+    println!("{:<8} {:>14?} {:>14?}", "walk", arena_walk, tree_walk);
+
+    let tree_bytes : usize = tree.iter().map(|func| func.name.capacity() + block_bytes(&func.block)).sum();
+    println!("{:<8} {:>14} {:>14}", "bytes", arena_bytes(&file), tree_bytes);
+    println!("{:<8} {:>14} {:>14}", "nodes", arena_nodes, tree_nodes);
+}
+
+// The average time of a number of rounds
+fn time<F : FnMut()>(mut f : F) -> Duration {
+    let start = Instant::now();
+    for _i in 0 .. ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+//
+// Every function gets a few variables, a loop, a conditional and a match
+//
+fn generate(count : usize) -> String {
+    let mut source = String::new();
+    source.push_str("struct Point is\n    x : i32;\n    y : i32;\nend\n\n");
+    for i in 0 .. count {
+        source.push_str(&format!("func work{}(a : i32, b : i32) -> i32 is\n", i));
+        source.push_str("    var total : i32 := a * b + 1;\n");
+        source.push_str("    var buf : char[16];\n");
+        source.push_str("    struct p : Point;\n");
+        source.push_str("    p.x := a;\n");
+        source.push_str("    while total < 1000 do\n");
+        source.push_str("        total += p.x * 2;\n");
+        source.push_str("        if total > 500 then\n");
+        source.push_str("            break;\n");
+        source.push_str("        elif total = 250 then\n");
+        source.push_str("            println(\"halfway\");\n");
+        source.push_str("        end\n");
+        source.push_str("    end\n");
+        source.push_str("    match total is\n");
+        source.push_str("        case 1, 2 .. 4 then\n");
+        source.push_str("            total := 0;\n");
+        source.push_str("        else\n");
+        source.push_str(&format!("            total := work{}(total, b);\n", i));
+        source.push_str("    end\n");
+        source.push_str("    return total;\n");
+        source.push_str("end\n\n");
+    }
+    source
+}
+
+//
+// The boxed tree
+// Only function bodies are kept, since that is where the nodes are
+//
+#[derive(Clone)]
+struct Func {
+    name : String,
+    block : Vec<Stmt>,
+}
+
+#[derive(Clone)]
+enum Stmt {
+    Return(Option<Expr>),
+    VarDec { name : String, data_type : DataType, value : Option<Expr> },
+    ArrayDec { name : String, data_type : DataType, size : Expr },
+    StructDec { name : String, struct_name : String },
+    Call { name : String, args : Vec<Expr> },
+    Expr(Expr),
+    While { label : Option<String>, cond : Expr, block : Vec<Stmt> },
+    Loop { label : Option<String>, block : Vec<Stmt> },
+    Repeat { label : Option<String>, block : Vec<Stmt>, cond : Expr },
+    Break(Option<String>),
+    Continue(Option<String>),
+    If { branches : Vec<(Expr, Vec<Stmt>)>, else_block : Option<Vec<Stmt>> },
+    Match { expr : Expr, cases : Vec<(Vec<Expr>, Vec<Stmt>)>, default : Option<Vec<Stmt>> },
+}
+
+#[derive(Clone)]
+enum Expr {
+    Id(String),
+    Int(u64),
+    Char(char),
+    Str(String),
+    Bool(bool),
+    ArrayAcc { name : String, index : Box<Expr> },
+    StructAcc { name : String, member : String },
+    Call { name : String, args : Vec<Expr> },
+    Cast { data_type : DataType, expr : Box<Expr> },
+    Binary { op : BinaryOp, lhs : Box<Expr>, rhs : Box<Expr> },
+    Assign { op : AssignOp, lhs : Box<Expr>, rhs : Box<Expr> },
+}
+
+fn boxed_file(file : &AstFile) -> Vec<Func> {
+    let mut funcs : Vec<Func> = Vec::new();
+    for func in file.get_functions() {
+        funcs.push(Func {
+            name : file.get_str(func.get_name()).to_string(),
+            block : boxed_block(file, func.get_block()),
+        });
+    }
+    funcs
+}
+
+fn boxed_block(file : &AstFile, block : &[StmtRef]) -> Vec<Stmt> {
+    block.iter().map(|stmt| boxed_stmt(file, *stmt)).collect()
+}
+
+fn boxed_list(file : &AstFile, list : &[ExprRef]) -> Vec<Expr> {
+    list.iter().map(|expr| boxed_expr(file, *expr)).collect()
+}
+
+fn boxed_stmt(file : &AstFile, stmt : StmtRef) -> Stmt {
+    let name = |sym : &Symbol| file.get_str(*sym).to_string();
+    let label = |label : &Option<Symbol>| label.map(|sym| file.get_str(sym).to_string());
+    let expr = |expr : &ExprRef| boxed_expr(file, *expr);
+
+    match file.get_statement(stmt).get_kind() {
+        AstStatementKind::Return(value) => Stmt::Return(value.as_ref().map(expr)),
+
+        AstStatementKind::VarDec { name : n, data_type, value } => {
+            Stmt::VarDec { name : name(n), data_type : *data_type, value : value.as_ref().map(expr) }
+        },
+
+        AstStatementKind::ArrayDec { name : n, data_type, size } => {
+            Stmt::ArrayDec { name : name(n), data_type : *data_type, size : expr(size) }
+        },
+
+        AstStatementKind::StructDec { name : n, struct_name } => {
+            Stmt::StructDec { name : name(n), struct_name : name(struct_name) }
+        },
+
+        AstStatementKind::CallStmt { name : n, args } => Stmt::Call { name : name(n), args : boxed_list(file, args) },
+        AstStatementKind::ExprStmt(value) => Stmt::Expr(expr(value)),
+
+        AstStatementKind::While { label : l, cond, block } => {
+            Stmt::While { label : label(l), cond : expr(cond), block : boxed_block(file, block) }
+        },
+
+        AstStatementKind::Loop { label : l, block } => Stmt::Loop { label : label(l), block : boxed_block(file, block) },
+
+        AstStatementKind::Repeat { label : l, block, cond } => {
+            Stmt::Repeat { label : label(l), block : boxed_block(file, block), cond : expr(cond) }
+        },
+
+        AstStatementKind::Break(l) => Stmt::Break(label(l)),
+        AstStatementKind::Continue(l) => Stmt::Continue(label(l)),
+
+        AstStatementKind::If { branches, else_block } => Stmt::If {
+            branches : branches.iter().map(|br| (expr(&br.get_cond()), boxed_block(file, br.get_block()))).collect(),
+            else_block : else_block.as_ref().map(|block| boxed_block(file, block)),
+        },
+
+        AstStatementKind::Match { expr : value, cases, default } => Stmt::Match {
+            expr : expr(value),
+            cases : cases.iter().map(|case| (boxed_list(file, case.get_values()), boxed_block(file, case.get_block()))).collect(),
+            default : default.as_ref().map(|block| boxed_block(file, block)),
+        },
+    }
+}
+
+fn boxed_expr(file : &AstFile, expr : ExprRef) -> Expr {
+    let name = |sym : &Symbol| file.get_str(*sym).to_string();
+    let child = |expr : &ExprRef| Box::new(boxed_expr(file, *expr));
+
+    match file.get_expression(expr).get_kind() {
+        AstExpressionKind::Id(n) => Expr::Id(name(n)),
+        AstExpressionKind::IntLiteral(val) => Expr::Int(*val),
+        AstExpressionKind::CharLiteral(val) => Expr::Char(*val),
+        AstExpressionKind::StringLiteral(val) => Expr::Str(name(val)),
+        AstExpressionKind::BoolLiteral(val) => Expr::Bool(*val),
+        AstExpressionKind::ArrayAcc { name : n, index } => Expr::ArrayAcc { name : name(n), index : child(index) },
+        AstExpressionKind::StructAcc { name : n, member } => Expr::StructAcc { name : name(n), member : name(member) },
+        AstExpressionKind::Call { name : n, args } => Expr::Call { name : name(n), args : boxed_list(file, args) },
+        AstExpressionKind::Cast { data_type, expr } => Expr::Cast { data_type : *data_type, expr : child(expr) },
+        AstExpressionKind::Binary { op, lhs, rhs } => Expr::Binary { op : *op, lhs : child(lhs), rhs : child(rhs) },
+        AstExpressionKind::Assign { op, lhs, rhs } => Expr::Assign { op : *op, lhs : child(lhs), rhs : child(rhs) },
+    }
+}
+
+//
+// Compares the arena with the boxed tree, node by node
+//
+fn same_block(file : &AstFile, block : &[StmtRef], boxed : &[Stmt]) -> bool {
+    block.len() == boxed.len() && block.iter().zip(boxed).all(|(stmt, boxed)| same_stmt(file, *stmt, boxed))
+}
+
+fn same_list(file : &AstFile, list : &[ExprRef], boxed : &[Expr]) -> bool {
+    list.len() == boxed.len() && list.iter().zip(boxed).all(|(expr, boxed)| same_expr(file, *expr, boxed))
+}
+
+fn same_value(file : &AstFile, value : &Option<ExprRef>, boxed : &Option<Expr>) -> bool {
+    match (value, boxed) {
+        (Some(expr), Some(boxed)) => same_expr(file, *expr, boxed),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_else(file : &AstFile, block : &Option<Vec<StmtRef>>, boxed : &Option<Vec<Stmt>>) -> bool {
+    match (block, boxed) {
+        (Some(block), Some(boxed)) => same_block(file, block, boxed),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_stmt(file : &AstFile, stmt : StmtRef, boxed : &Stmt) -> bool {
+    let name = |sym : &Symbol, boxed : &String| file.get_str(*sym) == boxed;
+    let label = |label : &Option<Symbol>, boxed : &Option<String>| label.map(|sym| file.get_str(sym)) == boxed.as_deref();
+    let expr = |expr : &ExprRef, boxed : &Expr| same_expr(file, *expr, boxed);
+
+    match (file.get_statement(stmt).get_kind(), boxed) {
+        (AstStatementKind::Return(a), Stmt::Return(b)) => same_value(file, a, b),
+
+        (AstStatementKind::VarDec { name : n, data_type, value }, Stmt::VarDec { name : bn, data_type : bt, value : bv }) => {
+            name(n, bn) && data_type == bt && same_value(file, value, bv)
+        },
+
+        (AstStatementKind::ArrayDec { name : n, data_type, size }, Stmt::ArrayDec { name : bn, data_type : bt, size : bs }) => {
+            name(n, bn) && data_type == bt && expr(size, bs)
+        },
+
+        (AstStatementKind::StructDec { name : n, struct_name }, Stmt::StructDec { name : bn, struct_name : bs }) => {
+            name(n, bn) && name(struct_name, bs)
+        },
+
+        (AstStatementKind::CallStmt { name : n, args }, Stmt::Call { name : bn, args : ba }) => {
+            name(n, bn) && same_list(file, args, ba)
+        },
+
+        (AstStatementKind::ExprStmt(a), Stmt::Expr(b)) => expr(a, b),
+
+        (AstStatementKind::While { label : l, cond, block }, Stmt::While { label : bl, cond : bc, block : bb }) => {
+            label(l, bl) && expr(cond, bc) && same_block(file, block, bb)
+        },
+
+        (AstStatementKind::Loop { label : l, block }, Stmt::Loop { label : bl, block : bb }) => {
+            label(l, bl) && same_block(file, block, bb)
+        },
+
+        (AstStatementKind::Repeat { label : l, block, cond }, Stmt::Repeat { label : bl, block : bb, cond : bc }) => {
+            label(l, bl) && same_block(file, block, bb) && expr(cond, bc)
+        },
+
+        (AstStatementKind::Break(l), Stmt::Break(bl)) | (AstStatementKind::Continue(l), Stmt::Continue(bl)) => label(l, bl),
+
+        (AstStatementKind::If { branches, else_block }, Stmt::If { branches : bb, else_block : be }) => {
+            branches.len() == bb.len()
+                && branches.iter().zip(bb).all(|(br, (cond, block))| expr(&br.get_cond(), cond) && same_block(file, br.get_block(), block))
+                && same_else(file, else_block, be)
+        },
+
+        (AstStatementKind::Match { expr : value, cases, default }, Stmt::Match { expr : bv, cases : bc, default : bd }) => {
+            expr(value, bv)
+                && cases.len() == bc.len()
+                && cases.iter().zip(bc).all(|(case, (values, block))| {
+                    same_list(file, case.get_values(), values) && same_block(file, case.get_block(), block)
+                })
+                && same_else(file, default, bd)
+        },
+
+        _ => false,
+    }
+}
+
+fn same_expr(file : &AstFile, expr : ExprRef, boxed : &Expr) -> bool {
+    let name = |sym : &Symbol, boxed : &String| file.get_str(*sym) == boxed;
+    let child = |expr : &ExprRef, boxed : &Expr| same_expr(file, *expr, boxed);
+
+    match (file.get_expression(expr).get_kind(), boxed) {
+        (AstExpressionKind::Id(n), Expr::Id(b)) | (AstExpressionKind::StringLiteral(n), Expr::Str(b)) => name(n, b),
+        (AstExpressionKind::IntLiteral(a), Expr::Int(b)) => a == b,
+        (AstExpressionKind::CharLiteral(a), Expr::Char(b)) => a == b,
+        (AstExpressionKind::BoolLiteral(a), Expr::Bool(b)) => a == b,
+
+        (AstExpressionKind::ArrayAcc { name : n, index }, Expr::ArrayAcc { name : bn, index : bi }) => {
+            name(n, bn) && child(index, bi)
+        },
+
+        (AstExpressionKind::StructAcc { name : n, member }, Expr::StructAcc { name : bn, member : bm }) => {
+            name(n, bn) && name(member, bm)
+        },
+
+        (AstExpressionKind::Call { name : n, args }, Expr::Call { name : bn, args : ba }) => {
+            name(n, bn) && same_list(file, args, ba)
+        },
+
+        (AstExpressionKind::Cast { data_type, expr }, Expr::Cast { data_type : bt, expr : be }) => {
+            data_type == bt && child(expr, be)
+        },
+
+        (AstExpressionKind::Binary { op, lhs, rhs }, Expr::Binary { op : bo, lhs : bl, rhs : br }) => {
+            op == bo && child(lhs, bl) && child(rhs, br)
+        },
+
+        (AstExpressionKind::Assign { op, lhs, rhs }, Expr::Assign { op : bo, lhs : bl, rhs : br }) => {
+            op == bo && child(lhs, bl) && child(rhs, br)
+        },
+
+        _ => false,
+    }
+}
+
+//
+// Counts the statements and expressions in both forms
+//
+struct Counter {
+    nodes : usize,
+}
+
+impl Visitor for Counter {
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        self.nodes += 1;
+        walk_statement(self, file, stmt);
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        self.nodes += 1;
+        walk_expression(self, file, expr);
+    }
+}
+
+// The boxed walk follows the boxes, like the visitor follows the handles
+fn count_block(block : &[Stmt], count : &mut usize) {
+    for stmt in block {
+        *count += 1;
+        match stmt {
+            Stmt::Return(value) | Stmt::VarDec { value, .. } => count_value(value, count),
+            Stmt::ArrayDec { size, .. } => count_expr(size, count),
+            Stmt::StructDec { .. } | Stmt::Break(_) | Stmt::Continue(_) => {},
+            Stmt::Call { args, .. } => count_list(args, count),
+            Stmt::Expr(expr) => count_expr(expr, count),
+
+            Stmt::While { cond, block, .. } => {
+                count_expr(cond, count);
+                count_block(block, count);
+            },
+
+            Stmt::Loop { block, .. } => count_block(block, count),
+
+            Stmt::Repeat { block, cond, .. } => {
+                count_block(block, count);
+                count_expr(cond, count);
+            },
+
+            Stmt::If { branches, else_block } => {
+                for (cond, block) in branches {
+                    count_expr(cond, count);
+                    count_block(block, count);
+                }
+                if let Some(block) = else_block {
+                    count_block(block, count);
+                }
+            },
+
+            Stmt::Match { expr, cases, default } => {
+                count_expr(expr, count);
+                for (values, block) in cases {
+                    count_list(values, count);
+                    count_block(block, count);
+                }
+                if let Some(block) = default {
+                    count_block(block, count);
+                }
+            },
+        }
+    }
+}
+
+fn count_value(value : &Option<Expr>, count : &mut usize) {
+    if let Some(expr) = value {
+        count_expr(expr, count);
+    }
+}
+
+fn count_list(list : &[Expr], count : &mut usize) {
+    for expr in list {
+        count_expr(expr, count);
+    }
+}
+
+fn count_expr(expr : &Expr, count : &mut usize) {
+    *count += 1;
+    match expr {
+        Expr::ArrayAcc { index, .. } => count_expr(index, count),
+        Expr::Call { args, .. } => count_list(args, count),
+        Expr::Cast { expr, .. } => count_expr(expr, count),
+
+        Expr::Binary { lhs, rhs, .. } | Expr::Assign { lhs, rhs, .. } => {
+            count_expr(lhs, count);
+            count_expr(rhs, count);
+        },
+
+        _ => {},
+    }
+}
+
+//
+// Estimates the heap taken by the arena: the two node vectors, the lists
+// inside the nodes, and the interned names
+//
+fn arena_bytes(file : &AstFile) -> usize {
+    let mut bytes = file.get_statements().capacity() * size_of::<AstStatement>()
+        + file.get_expressions().capacity() * size_of::<AstExpression>();
+
+    for func in file.get_functions() {
+        bytes += func.get_block().capacity() * size_of::<StmtRef>();
+    }
+
+    for stmt in file.get_statements() {
+        bytes += match stmt.get_kind() {
+            AstStatementKind::CallStmt { args, .. } => args.capacity() * size_of::<ExprRef>(),
+
+            AstStatementKind::While { block, .. }
+            | AstStatementKind::Loop { block, .. }
+            | AstStatementKind::Repeat { block, .. } => block.capacity() * size_of::<StmtRef>(),
+
+            AstStatementKind::If { branches, else_block } => {
+                let mut n = branches.capacity() * size_of::<AstBranch>();
+                for br in branches {
+                    n += br.get_block().capacity() * size_of::<StmtRef>();
+                }
+                n + else_block.as_ref().map_or(0, |block| block.capacity() * size_of::<StmtRef>())
+            },
+
+            AstStatementKind::Match { cases, default, .. } => {
+                let mut n = cases.capacity() * size_of::<AstCase>();
+                for case in cases {
+                    n += case.get_values().capacity() * size_of::<ExprRef>();
+                    n += case.get_block().capacity() * size_of::<StmtRef>();
+                }
+                n + default.as_ref().map_or(0, |block| block.capacity() * size_of::<StmtRef>())
+            },
+
+            _ => 0,
+        };
+    }
+
+    for expr in file.get_expressions() {
+        if let AstExpressionKind::Call { args, .. } = expr.get_kind() {
+            bytes += args.capacity() * size_of::<ExprRef>();
+        }
+    }
+
+    let names = file.get_names();
+    for i in 0 .. names.len() {
+        // Each name is kept in the list and as a key of the map
+        bytes += 2 * names.resolve(Symbol(i as u32)).len() + size_of::<String>() * 2 + size_of::<Symbol>();
+    }
+    bytes
+}
+
+//
+// Estimates the heap taken by the boxed tree: every node, and the
+// strings and vectors hanging off of it
+//
+fn block_bytes(block : &Vec<Stmt>) -> usize {
+    let mut bytes = block.capacity() * size_of::<Stmt>();
+    for stmt in block {
+        bytes += match stmt {
+            Stmt::Return(value) => value.as_ref().map_or(0, expr_bytes),
+            Stmt::VarDec { name, value, .. } => name.capacity() + value.as_ref().map_or(0, expr_bytes),
+            Stmt::ArrayDec { name, size, .. } => name.capacity() + expr_bytes(size),
+            Stmt::StructDec { name, struct_name } => name.capacity() + struct_name.capacity(),
+            Stmt::Call { name, args } => name.capacity() + list_bytes(args),
+            Stmt::Expr(expr) => expr_bytes(expr),
+            Stmt::While { label, cond, block } => label_bytes(label) + expr_bytes(cond) + block_bytes(block),
+            Stmt::Loop { label, block } => label_bytes(label) + block_bytes(block),
+            Stmt::Repeat { label, block, cond } => label_bytes(label) + block_bytes(block) + expr_bytes(cond),
+            Stmt::Break(label) | Stmt::Continue(label) => label_bytes(label),
+
+            Stmt::If { branches, else_block } => {
+                let mut n = branches.capacity() * size_of::<(Expr, Vec<Stmt>)>();
+                for (cond, block) in branches {
+                    n += expr_bytes(cond) + block_bytes(block);
+                }
+                n + else_block.as_ref().map_or(0, block_bytes)
+            },
+
+            Stmt::Match { expr, cases, default } => {
+                let mut n = expr_bytes(expr) + cases.capacity() * size_of::<(Vec<Expr>, Vec<Stmt>)>();
+                for (values, block) in cases {
+                    n += list_bytes(values) + block_bytes(block);
+                }
+                n + default.as_ref().map_or(0, block_bytes)
+            },
+        };
+    }
+    bytes
+}
+
+fn label_bytes(label : &Option<String>) -> usize {
+    label.as_ref().map_or(0, |label| label.capacity())
+}
+
+fn list_bytes(list : &Vec<Expr>) -> usize {
+    list.capacity() * size_of::<Expr>() + list.iter().map(expr_bytes).sum::<usize>()
+}
+
+// Children behind a box have a node of their own on the heap
+fn expr_bytes(expr : &Expr) -> usize {
+    match expr {
+        Expr::Id(name) | Expr::Str(name) => name.capacity(),
+        Expr::ArrayAcc { name, index } => name.capacity() + size_of::<Expr>() + expr_bytes(index),
+        Expr::StructAcc { name, member } => name.capacity() + member.capacity(),
+        Expr::Call { name, args } => name.capacity() + list_bytes(args),
+        Expr::Cast { expr, .. } => size_of::<Expr>() + expr_bytes(expr),
+
+        Expr::Binary { lhs, rhs, .. } | Expr::Assign { lhs, rhs, .. } => {
+            2 * size_of::<Expr>() + expr_bytes(lhs) + expr_bytes(rhs)
+        },
+
+        Expr::Int(_) | Expr::Char(_) | Expr::Bool(_) => 0,
+    }
+}
@@ -1,63 +1,85 @@
 //
 // Contains the AST for our python interpreter
 //
+// A file owns every node in it. The statements and expressions sit in two
+// vectors, and refer to their children by handle rather than owning them;
+// names and string literals are symbols from the interner the scanner
+// filled in, so nodes hold no strings of their own. The top level
+// declarations, and the lists of a node (blocks, arguments, branches and
+// cases), are kept in order in their parent.
+//
+use std::fmt;
+
+use crate::intern::*;
+use crate::visit::*;
 
 //
 // The type definitions
 //
-#[derive(Debug, Clone, PartialEq)]
-pub enum AstType {
-    None,
-
-    // Statements
-    Block,
-    Return,
-    VarDec,
-    ArrayDec,
-    StructDec,
-    CallStmt,
-    While,
-    If, Elif, Else,
-    ExprStmt,
-    Break, Continue,
-    
-    // Expressions
-    ExprList,
-    Call,
-    
-    // Expressions- operators
-    Assign,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataType {
+    Void,
+    I8, U8,
+    I16, U16,
+    I32, U32,
+    I64, U64,
+    String,
+    Char,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
     Mod,
     And, Or, Xor,
+    Lsh, Rsh,
     Eq, Ne, Gt, Lt, Ge, Le,
     LGAnd, LGOr,
-    
-    // Expressions- literals
-    Id,
-    ArrayAcc,
-    StructAcc,
-    IntLiteral,
-    CharLiteral,
-    StringLiteral,
-    BoolLiteral(bool),
+    Range,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum DataType {
-    Void,
-    I8, U8,
-    I16, U16,
-    I32, U32,
-    I64, U64,
-    String,
-    Char,
-    Bool,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssignOp {
+    Assign,
+    Add, Sub, Mul, Div, Mod,
+    And, Or, Xor,
+    Lsh, Rsh,
+}
+
+//
+// Where a node came from in the source file
+// Lines and columns count from 1, and the end is just past the last character
+//
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub line : usize,
+    pub col : usize,
+    pub end_line : usize,
+    pub end_col : usize,
 }
 
+//
+// Every node gets its own ID from the parser, so later passes can keep
+// what they find out about a node in a side table
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NodeId(pub u32);
+
+//
+// The handles nodes refer to their children by; a handle is an index into
+// the statements or expressions of the file that made it, and means nothing
+// in any other file
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtRef(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprRef(pub u32);
+
 #[derive(Clone)]
 pub struct AstFile {
     name : String,
@@ -65,531 +87,896 @@ pub struct AstFile {
     structs : Vec<AstStruct>,
     consts : Vec<AstArg>,
     functions : Vec<AstFunction>,
+
+    // Every statement and expression of the file, and the names they use
+    statements : Vec<AstStatement>,
+    expressions : Vec<AstExpression>,
+    names : Interner,
+    next_id : u32,
 }
 
 #[derive(Clone)]
 pub struct AstStruct {
-    name : String,
+    id : NodeId,
+    span : Span,
+    name : Symbol,
     items : Vec<AstArg>,
 }
 
 #[derive(Clone)]
 pub struct AstFunction {
-    name : String,
+    id : NodeId,
+    span : Span,
+    name : Symbol,
     data_type : DataType,
-    block : AstStatement,
+    block : Vec<StmtRef>,
     args : Vec<AstArg>,
     consts : Vec<AstArg>,
 }
 
 #[derive(Clone)]
 pub struct AstArg {
-    name : String,
+    id : NodeId,
+    span : Span,
+    name : Symbol,
     data_type : DataType,
-    expr : AstExpression,       // Only for structures
+    expr : Option<ExprRef>,             // Constant value or structure default
+}
+
+#[derive(Clone, PartialEq)]
+pub struct AstBranch {
+    cond : ExprRef,
+    block : Vec<StmtRef>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct AstCase {
+    values : Vec<ExprRef>,
+    block : Vec<StmtRef>,
 }
 
 #[derive(Clone)]
 pub struct AstStatement {
-    ast_type : AstType,
-    name : String,
-    data_type : DataType,
-    expr : AstExpression,
-    statements : Vec<AstStatement>,     // For blocks
-    branches : Vec<AstStatement>,       // For conditionals
+    id : NodeId,
+    span : Span,
+    kind : AstStatementKind,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AstStatementKind {
+    Return(Option<ExprRef>),
+    VarDec { name : Symbol, data_type : DataType, value : Option<ExprRef> },
+    ArrayDec { name : Symbol, data_type : DataType, size : ExprRef },
+    StructDec { name : Symbol, struct_name : Symbol },
+    CallStmt { name : Symbol, args : Vec<ExprRef> },
+    ExprStmt(ExprRef),
+
+    // Loops; the label is what break and continue refer to
+    While { label : Option<Symbol>, cond : ExprRef, block : Vec<StmtRef> },
+    Loop { label : Option<Symbol>, block : Vec<StmtRef> },
+    Repeat { label : Option<Symbol>, block : Vec<StmtRef>, cond : ExprRef },
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+
+    // Conditionals; the first branch of an if is the if itself, the rest are elifs
+    If { branches : Vec<AstBranch>, else_block : Option<Vec<StmtRef>> },
+    Match { expr : ExprRef, cases : Vec<AstCase>, default : Option<Vec<StmtRef>> },
 }
 
 #[derive(Clone)]
 pub struct AstExpression {
-    ast_type : AstType,
-    int_value : u64,
-    char_value : char,
-    string_value : String,
-    
-    // This should only be used by an expression list
-    list : Vec<AstExpression>,
-    
-    // Only for binary operators
-    args : Vec<AstExpression>,
+    id : NodeId,
+    span : Span,
+    kind : AstExpressionKind,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AstExpressionKind {
+    // Literals
+    Id(Symbol),
+    IntLiteral(u64),
+    CharLiteral(char),
+    StringLiteral(Symbol),
+    BoolLiteral(bool),
+
+    ArrayAcc { name : Symbol, index : ExprRef },
+    StructAcc { name : Symbol, member : Symbol },
+    Call { name : Symbol, args : Vec<ExprRef> },
+    Cast { data_type : DataType, expr : ExprRef },
+
+    // Operators
+    Binary { op : BinaryOp, lhs : ExprRef, rhs : ExprRef },
+    Assign { op : AssignOp, lhs : ExprRef, rhs : ExprRef },
 }
 
 //
 // Function implementations for the structuress
 //
-impl AstFile {
-    pub fn print(&self) {
-        println!("FILE {}", self.name);
-        println!("");
-        
-        for i in &self.imports {
-            println!("import {};", i);
+impl Span {
+    // Returns a span from the start of this one to the end of the other
+    pub fn to(&self, other : Span) -> Span {
+        Span {
+            line : self.line,
+            col : self.col,
+            end_line : other.end_line,
+            end_col : other.end_col,
         }
-        
-        for s in &self.structs {
-            s.print();
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+impl DataType {
+    // The name of the type in source
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::Void => "void",
+            DataType::I8 => "i8",
+            DataType::U8 => "u8",
+            DataType::I16 => "i16",
+            DataType::U16 => "u16",
+            DataType::I32 => "i32",
+            DataType::U32 => "u32",
+            DataType::I64 => "i64",
+            DataType::U64 => "u64",
+            DataType::String => "string",
+            DataType::Char => "char",
+            DataType::Bool => "bool",
         }
-        println!("");
-        
-        for c in &self.consts {
-            print!("CONST ");
-            c.print();
-            println!("");
+    }
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Lsh => "<<",
+            BinaryOp::Rsh => ">>",
+
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Lt => "<",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Le => "<=",
+
+            BinaryOp::LGAnd => "&&",
+            BinaryOp::LGOr => "||",
+
+            BinaryOp::Range => "..",
         }
-        println!("");
-        
-        for func in &self.functions {
-            func.print();
+    }
+}
+
+impl AssignOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            AssignOp::Assign => ":=",
+            AssignOp::Add => "+=",
+            AssignOp::Sub => "-=",
+            AssignOp::Mul => "*=",
+            AssignOp::Div => "/=",
+            AssignOp::Mod => "%=",
+            AssignOp::And => "&=",
+            AssignOp::Or => "|=",
+            AssignOp::Xor => "^=",
+            AssignOp::Lsh => "<<=",
+            AssignOp::Rsh => ">>=",
         }
     }
-    
+}
+
+impl AstFile {
+    pub fn print(&self) {
+        AstPrinter { indent : 0 }.visit_file(self);
+    }
+
     //
     // Setter functions
     //
     pub fn add_import(&mut self, path : String) {
         self.imports.push(path);
     }
-    
+
     pub fn add_struct(&mut self, s : AstStruct) {
         self.structs.push(s);
     }
-    
+
     pub fn add_const(&mut self, c : AstArg) {
         self.consts.push(c);
     }
-    
+
     pub fn add_function(&mut self, func : AstFunction) {
         self.functions.push(func);
     }
-    
+
+    // Moves a statement into the file, and returns its handle
+    pub fn add_statement(&mut self, stmt : AstStatement) -> StmtRef {
+        self.statements.push(stmt);
+        StmtRef(self.statements.len() as u32 - 1)
+    }
+
+    pub fn add_expression(&mut self, expr : AstExpression) -> ExprRef {
+        self.expressions.push(expr);
+        ExprRef(self.expressions.len() as u32 - 1)
+    }
+
+    // Returns the symbol for a name in this file
+    pub fn intern(&mut self, name : &str) -> Symbol {
+        self.names.intern(name)
+    }
+
+    // The parser hands over the names the scanner found
+    pub fn set_names(&mut self, names : Interner) {
+        self.names = names;
+    }
+
+    // Hands out the next node ID; IDs keep counting across files
+    // when the first ID is set to where the last file stopped
+    pub fn new_id(&mut self) -> NodeId {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    pub fn set_next_id(&mut self, next_id : u32) {
+        self.next_id = next_id;
+    }
+
     //
     // Getter functions
     //
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
     pub fn get_imports(&self) -> &Vec<String> {
         &self.imports
     }
-    
+
     pub fn get_structs(&self) -> &Vec<AstStruct> {
         &self.structs
     }
-    
+
     pub fn get_consts(&self) -> &Vec<AstArg> {
         &self.consts
     }
-    
+
     pub fn get_functions(&self) -> &Vec<AstFunction> {
         &self.functions
     }
+
+    pub fn get_structs_mut(&mut self) -> &mut Vec<AstStruct> {
+        &mut self.structs
+    }
+
+    pub fn get_consts_mut(&mut self) -> &mut Vec<AstArg> {
+        &mut self.consts
+    }
+
+    pub fn get_functions_mut(&mut self) -> &mut Vec<AstFunction> {
+        &mut self.functions
+    }
+
+    pub fn get_statement(&self, stmt : StmtRef) -> &AstStatement {
+        &self.statements[stmt.0 as usize]
+    }
+
+    pub fn get_expression(&self, expr : ExprRef) -> &AstExpression {
+        &self.expressions[expr.0 as usize]
+    }
+
+    pub fn get_statement_mut(&mut self, stmt : StmtRef) -> &mut AstStatement {
+        &mut self.statements[stmt.0 as usize]
+    }
+
+    pub fn get_expression_mut(&mut self, expr : ExprRef) -> &mut AstExpression {
+        &mut self.expressions[expr.0 as usize]
+    }
+
+    // Every statement and expression, in the order they were added
+    pub fn get_statements(&self) -> &Vec<AstStatement> {
+        &self.statements
+    }
+
+    pub fn get_expressions(&self) -> &Vec<AstExpression> {
+        &self.expressions
+    }
+
+    pub fn get_names(&self) -> &Interner {
+        &self.names
+    }
+
+    // The name or string a symbol of this file stands for
+    pub fn get_str(&self, sym : Symbol) -> &str {
+        self.names.resolve(sym)
+    }
+
+    pub fn get_next_id(&self) -> u32 {
+        self.next_id
+    }
 }
 
 impl AstStruct {
-    pub fn print(&self) {
-        println!("struct {} is", self.name);
-        for item in &self.items {
-            print!("  ");
-            item.print();
-            println!("");
-        }
-        println!("end");
+    pub fn print(&self, file : &AstFile) {
+        AstPrinter { indent : 0 }.visit_struct(file, self);
     }
-    
+
     //
     // Setter functions
     //
     pub fn add_item(&mut self, item : AstArg) {
         self.items.push(item);
     }
-    
+
+    pub fn set_span(&mut self, span : Span) {
+        self.span = span;
+    }
+
+    pub fn set_id(&mut self, id : NodeId) {
+        self.id = id;
+    }
+
     //
     // Getter functions
     //
-    pub fn get_name(&self) -> String {
-        self.name.clone()
+    pub fn get_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-    
+
+    pub fn get_name(&self) -> Symbol {
+        self.name
+    }
+
     pub fn get_items(&self) -> &Vec<AstArg> {
         &self.items
     }
+
+    pub fn get_items_mut(&mut self) -> &mut Vec<AstArg> {
+        &mut self.items
+    }
 }
 
 impl AstFunction {
-    pub fn print(&self) {
-        print!("func {}(", self.name);
-        for arg in &self.args {
-            arg.print();
-            print!(", ");
-        }
-        println!(") -> {:?} is", self.data_type);
-        for c in &self.consts {
-            print!("  CONST ");
-            c.print();
-            println!("");
-        }
-        for stmt in &self.block.statements {
-            stmt.print(2);
-        }
-        println!("end");
+    pub fn print(&self, file : &AstFile) {
+        AstPrinter { indent : 0 }.visit_function(file, self);
     }
-    
+
     //
     // Setter functions
     //
-    pub fn set_block(&mut self, block : AstStatement) {
+    pub fn set_block(&mut self, block : Vec<StmtRef>) {
         self.block = block;
     }
-    
+
     pub fn set_data_type(&mut self, data_type : DataType) {
         self.data_type = data_type;
     }
-    
+
     pub fn add_arg(&mut self, arg : AstArg) {
         self.args.push(arg);
     }
-    
+
     pub fn add_const(&mut self, c : AstArg) {
         self.consts.push(c);
     }
-    
+
+    pub fn set_span(&mut self, span : Span) {
+        self.span = span;
+    }
+
+    pub fn set_id(&mut self, id : NodeId) {
+        self.id = id;
+    }
+
     //
     // Getter functions
     //
-    pub fn get_name(&self) -> String {
-        self.name.clone()
+    pub fn get_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-    
+
+    pub fn get_name(&self) -> Symbol {
+        self.name
+    }
+
     pub fn get_data_type(&self) -> DataType {
-        self.data_type.clone()
+        self.data_type
     }
-    
+
     pub fn get_args(&self) -> &Vec<AstArg> {
         &self.args
     }
-    
+
     pub fn get_consts(&self) -> &Vec<AstArg> {
         &self.consts
     }
-    
-    pub fn get_block(&self) -> &AstStatement {
+
+    pub fn get_block(&self) -> &Vec<StmtRef> {
         &self.block
     }
+
+    pub fn get_args_mut(&mut self) -> &mut Vec<AstArg> {
+        &mut self.args
+    }
+
+    pub fn get_consts_mut(&mut self) -> &mut Vec<AstArg> {
+        &mut self.consts
+    }
+
+    pub fn get_block_mut(&mut self) -> &mut Vec<StmtRef> {
+        &mut self.block
+    }
 }
 
 impl AstArg {
-    pub fn print(&self) {
-        print!("{} : {:?}", self.name, self.data_type);
-        if self.expr.get_type() != AstType::None {
-            print!(" -> ");
-            self.expr.print();
-            print!(";");
-        }
+    pub fn print(&self, file : &AstFile) {
+        AstPrinter { indent : 0 }.print_arg(file, self);
     }
-    
+
     //
     // Setter functions
     //
-    pub fn set_expression(&mut self, expr : AstExpression) {
-        self.expr = expr;
+    pub fn set_expression(&mut self, expr : ExprRef) {
+        self.expr = Some(expr);
+    }
+
+    pub fn set_span(&mut self, span : Span) {
+        self.span = span;
+    }
+
+    pub fn set_id(&mut self, id : NodeId) {
+        self.id = id;
     }
-    
+
     //
     // Getter functions
     //
-    pub fn get_name(&self) -> String {
-        self.name.clone()
+    pub fn get_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-    
+
+    pub fn get_name(&self) -> Symbol {
+        self.name
+    }
+
     pub fn get_data_type(&self) -> DataType {
-        self.data_type.clone()
+        self.data_type
     }
-    
-    pub fn get_expression(&self) -> &AstExpression {
-        &self.expr
+
+    pub fn get_expression(&self) -> Option<ExprRef> {
+        self.expr
     }
 }
 
-impl AstStatement {
-    pub fn print(&self, index : i32) {
-        if self.ast_type == AstType::Block {
-            for stmt in &self.statements {
-                for _i in 0 .. index { print!(" "); }
-                stmt.print(index);
-            }
-            //for _i in 0 .. index { print!(" "); }
-            //println!("end");
-        } else {
-            for _i in 0 .. index {
-                print!(" ");
-            }
-            print!("{:?} {:?} {} ", self.ast_type, self.data_type, self.name);
-            
-            //if self.expr.ast_type != AstType::None {
-                self.expr.print();
-            //}
-            
-            println!("");
-            
-            match &self.ast_type {
-                AstType::While => {
-                    let block = self.get_block();
-                    block.print(index+2);
-                    for _i in 0 .. index { print!(" "); }
-                    println!("end");
-                },
-                
-                AstType::If | AstType::Elif | AstType::Else => {
-                    let block = self.get_block();
-                    block.print(index+2);
-                    for br in self.get_branches() {
-                        br.print(index);
-                    }
-                    if self.ast_type == AstType::If {
-                        for _i in 0 .. index { print!(" "); }
-                        println!("end");
-                    }
-                },
-                _ => {},
-            }
-        }
+impl AstBranch {
+    //
+    // Getter functions
+    //
+    pub fn get_cond(&self) -> ExprRef {
+        self.cond
     }
-    
+
+    pub fn get_block(&self) -> &Vec<StmtRef> {
+        &self.block
+    }
+
+    pub fn get_block_mut(&mut self) -> &mut Vec<StmtRef> {
+        &mut self.block
+    }
+}
+
+impl AstCase {
+    //
+    // Getter functions
+    //
+    pub fn get_values(&self) -> &Vec<ExprRef> {
+        &self.values
+    }
+
+    pub fn get_block(&self) -> &Vec<StmtRef> {
+        &self.block
+    }
+
+    pub fn get_values_mut(&mut self) -> &mut Vec<ExprRef> {
+        &mut self.values
+    }
+
+    pub fn get_block_mut(&mut self) -> &mut Vec<StmtRef> {
+        &mut self.block
+    }
+}
+
+impl AstStatement {
     //
     // Setter functions
     //
-    pub fn set_name(&mut self, name : String) {
-        self.name = name;
+    pub fn set_id(&mut self, id : NodeId) {
+        self.id = id;
     }
-    
-    pub fn set_data_type(&mut self, data_type : DataType) {
-        self.data_type = data_type;
+
+    //
+    // Getter functions
+    //
+    pub fn get_id(&self) -> NodeId {
+        self.id
     }
-    
-    pub fn set_expression(&mut self, expr : AstExpression) {
-        self.expr = expr;
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-    
-    pub fn add_statement(&mut self, stmt : AstStatement) {
-        self.statements.push(stmt);
+
+    pub fn get_kind(&self) -> &AstStatementKind {
+        &self.kind
     }
-    
-    pub fn add_sub_block(&mut self, stmt : AstStatement) {
-        self.statements.push(stmt);
+
+    pub fn get_kind_mut(&mut self) -> &mut AstStatementKind {
+        &mut self.kind
     }
-    
-    pub fn add_branch(&mut self, stmt : AstStatement) {
-        self.branches.push(stmt);
+
+    pub fn print(&self, file : &AstFile, index : i32) {
+        AstPrinter { indent : index }.visit_statement(file, self);
+    }
+}
+
+impl AstExpression {
+    //
+    // Setter functions
+    //
+    pub fn set_id(&mut self, id : NodeId) {
+        self.id = id;
     }
-    
+
     //
     // Getter functions
     //
-    pub fn get_type(&self) -> AstType {
-        self.ast_type.clone()
+    pub fn get_id(&self) -> NodeId {
+        self.id
     }
-    
-    pub fn get_name(&self) -> String {
-        self.name.clone()
+
+    pub fn get_span(&self) -> Span {
+        self.span
     }
-    
-    pub fn get_data_type(&self) -> DataType {
-        self.data_type.clone()
+
+    pub fn get_kind(&self) -> &AstExpressionKind {
+        &self.kind
     }
-    
-    pub fn get_expression(&self) -> &AstExpression {
-        &self.expr
+
+    pub fn get_kind_mut(&mut self) -> &mut AstExpressionKind {
+        &mut self.kind
     }
-    
-    pub fn get_statements(&self) -> &Vec<AstStatement> {
-        &self.statements
+
+    pub fn print(&self, file : &AstFile) {
+        AstPrinter { indent : 0 }.visit_expression(file, self);
+    }
+}
+
+//
+// Prints the tree in our debug format
+//
+struct AstPrinter {
+    indent : i32,
+}
+
+impl AstPrinter {
+    fn print_indent(&self, extra : i32) {
+        for _i in 0 .. self.indent + extra {
+            print!(" ");
+        }
     }
-    
-    pub fn get_block(&self) -> &AstStatement {
-        &self.statements[0]
+
+    fn print_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        print!("{} : {:?}", file.get_str(arg.name), arg.data_type);
+        if let Some(expr) = arg.expr {
+            print!(" -> ");
+            self.visit_expression(file, file.get_expression(expr));
+            print!(";");
+        }
+    }
+
+    fn print_nested(&mut self, file : &AstFile, block : &Vec<StmtRef>, extra : i32) {
+        self.indent += extra;
+        self.visit_block(file, block);
+        self.indent -= extra;
     }
-    
-    pub fn get_branches(&self) -> &Vec<AstStatement> {
-        &self.branches
+
+    fn print_list(&mut self, file : &AstFile, list : &Vec<ExprRef>) {
+        print!("{{");
+        let mut index : usize = 0;
+        for item in list {
+            self.visit_expression(file, file.get_expression(*item));
+            if index + 1 < list.len() {
+                print!(", ");
+            }
+            index += 1;
+        }
+        print!("}}");
+    }
+
+    // Labels print like the strings they used to be: None or Some("name")
+    fn label<'a>(&self, file : &'a AstFile, label : &Option<Symbol>) -> Option<&'a str> {
+        label.map(|l| file.get_str(l))
     }
 }
 
-impl AstExpression {
-    pub fn print(&self) {
-        match self.ast_type {
-            //
-            // Generic expressions
-            //
-            AstType::ExprList => {
-                print!("{{");
-                let mut index : usize = 0;
-                for item in &self.list {
-                    item.print();
-                    if index + 1 < self.list.len() {
-                        print!(", ");
+impl Visitor for AstPrinter {
+    fn visit_file(&mut self, file : &AstFile) {
+        println!("FILE {}", file.name);
+        println!("");
+
+        for i in &file.imports {
+            self.visit_import(file, i);
+        }
+
+        for s in &file.structs {
+            self.visit_struct(file, s);
+        }
+        println!("");
+
+        for c in &file.consts {
+            self.visit_const(file, c);
+        }
+        println!("");
+
+        for func in &file.functions {
+            self.visit_function(file, func);
+        }
+    }
+
+    fn visit_import(&mut self, _file : &AstFile, path : &str) {
+        println!("import {};", path);
+    }
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        println!("struct {} is", file.get_str(s.name));
+        walk_struct(self, file, s);
+        println!("end");
+    }
+
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        print!("  ");
+        self.print_arg(file, item);
+        println!("");
+    }
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        self.print_arg(file, arg);
+        print!(", ");
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.print_indent(0);
+        print!("CONST ");
+        self.print_arg(file, c);
+        println!("");
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        print!("func {}(", file.get_str(func.name));
+        for arg in &func.args {
+            self.visit_arg(file, arg);
+        }
+        println!(") -> {:?} is", func.data_type);
+
+        self.indent += 2;
+        for c in &func.consts {
+            self.visit_const(file, c);
+        }
+        self.visit_block(file, &func.block);
+        self.indent -= 2;
+        println!("end");
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        self.print_indent(0);
+
+        match &stmt.kind {
+            AstStatementKind::Return(expr) => {
+                print!("Return ");
+                if let Some(expr) = expr {
+                    self.visit_expression(file, file.get_expression(*expr));
+                }
+                println!("");
+            },
+
+            AstStatementKind::VarDec { name, data_type, value } => {
+                print!("VarDec {:?} {} ", data_type, file.get_str(*name));
+                if let Some(value) = value {
+                    self.visit_expression(file, file.get_expression(*value));
+                }
+                println!("");
+            },
+
+            AstStatementKind::ArrayDec { name, data_type, size } => {
+                print!("ArrayDec {:?} {} ", data_type, file.get_str(*name));
+                self.visit_expression(file, file.get_expression(*size));
+                println!("");
+            },
+
+            AstStatementKind::StructDec { name, struct_name } => {
+                println!("StructDec {} {}", file.get_str(*struct_name), file.get_str(*name));
+            },
+
+            AstStatementKind::CallStmt { name, args } => {
+                print!("CallStmt {} ", file.get_str(*name));
+                self.print_list(file, args);
+                println!("");
+            },
+
+            AstStatementKind::ExprStmt(expr) => {
+                print!("ExprStmt ");
+                self.visit_expression(file, file.get_expression(*expr));
+                println!("");
+            },
+
+            AstStatementKind::While { label, cond, block } => {
+                print!("While {:?} ", self.label(file, label));
+                self.visit_expression(file, file.get_expression(*cond));
+                println!("");
+                self.print_nested(file, block, 2);
+                self.print_indent(0);
+                println!("end");
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                println!("Loop {:?}", self.label(file, label));
+                self.print_nested(file, block, 2);
+                self.print_indent(0);
+                println!("end");
+            },
+
+            AstStatementKind::Repeat { label, block, cond } => {
+                println!("Repeat {:?}", self.label(file, label));
+                self.print_nested(file, block, 2);
+                self.print_indent(0);
+                print!("until ");
+                self.visit_expression(file, file.get_expression(*cond));
+                println!("");
+            },
+
+            AstStatementKind::Break(label) => println!("Break {:?}", self.label(file, label)),
+            AstStatementKind::Continue(label) => println!("Continue {:?}", self.label(file, label)),
+
+            AstStatementKind::If { branches, else_block } => {
+                let mut first = true;
+                for br in branches {
+                    if !first {
+                        self.print_indent(0);
+                        print!("Elif ");
+                    } else {
+                        print!("If ");
                     }
-                    index += 1;
+                    self.visit_expression(file, file.get_expression(br.cond));
+                    println!("");
+                    self.print_nested(file, &br.block, 2);
+                    first = false;
+                }
+
+                if let Some(block) = else_block {
+                    self.print_indent(0);
+                    println!("Else");
+                    self.print_nested(file, block, 2);
+                }
+
+                self.print_indent(0);
+                println!("end");
+            },
+
+            AstStatementKind::Match { expr, cases, default } => {
+                print!("Match ");
+                self.visit_expression(file, file.get_expression(*expr));
+                println!("");
+
+                for case in cases {
+                    self.print_indent(2);
+                    print!("Case ");
+                    self.print_list(file, &case.values);
+                    println!("");
+                    self.print_nested(file, &case.block, 4);
                 }
-                print!("}}");
+
+                if let Some(block) = default {
+                    self.print_indent(2);
+                    println!("Else");
+                    self.print_nested(file, block, 4);
+                }
+
+                self.print_indent(0);
+                println!("end");
             },
-            
-            AstType::Call => {
-                print!("{}(", self.string_value);
-                self.args[0].print();
+        }
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        match &expr.kind {
+            AstExpressionKind::Call { name, args } => {
+                print!("{}(", file.get_str(*name));
+                self.print_list(file, args);
                 print!(")");
             },
-            
+
+            AstExpressionKind::Cast { data_type, expr } => {
+                print!("CAST(");
+                self.visit_expression(file, file.get_expression(*expr));
+                print!(" as {:?})", data_type);
+            },
+
             //
-            // Binary operators
+            // Operators
             //
-            AstType::Assign
-            | AstType::Add | AstType::Sub
-            | AstType::Mul | AstType::Div | AstType::Mod
-            | AstType::And | AstType::Or | AstType::Xor 
-            | AstType::Eq | AstType::Ne
-            | AstType::Gt | AstType::Ge | AstType::Lt | AstType::Le
-            | AstType::LGAnd | AstType::LGOr => {
+            AstExpressionKind::Binary { op, lhs, rhs } => {
                 print!("(");
-                self.args[0].print();
-                match self.ast_type {
-                    AstType::Assign => print!(" := "),
-                
-                    AstType::Add => print!(" + "),
-                    AstType::Sub => print!(" - "),
-                    AstType::Mul => print!(" * "),
-                    AstType::Div => print!(" / "),
-                    AstType::Mod => print!(" % "),
-                    AstType::And => print!(" & "),
-                    AstType::Or => print!(" | "),
-                    AstType::Xor => print!(" ^ "),
-                    
-                    AstType::Eq => print!(" = "),
-                    AstType::Ne => print!(" != "),
-                    AstType::Gt => print!(" > "),
-                    AstType::Ge => print!(" >= "),
-                    AstType::Lt => print!(" < "),
-                    AstType::Le => print!(" <= "),
-                    
-                    AstType::LGAnd => print!(" && "),
-                    AstType::LGOr => print!(" || "),
-                    
-                    _ => {},
-                }
-                self.args[1].print();
+                self.visit_expression(file, file.get_expression(*lhs));
+                print!(" {} ", op.symbol());
+                self.visit_expression(file, file.get_expression(*rhs));
+                print!(")");
+            },
+
+            AstExpressionKind::Assign { op, lhs, rhs } => {
+                print!("(");
+                self.visit_expression(file, file.get_expression(*lhs));
+                print!(" {} ", op.symbol());
+                self.visit_expression(file, file.get_expression(*rhs));
                 print!(")");
             },
-            
+
             //
             // Literal expressions
             //
-            AstType::Id => {
-                print!("ID({})", self.string_value);
+            AstExpressionKind::Id(name) => {
+                print!("ID({})", file.get_str(*name));
             }
-            
-            AstType::ArrayAcc => {
-                print!("AC({})[", self.string_value);
-                self.args[0].print();
+
+            AstExpressionKind::ArrayAcc { name, index } => {
+                print!("AC({})[", file.get_str(*name));
+                self.visit_expression(file, file.get_expression(*index));
                 print!("]");
             }
-            
-            AstType::StructAcc => {
-                print!("SA({}).", self.string_value);
-                self.args[0].print();
+
+            AstExpressionKind::StructAcc { name, member } => {
+                print!("SA({}).ID({})", file.get_str(*name), file.get_str(*member));
             },
-            
-            AstType::IntLiteral => {
-                print!("{}", self.int_value);
+
+            AstExpressionKind::IntLiteral(val) => {
+                print!("{}", val);
             },
-            
-            AstType::CharLiteral => {
-                print!("\'{:?}\'", self.char_value);
+
+            AstExpressionKind::CharLiteral(val) => {
+                print!("\'{:?}\'", val);
             },
-            
-            AstType::StringLiteral => {
-                print!("{:?}", self.string_value);
+
+            AstExpressionKind::StringLiteral(val) => {
+                print!("{:?}", file.get_str(*val));
+            },
+
+            AstExpressionKind::BoolLiteral(val) => {
+                print!("{}", val);
             },
-            
-            _ => { print!("??-> {:?}", self.ast_type); },
-        }
-    }
-    
-    //
-    // Setter functions
-    //
-    pub fn set_arg(&mut self, item : AstExpression) {
-        self.args.push(item);
-    }
-    
-    pub fn set_lval(&mut self, item : AstExpression) {
-        if self.args.len() > 0 {
-            self.args.insert(0, item);
-        } else {
-            self.args.push(item);
         }
     }
-    
-    pub fn set_rval(&mut self, item : AstExpression) {
-        self.args.push(item);
-    }
-    
-    pub fn add_list_item(&mut self, item : AstExpression) {
-        self.list.push(item);
-    }
-    
-    pub fn set_int(&mut self, value : u64) {
-        self.int_value = value;
-    }
-    
-    pub fn set_char(&mut self, value : char) {
-        self.char_value = value;
-    }
-    
-    pub fn set_string(&mut self, value : String) {
-        self.string_value = value;
-    }
-    
-    pub fn set_name(&mut self, value : String) {
-        self.string_value = value;
-    }
-    
-    //
-    // Getter functions
-    //
-    pub fn get_type(&self) -> AstType {
-        self.ast_type.clone()
-    }
-    
-    pub fn get_arg(&self) -> &AstExpression {
-        &self.args[0]
-    }
-    
-    pub fn get_lval(&self) -> &AstExpression {
-        &self.args[0]
-    }
-    
-    pub fn get_rval(&self) -> &AstExpression {
-        &self.args[1]
-    }
-    
-    pub fn get_list(&self) -> &Vec<AstExpression> {
-        &self.list
-    }
-    
-    pub fn get_list_size(&self) -> usize {
-        self.list.len()
-    }
-    
-    pub fn get_int(&self) -> u64 {
-        self.int_value
-    }
-    
-    pub fn get_char(&self) -> char {
-        self.char_value
-    }
-    
-    pub fn get_string(&self) -> String {
-        self.string_value.clone()
-    }
-    
-    pub fn get_name(&self) -> String {
-        self.string_value.clone()
-    }
 }
 
 //
@@ -602,53 +989,70 @@ pub fn ast_new_file(name : String) -> AstFile {
         structs : Vec::new(),
         consts : Vec::new(),
         functions : Vec::new(),
+        statements : Vec::new(),
+        expressions : Vec::new(),
+        names : interner_new(),
+        next_id : 0,
     }
 }
 
-pub fn ast_new_struct(name : String) -> AstStruct {
+pub fn ast_new_struct(name : Symbol, id : NodeId) -> AstStruct {
     AstStruct {
+        id : id,
+        span : Span::default(),
         name : name,
         items : Vec::new(),
     }
 }
 
-pub fn ast_new_function(name : String) -> AstFunction {
+pub fn ast_new_function(name : Symbol, id : NodeId) -> AstFunction {
     AstFunction {
+        id : id,
+        span : Span::default(),
         name : name,
         data_type : DataType::Void,
-        block : ast_new_statement(AstType::Block),
+        block : Vec::new(),
         args : Vec::new(),
         consts : Vec::new(),
     }
 }
 
-pub fn ast_new_arg(name : String, data_type : DataType) -> AstArg {
+pub fn ast_new_arg(name : Symbol, data_type : DataType, id : NodeId) -> AstArg {
     AstArg {
+        id : id,
+        span : Span::default(),
         name : name,
         data_type : data_type,
-        expr : ast_new_expression(AstType::None),
+        expr : None,
+    }
+}
+
+pub fn ast_new_branch(cond : ExprRef, block : Vec<StmtRef>) -> AstBranch {
+    AstBranch {
+        cond : cond,
+        block : block,
+    }
+}
+
+pub fn ast_new_case(values : Vec<ExprRef>, block : Vec<StmtRef>) -> AstCase {
+    AstCase {
+        values : values,
+        block : block,
     }
 }
 
-pub fn ast_new_statement(ast_type : AstType) -> AstStatement {
+pub fn ast_new_statement(kind : AstStatementKind, span : Span, id : NodeId) -> AstStatement {
     AstStatement {
-        ast_type : ast_type,
-        name : String::new(),
-        data_type : DataType::Void,
-        expr : ast_new_expression(AstType::None),
-        statements : Vec::new(),
-        branches : Vec::new(),
+        id : id,
+        span : span,
+        kind : kind,
     }
 }
 
-pub fn ast_new_expression(ast_type : AstType) -> AstExpression {
+pub fn ast_new_expression(kind : AstExpressionKind, span : Span, id : NodeId) -> AstExpression {
     AstExpression {
-        ast_type : ast_type,
-        int_value : 0,
-        char_value : 0 as char,
-        string_value : String::new(),
-        list : Vec::new(),
-        args : Vec::new(),
+        id : id,
+        span : span,
+        kind : kind,
     }
 }
-
@@ -0,0 +1,791 @@
+//! A builder for generating Tiny Lang programmatically
+//!
+//! The constructors here only take what a node needs, so a tree built with
+//! them always has the right shape. What the types can't rule out (a keyword
+//! used as a name, a break outside of a loop, an assignment in the middle of an
+//! expression) is checked when the file is built, so whatever comes out of
+//! `FileBuilder::build` can be unwritten as valid source.
+//!
+//! Statements and expressions are descriptions of a node; they are only
+//! added to the file, and their names interned into it, when the file is
+//! built. Nodes get their IDs in the order a visitor would walk them.
+//!
+//! ```
+//! use tlc::ast::{BinaryOp, DataType};
+//! use tlc::build::*;
+//!
+//! let source = file("gen.tl")
+//!     .function(func("add")
+//!         .arg("a", DataType::I32)
+//!         .arg("b", DataType::I32)
+//!         .returns(DataType::I32)
+//!         .body(vec![ret(bin(BinaryOp::Add, id("a"), id("b")))]))
+//!     .function(func("main")
+//!         .returns(DataType::I32)
+//!         .body(vec![
+//!             var("x", DataType::I32, call("add", vec![int(1), int(2)])),
+//!             ret(id("x")),
+//!         ]))
+//!     .to_source()
+//!     .unwrap();
+//!
+//! assert!(source.contains("func add(a : i32, b : i32) -> i32 is"));
+//! assert!(source.contains("    var x : i32 := add(1, 2);"));
+//! ```
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::intern::Symbol;
+use crate::lex::{keyword, Token};
+use crate::unwriter;
+use crate::visit::*;
+
+//
+// A statement or expression still to be added to a file
+//
+pub struct Stmt(Box<dyn FnOnce(&mut AstFile) -> StmtRef>);
+
+pub struct Expr(Box<dyn FnOnce(&mut AstFile) -> ExprRef>);
+
+impl Stmt {
+    fn add(self, file : &mut AstFile) -> StmtRef {
+        (self.0)(file)
+    }
+}
+
+impl Expr {
+    fn add(self, file : &mut AstFile) -> ExprRef {
+        (self.0)(file)
+    }
+}
+
+fn add_block(file : &mut AstFile, block : Vec<Stmt>) -> Vec<StmtRef> {
+    block.into_iter().map(|stmt| stmt.add(file)).collect()
+}
+
+fn add_list(file : &mut AstFile, list : Vec<Expr>) -> Vec<ExprRef> {
+    list.into_iter().map(|expr| expr.add(file)).collect()
+}
+
+//
+// Files
+// The declarations are kept apart until the file is built, so they are
+// numbered in the order they sit in the file
+//
+pub struct FileBuilder {
+    file : AstFile,
+    structs : Vec<StructBuilder>,
+    consts : Vec<ArgBuilder>,
+    functions : Vec<FuncBuilder>,
+}
+
+pub fn file(name : &str) -> FileBuilder {
+    FileBuilder {
+        file : ast_new_file(name.to_string()),
+        structs : Vec::new(),
+        consts : Vec::new(),
+        functions : Vec::new(),
+    }
+}
+
+impl FileBuilder {
+    // Takes the module path as written in source ("std.io")
+    pub fn import(mut self, path : &str) -> Self {
+        self.file.add_import(path.replace('.', "/"));
+        self
+    }
+
+    pub fn structure(mut self, s : StructBuilder) -> Self {
+        self.structs.push(s);
+        self
+    }
+
+    pub fn constant(mut self, name : &str, data_type : DataType, value : Expr) -> Self {
+        self.consts.push(new_arg(name, data_type, Some(value)));
+        self
+    }
+
+    pub fn function(mut self, func : FuncBuilder) -> Self {
+        self.functions.push(func);
+        self
+    }
+
+    //
+    // Adds the nodes to the file and checks it
+    //
+    pub fn build(self) -> Result<AstFile, Vec<Diagnostic>> {
+        let mut file = self.file;
+        for s in self.structs {
+            let s = s.add(&mut file);
+            file.add_struct(s);
+        }
+        for c in self.consts {
+            let c = c.add(&mut file);
+            file.add_const(c);
+        }
+        for func in self.functions {
+            let func = func.add(&mut file);
+            file.add_function(func);
+        }
+
+        let mut checker = Checker {
+            file_name : file.get_name().to_string(),
+            context : String::new(),
+            loops : Vec::new(),
+            diagnostics : Vec::new(),
+        };
+        checker.visit_file(&file);
+
+        if checker.diagnostics.len() > 0 {
+            return Err(checker.diagnostics);
+        }
+        Ok(file)
+    }
+
+    // Builds the file and unwrites it
+    pub fn to_source(self) -> Result<String, Vec<Diagnostic>> {
+        let file = self.build()?;
+        Ok(unwriter::render(&file))
+    }
+}
+
+//
+// Structures
+//
+pub struct StructBuilder {
+    name : String,
+    items : Vec<ArgBuilder>,
+}
+
+pub fn structure(name : &str) -> StructBuilder {
+    StructBuilder {
+        name : name.to_string(),
+        items : Vec::new(),
+    }
+}
+
+impl StructBuilder {
+    pub fn item(mut self, name : &str, data_type : DataType) -> Self {
+        self.items.push(new_arg(name, data_type, None));
+        self
+    }
+
+    pub fn item_default(mut self, name : &str, data_type : DataType, value : Expr) -> Self {
+        self.items.push(new_arg(name, data_type, Some(value)));
+        self
+    }
+
+    fn add(self, file : &mut AstFile) -> AstStruct {
+        let id = file.new_id();
+        let mut s = ast_new_struct(file.intern(&self.name), id);
+        for item in self.items {
+            s.add_item(item.add(file));
+        }
+        s
+    }
+}
+
+//
+// Functions
+// A function returns void unless told otherwise
+//
+pub struct FuncBuilder {
+    name : String,
+    data_type : DataType,
+    args : Vec<ArgBuilder>,
+    consts : Vec<ArgBuilder>,
+    block : Vec<Stmt>,
+}
+
+pub fn func(name : &str) -> FuncBuilder {
+    FuncBuilder {
+        name : name.to_string(),
+        data_type : DataType::Void,
+        args : Vec::new(),
+        consts : Vec::new(),
+        block : Vec::new(),
+    }
+}
+
+impl FuncBuilder {
+    pub fn arg(mut self, name : &str, data_type : DataType) -> Self {
+        self.args.push(new_arg(name, data_type, None));
+        self
+    }
+
+    pub fn returns(mut self, data_type : DataType) -> Self {
+        self.data_type = data_type;
+        self
+    }
+
+    pub fn constant(mut self, name : &str, data_type : DataType, value : Expr) -> Self {
+        self.consts.push(new_arg(name, data_type, Some(value)));
+        self
+    }
+
+    pub fn body(mut self, block : Vec<Stmt>) -> Self {
+        self.block = block;
+        self
+    }
+
+    fn add(self, file : &mut AstFile) -> AstFunction {
+        let id = file.new_id();
+        let mut func = ast_new_function(file.intern(&self.name), id);
+        func.set_data_type(self.data_type);
+        for arg in self.args {
+            func.add_arg(arg.add(file));
+        }
+        for c in self.consts {
+            func.add_const(c.add(file));
+        }
+        func.set_block(add_block(file, self.block));
+        func
+    }
+}
+
+//
+// Structure items, function arguments and constants
+//
+struct ArgBuilder {
+    name : String,
+    data_type : DataType,
+    value : Option<Expr>,
+}
+
+fn new_arg(name : &str, data_type : DataType, value : Option<Expr>) -> ArgBuilder {
+    ArgBuilder {
+        name : name.to_string(),
+        data_type : data_type,
+        value : value,
+    }
+}
+
+impl ArgBuilder {
+    fn add(self, file : &mut AstFile) -> AstArg {
+        let id = file.new_id();
+        let mut arg = ast_new_arg(file.intern(&self.name), self.data_type, id);
+        if let Some(value) = self.value {
+            arg.set_expression(value.add(file));
+        }
+        arg
+    }
+}
+
+//
+// Statements
+// The node takes its ID before its children are added
+//
+fn stmt<F>(make : F) -> Stmt
+    where F : FnOnce(&mut AstFile) -> AstStatementKind + 'static
+{
+    Stmt(Box::new(move |file| {
+        let id = file.new_id();
+        let kind = make(file);
+        file.add_statement(ast_new_statement(kind, Span::default(), id))
+    }))
+}
+
+pub fn ret(value : Expr) -> Stmt {
+    stmt(move |file| AstStatementKind::Return(Some(value.add(file))))
+}
+
+pub fn ret_void() -> Stmt {
+    stmt(|_| AstStatementKind::Return(None))
+}
+
+pub fn var(name : &str, data_type : DataType, value : Expr) -> Stmt {
+    let name = name.to_string();
+    stmt(move |file| AstStatementKind::VarDec { name : file.intern(&name), data_type : data_type, value : Some(value.add(file)) })
+}
+
+// A variable that is given its value later
+pub fn var_uninit(name : &str, data_type : DataType) -> Stmt {
+    let name = name.to_string();
+    stmt(move |file| AstStatementKind::VarDec { name : file.intern(&name), data_type : data_type, value : None })
+}
+
+pub fn array(name : &str, data_type : DataType, size : Expr) -> Stmt {
+    let name = name.to_string();
+    stmt(move |file| AstStatementKind::ArrayDec { name : file.intern(&name), data_type : data_type, size : size.add(file) })
+}
+
+pub fn struct_var(name : &str, struct_name : &str) -> Stmt {
+    let name = name.to_string();
+    let struct_name = struct_name.to_string();
+    stmt(move |file| AstStatementKind::StructDec { name : file.intern(&name), struct_name : file.intern(&struct_name) })
+}
+
+pub fn call_stmt(name : &str, args : Vec<Expr>) -> Stmt {
+    let name = name.to_string();
+    stmt(move |file| AstStatementKind::CallStmt { name : file.intern(&name), args : add_list(file, args) })
+}
+
+// The target is a variable, an array element or a structure member
+pub fn assign(op : AssignOp, lhs : Expr, rhs : Expr) -> Stmt {
+    let value = expr(move |file| AstExpressionKind::Assign { op : op, lhs : lhs.add(file), rhs : rhs.add(file) });
+    stmt(move |file| AstStatementKind::ExprStmt(value.add(file)))
+}
+
+pub fn set(lhs : Expr, rhs : Expr) -> Stmt {
+    assign(AssignOp::Assign, lhs, rhs)
+}
+
+pub fn break_loop(label : Option<&str>) -> Stmt {
+    let label = label.map(|l| l.to_string());
+    stmt(move |file| AstStatementKind::Break(label.map(|l| file.intern(&l))))
+}
+
+pub fn continue_loop(label : Option<&str>) -> Stmt {
+    let label = label.map(|l| l.to_string());
+    stmt(move |file| AstStatementKind::Continue(label.map(|l| file.intern(&l))))
+}
+
+//
+// Loops
+// The kind is made once the label is known
+//
+type LoopKind = Box<dyn FnOnce(&mut AstFile, Option<Symbol>) -> AstStatementKind>;
+
+pub struct LoopBuilder {
+    label : Option<String>,
+    kind : LoopKind,
+}
+
+pub fn while_do(cond : Expr, block : Vec<Stmt>) -> LoopBuilder {
+    LoopBuilder {
+        label : None,
+        kind : Box::new(move |file, label| {
+            let cond = cond.add(file);
+            AstStatementKind::While { label : label, cond : cond, block : add_block(file, block) }
+        }),
+    }
+}
+
+pub fn loop_block(block : Vec<Stmt>) -> LoopBuilder {
+    LoopBuilder {
+        label : None,
+        kind : Box::new(move |file, label| AstStatementKind::Loop { label : label, block : add_block(file, block) }),
+    }
+}
+
+pub fn repeat_until(block : Vec<Stmt>, cond : Expr) -> LoopBuilder {
+    LoopBuilder {
+        label : None,
+        kind : Box::new(move |file, label| {
+            let block = add_block(file, block);
+            AstStatementKind::Repeat { label : label, block : block, cond : cond.add(file) }
+        }),
+    }
+}
+
+impl LoopBuilder {
+    pub fn label(mut self, name : &str) -> Self {
+        self.label = Some(name.to_string());
+        self
+    }
+
+    pub fn build(self) -> Stmt {
+        let label = self.label;
+        let kind = self.kind;
+        stmt(move |file| {
+            let label = label.map(|l| file.intern(&l));
+            kind(file, label)
+        })
+    }
+}
+
+//
+// Conditionals
+//
+pub struct IfBuilder {
+    branches : Vec<(Expr, Vec<Stmt>)>,
+    else_block : Option<Vec<Stmt>>,
+}
+
+pub fn if_then(cond : Expr, block : Vec<Stmt>) -> IfBuilder {
+    IfBuilder {
+        branches : vec![(cond, block)],
+        else_block : None,
+    }
+}
+
+impl IfBuilder {
+    pub fn elif(mut self, cond : Expr, block : Vec<Stmt>) -> Self {
+        self.branches.push((cond, block));
+        self
+    }
+
+    pub fn otherwise(mut self, block : Vec<Stmt>) -> Self {
+        self.else_block = Some(block);
+        self
+    }
+
+    pub fn build(self) -> Stmt {
+        stmt(move |file| {
+            let mut branches : Vec<AstBranch> = Vec::new();
+            for (cond, block) in self.branches {
+                let cond = cond.add(file);
+                branches.push(ast_new_branch(cond, add_block(file, block)));
+            }
+            let else_block = self.else_block.map(|block| add_block(file, block));
+            AstStatementKind::If { branches : branches, else_block : else_block }
+        })
+    }
+}
+
+pub struct MatchBuilder {
+    expr : Expr,
+    cases : Vec<(Vec<Expr>, Vec<Stmt>)>,
+    default : Option<Vec<Stmt>>,
+}
+
+pub fn match_on(expr : Expr) -> MatchBuilder {
+    MatchBuilder {
+        expr : expr,
+        cases : Vec::new(),
+        default : None,
+    }
+}
+
+impl MatchBuilder {
+    // The values are literals, constants, or ranges of literals
+    pub fn case(mut self, values : Vec<Expr>, block : Vec<Stmt>) -> Self {
+        self.cases.push((values, block));
+        self
+    }
+
+    pub fn otherwise(mut self, block : Vec<Stmt>) -> Self {
+        self.default = Some(block);
+        self
+    }
+
+    pub fn build(self) -> Stmt {
+        stmt(move |file| {
+            let expr = self.expr.add(file);
+            let mut cases : Vec<AstCase> = Vec::new();
+            for (values, block) in self.cases {
+                let values = add_list(file, values);
+                cases.push(ast_new_case(values, add_block(file, block)));
+            }
+            let default = self.default.map(|block| add_block(file, block));
+            AstStatementKind::Match { expr : expr, cases : cases, default : default }
+        })
+    }
+}
+
+//
+// Expressions
+//
+fn expr<F>(make : F) -> Expr
+    where F : FnOnce(&mut AstFile) -> AstExpressionKind + 'static
+{
+    Expr(Box::new(move |file| {
+        let id = file.new_id();
+        let kind = make(file);
+        file.add_expression(ast_new_expression(kind, Span::default(), id))
+    }))
+}
+
+pub fn id(name : &str) -> Expr {
+    let name = name.to_string();
+    expr(move |file| AstExpressionKind::Id(file.intern(&name)))
+}
+
+pub fn int(val : u64) -> Expr {
+    expr(move |_| AstExpressionKind::IntLiteral(val))
+}
+
+pub fn chr(val : char) -> Expr {
+    expr(move |_| AstExpressionKind::CharLiteral(val))
+}
+
+pub fn string(val : &str) -> Expr {
+    let val = val.to_string();
+    expr(move |file| AstExpressionKind::StringLiteral(file.intern(&val)))
+}
+
+pub fn boolean(val : bool) -> Expr {
+    expr(move |_| AstExpressionKind::BoolLiteral(val))
+}
+
+pub fn index(name : &str, index : Expr) -> Expr {
+    let name = name.to_string();
+    expr(move |file| AstExpressionKind::ArrayAcc { name : file.intern(&name), index : index.add(file) })
+}
+
+pub fn member(name : &str, member : &str) -> Expr {
+    let name = name.to_string();
+    let member = member.to_string();
+    expr(move |file| AstExpressionKind::StructAcc { name : file.intern(&name), member : file.intern(&member) })
+}
+
+pub fn call(name : &str, args : Vec<Expr>) -> Expr {
+    let name = name.to_string();
+    expr(move |file| AstExpressionKind::Call { name : file.intern(&name), args : add_list(file, args) })
+}
+
+pub fn cast(value : Expr, data_type : DataType) -> Expr {
+    expr(move |file| AstExpressionKind::Cast { data_type : data_type, expr : value.add(file) })
+}
+
+pub fn bin(op : BinaryOp, lhs : Expr, rhs : Expr) -> Expr {
+    expr(move |file| AstExpressionKind::Binary { op : op, lhs : lhs.add(file), rhs : rhs.add(file) })
+}
+
+// Only used as a case value
+pub fn range(low : Expr, high : Expr) -> Expr {
+    bin(BinaryOp::Range, low, high)
+}
+
+//
+// Checks what the builder types can't rule out
+// The context is the structure or function we are in, for the notes
+//
+struct Checker {
+    file_name : String,
+    context : String,
+    loops : Vec<Option<Symbol>>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn error(&mut self, message : String) {
+        let mut d = diag_new(Level::Error, &self.file_name, None, message);
+        if self.context.len() > 0 {
+            d.add_note(self.context.clone());
+        }
+        self.diagnostics.push(d);
+    }
+
+    // Names start with a letter or an underscore, and can't be keywords
+    fn check_name(&mut self, name : &str) {
+        let mut chars = name.chars();
+        let valid = match chars.next() {
+            Some(c) => (c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            None => false,
+        };
+
+        if !valid || keyword(name) != Token::None {
+            self.error(format!("Invalid name \"{}\".", name));
+        }
+    }
+
+    fn check_data_type(&mut self, name : &str, data_type : &DataType) {
+        if *data_type == DataType::Void {
+            self.error(format!("\"{}\" can't be void.", name));
+        }
+    }
+
+    fn check_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        let name = file.get_str(arg.get_name());
+        self.check_name(name);
+        self.check_data_type(name, &arg.get_data_type());
+        walk_arg(self, file, arg);
+    }
+
+    // The label of a loop can't hide the label of a loop it is in
+    fn check_loop(&mut self, file : &AstFile, label : &Option<Symbol>) {
+        if let Some(sym) = label {
+            let name = file.get_str(*sym);
+            self.check_name(name);
+            if self.loops.contains(label) {
+                self.error(format!("Label \"{}\" is already in use by an enclosing loop.", name));
+            }
+        }
+    }
+
+    fn check_jump(&mut self, file : &AstFile, kind : &str, label : &Option<Symbol>) {
+        if self.loops.len() == 0 {
+            self.error(format!("\"{}\" outside of a loop.", kind));
+        } else if let Some(sym) = label {
+            if !self.loops.contains(label) {
+                self.error(format!("Unknown loop label \"{}\".", file.get_str(*sym)));
+            }
+        }
+    }
+
+    fn loop_block(&mut self, file : &AstFile, label : &Option<Symbol>, block : &Vec<StmtRef>) {
+        self.loops.push(*label);
+        self.visit_block(file, block);
+        self.loops.pop();
+    }
+
+    // Only places can be assigned to
+    fn check_assign(&mut self, file : &AstFile, lhs : ExprRef, rhs : ExprRef) {
+        let lhs = file.get_expression(lhs);
+        match lhs.get_kind() {
+            AstExpressionKind::Id(_)
+            | AstExpressionKind::ArrayAcc { .. }
+            | AstExpressionKind::StructAcc { .. } => self.visit_expression(file, lhs),
+
+            _ => self.error("Only variables, array elements and structure members can be assigned to.".to_string()),
+        }
+        self.visit_expression(file, file.get_expression(rhs));
+    }
+
+    fn check_case_value(&mut self, file : &AstFile, value : ExprRef) {
+        let value = file.get_expression(value);
+        match value.get_kind() {
+            AstExpressionKind::IntLiteral(_)
+            | AstExpressionKind::CharLiteral(_)
+            | AstExpressionKind::Id(_) => self.visit_expression(file, value),
+
+            AstExpressionKind::Binary { op : BinaryOp::Range, lhs, rhs } => {
+                match (file.get_expression(*lhs).get_kind(), file.get_expression(*rhs).get_kind()) {
+                    (AstExpressionKind::IntLiteral(_), AstExpressionKind::IntLiteral(_))
+                    | (AstExpressionKind::CharLiteral(_), AstExpressionKind::CharLiteral(_)) => {},
+
+                    _ => self.error("Case ranges must be two integer or two character literals.".to_string()),
+                }
+            },
+
+            _ => self.error("Invalid case value in match statement.".to_string()),
+        }
+    }
+}
+
+impl Visitor for Checker {
+    fn visit_import(&mut self, _file : &AstFile, path : &str) {
+        for part in path.split('/') {
+            self.check_name(part);
+        }
+    }
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        let name = file.get_str(s.get_name());
+        self.check_name(name);
+        self.context = format!("in struct {}", name);
+        walk_struct(self, file, s);
+        self.context = String::new();
+    }
+
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        self.check_arg(file, item);
+    }
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        self.check_arg(file, arg);
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.check_arg(file, c);
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        let name = file.get_str(func.get_name());
+        self.check_name(name);
+        self.context = format!("in func {}", name);
+        walk_function(self, file, func);
+        self.context = String::new();
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        let e = |r : &ExprRef| file.get_expression(*r);
+
+        match stmt.get_kind() {
+            AstStatementKind::VarDec { name, data_type, .. }
+            | AstStatementKind::ArrayDec { name, data_type, .. } => {
+                let name = file.get_str(*name);
+                self.check_name(name);
+                self.check_data_type(name, data_type);
+                walk_statement(self, file, stmt);
+            },
+
+            AstStatementKind::StructDec { name, struct_name } => {
+                self.check_name(file.get_str(*name));
+                self.check_name(file.get_str(*struct_name));
+            },
+
+            AstStatementKind::CallStmt { name, .. } => {
+                self.check_name(file.get_str(*name));
+                walk_statement(self, file, stmt);
+            },
+
+            // The only expression that stands on its own is an assignment
+            AstStatementKind::ExprStmt(expr) => {
+                match e(expr).get_kind() {
+                    AstExpressionKind::Assign { lhs, rhs, .. } => self.check_assign(file, *lhs, *rhs),
+                    _ => self.error("Only assignments can be used as statements.".to_string()),
+                }
+            },
+
+            AstStatementKind::While { label, cond, block } => {
+                self.check_loop(file, label);
+                self.visit_expression(file, e(cond));
+                self.loop_block(file, label, block);
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                self.check_loop(file, label);
+                self.loop_block(file, label, block);
+            },
+
+            AstStatementKind::Repeat { label, block, cond } => {
+                self.check_loop(file, label);
+                self.loop_block(file, label, block);
+                self.visit_expression(file, e(cond));
+            },
+
+            AstStatementKind::Break(label) => self.check_jump(file, "break", label),
+            AstStatementKind::Continue(label) => self.check_jump(file, "continue", label),
+
+            AstStatementKind::Match { expr, cases, default } => {
+                self.visit_expression(file, e(expr));
+                for case in cases {
+                    if case.get_values().len() == 0 {
+                        self.error("Expected case value.".to_string());
+                    }
+                    for value in case.get_values() {
+                        self.check_case_value(file, *value);
+                    }
+                    self.visit_block(file, case.get_block());
+                }
+
+                if let Some(block) = default {
+                    self.visit_block(file, block);
+                }
+            },
+
+            _ => walk_statement(self, file, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        match expr.get_kind() {
+            AstExpressionKind::Id(name)
+            | AstExpressionKind::ArrayAcc { name, .. }
+            | AstExpressionKind::Call { name, .. } => self.check_name(file.get_str(*name)),
+
+            AstExpressionKind::StructAcc { name, member } => {
+                self.check_name(file.get_str(*name));
+                self.check_name(file.get_str(*member));
+            },
+
+            // The scanner has no escapes, so the unwriter must not need any
+            AstExpressionKind::StringLiteral(val) => {
+                let val = file.get_str(*val);
+                if format!("{:?}", val) != format!("\"{}\"", val) {
+                    self.error(format!("String literal {:?} can't be written in source.", val));
+                }
+            },
+
+            AstExpressionKind::CharLiteral(val) if format!("{:?}", val) != format!("'{}'", val) => {
+                self.error(format!("Character literal {:?} can't be written in source.", val));
+            },
+
+            AstExpressionKind::Cast { data_type, .. } => self.check_data_type("cast", data_type),
+
+            AstExpressionKind::Binary { op : BinaryOp::Range, .. } => {
+                self.error("Ranges can only be used as case values.".to_string());
+            },
+
+            AstExpressionKind::Assign { .. } => {
+                self.error("Assignments can't be used inside an expression.".to_string());
+            },
+
+            _ => {},
+        }
+
+        walk_expression(self, file, expr);
+    }
+}
@@ -0,0 +1,138 @@
+//
+// Declaration collisions
+//
+// Two declarations of a name in the same scope are an error: two globals of
+// a file (functions, structures and constants share one scope), two
+// arguments, two constants of a function, or two variables of a block. Both
+// places are given.
+//
+// A local that hides another local from an enclosing scope, such as a
+// variable named after an argument, is legal, but is usually a mistake. It
+// is reported at the level the caller asks for, or not at all. Locals can
+// hide globals freely.
+//
+use crate::ast::*;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::resolve::DeclKind;
+use crate::visit::*;
+
+//
+// Checks every file of a program
+// Shadowing is reported at the given level, or not at all with None
+//
+pub fn check(program : &Program, shadowing : Option<Level>) -> Vec<Diagnostic> {
+    let mut checker = Collider {
+        diagnostics : Vec::new(),
+        file_name : String::new(),
+        shadowing : shadowing,
+        scopes : Vec::new(),
+    };
+
+    for file in program.get_files() {
+        checker.file_name = file.get_name().to_string();
+        checker.visit_file(file);
+    }
+    checker.diagnostics
+}
+
+// A name declared in a scope, and where
+struct Entry {
+    name : String,
+    kind : DeclKind,
+    span : Span,
+}
+
+struct Collider {
+    diagnostics : Vec<Diagnostic>,
+    file_name : String,
+    shadowing : Option<Level>,
+
+    // The globals come first, then the local scopes from the outermost in
+    scopes : Vec<Vec<Entry>>,
+}
+
+impl Collider {
+    fn report(&mut self, level : Level, span : Span, message : String, note : String) {
+        let mut d = diag_new(level, &self.file_name, Some(span), message);
+        d.add_note(note);
+        self.diagnostics.push(d);
+    }
+
+    fn declare(&mut self, name : &str, kind : DeclKind, span : Span) {
+        let find = |scope : &Vec<Entry>| scope.iter().find(|e| e.name == name).map(|e| (e.kind, e.span));
+        let same = self.scopes.last().and_then(find);
+
+        // The enclosing local scopes; the globals never count as hidden
+        let outer = self.scopes.iter().skip(1).rev().skip(1).find_map(find);
+
+        if let Some((first, first_span)) = same {
+            let message = format!("Duplicate declaration of \"{}\".", name);
+            let note = format!("The first is the {} at {}.", first.name(), first_span);
+            self.report(Level::Error, span, message, note);
+        } else if let (Some((hidden, hidden_span)), Some(level)) = (outer, self.shadowing) {
+            let message = format!("The {} \"{}\" hides the {} of the same name.", kind.name(), name, hidden.name());
+            let note = format!("The {} is at {}.", hidden.name(), hidden_span);
+            self.report(level, span, message, note);
+        }
+
+        self.scopes.last_mut().unwrap().push(Entry {
+            name : name.to_string(),
+            kind : kind,
+            span : span,
+        });
+    }
+}
+
+impl Visitor for Collider {
+    fn visit_file(&mut self, file : &AstFile) {
+        self.scopes.clear();
+        self.scopes.push(Vec::new());
+        for s in file.get_structs() {
+            self.declare(file.get_str(s.get_name()), DeclKind::Struct, s.get_span());
+        }
+        for c in file.get_consts() {
+            self.declare(file.get_str(c.get_name()), DeclKind::Const, c.get_span());
+        }
+        for func in file.get_functions() {
+            self.declare(file.get_str(func.get_name()), DeclKind::Function, func.get_span());
+        }
+
+        for func in file.get_functions() {
+            self.visit_function(file, func);
+        }
+    }
+
+    // The arguments and the constants each have a scope of their own
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        self.scopes.push(Vec::new());
+        for arg in func.get_args() {
+            self.declare(file.get_str(arg.get_name()), DeclKind::Arg, arg.get_span());
+        }
+
+        self.scopes.push(Vec::new());
+        for c in func.get_consts() {
+            self.declare(file.get_str(c.get_name()), DeclKind::Const, c.get_span());
+        }
+
+        self.visit_block(file, func.get_block());
+        self.scopes.pop();
+        self.scopes.pop();
+    }
+
+    fn visit_block(&mut self, file : &AstFile, block : &Vec<StmtRef>) {
+        self.scopes.push(Vec::new());
+        walk_block(self, file, block);
+        self.scopes.pop();
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        match stmt.get_kind() {
+            AstStatementKind::VarDec { name, .. } => self.declare(file.get_str(*name), DeclKind::Var, stmt.get_span()),
+            AstStatementKind::ArrayDec { name, .. } => self.declare(file.get_str(*name), DeclKind::Array, stmt.get_span()),
+            AstStatementKind::StructDec { name, .. } => self.declare(file.get_str(*name), DeclKind::StructVar, stmt.get_span()),
+            _ => {},
+        }
+        walk_statement(self, file, stmt);
+    }
+}
@@ -0,0 +1,491 @@
+//
+// Constant evaluation
+//
+// Works out the value of every constant, global or local, and the size of
+// every array, while compiling. A constant expression is made of literals,
+// other constants, operators and casts. Each step is checked against the
+// type the type checker gave it, so an overflow or a division by zero is
+// an error here rather than a surprise when the program runs.
+//
+// Constants are evaluated when first needed, so they can refer to one
+// another in any order, but not, through any number of steps, to themselves.
+// Once a constant used as a case value is known, it is checked against the
+// other values of its match statement; the parser can only compare literals.
+//
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::resolve::*;
+use crate::typeck::*;
+use crate::visit::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Char(char),
+    Bool(bool),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Char(c) => write!(f, "'{}'", c),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+pub struct ConstValues {
+    values : HashMap<NodeId, Value>,
+    array_sizes : HashMap<NodeId, u64>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl ConstValues {
+    //
+    // Getter functions
+    //
+    // The value of a constant, by the ID of its declaration
+    pub fn get_value(&self, id : NodeId) -> Option<&Value> {
+        self.values.get(&id)
+    }
+
+    pub fn get_values(&self) -> &HashMap<NodeId, Value> {
+        &self.values
+    }
+
+    // The size of an array, by the ID of the statement that declares it
+    pub fn get_array_size(&self, id : NodeId) -> Option<u64> {
+        self.array_sizes.get(&id).copied()
+    }
+
+    pub fn get_diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+}
+
+//
+// Evaluates every constant and array size of a program
+//
+pub fn evaluate(program : &Program, res : &Resolution, types : &TypeInfo) -> ConstValues {
+    let mut consts : HashMap<NodeId, (&AstArg, &AstFile)> = HashMap::new();
+    for file in program.get_files() {
+        for c in file.get_consts() {
+            consts.insert(c.get_id(), (c, file));
+        }
+        for func in file.get_functions() {
+            for c in func.get_consts() {
+                consts.insert(c.get_id(), (c, file));
+            }
+        }
+    }
+
+    let mut eval = Evaluator {
+        res : res,
+        types : types,
+        consts : consts,
+        state : HashMap::new(),
+        chain : Vec::new(),
+        file : program.get_root(),
+        out : ConstValues {
+            values : HashMap::new(),
+            array_sizes : HashMap::new(),
+            diagnostics : Vec::new(),
+        },
+    };
+
+    for file in program.get_files() {
+        eval.file = file;
+        eval.visit_file(file);
+    }
+    eval.out
+}
+
+// How far a constant has got
+#[derive(Clone, PartialEq)]
+enum State {
+    Working,
+    Done,
+}
+
+struct Evaluator<'a> {
+    res : &'a Resolution,
+    types : &'a TypeInfo,
+    // Each constant, with the file that declares it
+    consts : HashMap<NodeId, (&'a AstArg, &'a AstFile)>,
+    state : HashMap<NodeId, State>,
+
+    // The constants being worked out, from the first one needed, and the
+    // file the expression we are in comes from
+    chain : Vec<String>,
+    file : &'a AstFile,
+    out : ConstValues,
+}
+
+impl<'a> Evaluator<'a> {
+    fn error(&mut self, span : Span, message : String, note : Option<String>) {
+        let mut d = diag_new(Level::Error, self.file.get_name(), Some(span), message);
+        if let Some(note) = note {
+            d.add_note(note);
+        }
+        self.out.diagnostics.push(d);
+    }
+
+    //
+    // Works out a constant, or returns its value if that's already done
+    //
+    fn constant(&mut self, id : NodeId, span : Span) -> Option<Value> {
+        match self.state.get(&id) {
+            Some(State::Done) => return self.out.values.get(&id).cloned(),
+
+            Some(State::Working) => {
+                let (c, file) = self.consts[&id];
+                let name = file.get_str(c.get_name()).to_string();
+                let start = self.chain.iter().position(|c| *c == name).unwrap_or(0);
+                let mut cycle = self.chain[start ..].to_vec();
+                cycle.push(name.clone());
+
+                let message = format!("Constant \"{}\" is defined in terms of itself.", name);
+                self.error(span, message, Some(format!("Through {}.", cycle.join(" -> "))));
+                return None;
+            },
+
+            None => {},
+        }
+
+        let (c, file) = *self.consts.get(&id)?;
+        let value_expr = file.get_expression(c.get_expression()?);
+
+        self.state.insert(id, State::Working);
+        self.chain.push(file.get_str(c.get_name()).to_string());
+        let outer_file = std::mem::replace(&mut self.file, file);
+
+        let value = self.expr(value_expr);
+
+        self.file = outer_file;
+        self.chain.pop();
+        self.state.insert(id, State::Done);
+
+        if let Some(value) = &value {
+            self.out.values.insert(id, value.clone());
+        }
+        value
+    }
+
+    //
+    // Checks that a value fits the type of the expression that made it
+    // None is a value too big to even work out
+    //
+    fn fit(&mut self, expr : &AstExpression, value : Option<i128>) -> Option<Value> {
+        let data_type = self.types.get_type(expr.get_id())?;
+        let min = if is_signed(&data_type) { -(int_max(&data_type) as i128) - 1 } else { 0 };
+        let max = int_max(&data_type) as i128;
+
+        match value {
+            Some(n) if n >= min && n <= max => Some(Value::Int(n)),
+
+            Some(n) => {
+                let message = format!("The constant value {} doesn't fit in {}.", n, data_type.name());
+                self.error(expr.get_span(), message, None);
+                None
+            },
+
+            None => {
+                let message = format!("The constant value doesn't fit in {}.", data_type.name());
+                self.error(expr.get_span(), message, None);
+                None
+            },
+        }
+    }
+
+    fn not_constant(&mut self, expr : &AstExpression, what : String) -> Option<Value> {
+        self.error(expr.get_span(), format!("{} isn't known while compiling.", what), None);
+        None
+    }
+
+    //
+    // Works out the value of an expression
+    // None means it has no value; the reason has been reported
+    //
+    fn expr(&mut self, expr : &AstExpression) -> Option<Value> {
+        let file = self.file;
+        match expr.get_kind() {
+            AstExpressionKind::IntLiteral(n) => Some(Value::Int(*n as i128)),
+            AstExpressionKind::CharLiteral(c) => Some(Value::Char(*c)),
+            AstExpressionKind::BoolLiteral(b) => Some(Value::Bool(*b)),
+            AstExpressionKind::StringLiteral(s) => Some(Value::String(file.get_str(*s).to_string())),
+
+            // Names the resolver couldn't bind have already been reported
+            AstExpressionKind::Id(name) => {
+                let decl = self.res.get_binding(expr.get_id())?;
+                match self.res.get_decl(decl).map(|d| d.get_kind()) {
+                    Some(DeclKind::Const) => self.constant(decl, expr.get_span()),
+                    Some(kind) => self.not_constant(expr, format!("The value of {} \"{}\"", kind.name(), file.get_str(*name))),
+                    None => None,
+                }
+            },
+
+            AstExpressionKind::ArrayAcc { name, .. } | AstExpressionKind::StructAcc { name, .. } => {
+                self.not_constant(expr, format!("The value of \"{}\"", file.get_str(*name)))
+            },
+
+            AstExpressionKind::Call { name, .. } => {
+                self.not_constant(expr, format!("The result of calling \"{}\"", file.get_str(*name)))
+            },
+
+            AstExpressionKind::Assign { .. } => self.not_constant(expr, "The result of an assignment".to_string()),
+
+            AstExpressionKind::Cast { data_type, expr : inner } => {
+                let value = self.expr(file.get_expression(*inner))?;
+                self.cast(expr, value, data_type)
+            },
+
+            AstExpressionKind::Binary { op, lhs, rhs } => {
+                let a = self.expr(file.get_expression(*lhs));
+                let b = self.expr(file.get_expression(*rhs));
+                self.binary(expr, *op, a?, b?)
+            },
+        }
+    }
+
+    //
+    // An explicit cast between integers wraps around, like it will when the
+    // program runs
+    //
+    fn cast(&mut self, expr : &AstExpression, value : Value, data_type : &DataType) -> Option<Value> {
+        let n = match value {
+            Value::Int(n) => n,
+            Value::Char(c) => c as i128,
+            Value::Bool(b) => b as i128,
+            Value::String(_) => return Some(value),
+        };
+
+        match data_type {
+            DataType::Bool => Some(Value::Bool(n != 0)),
+
+            DataType::Char => match to_char(n) {
+                Some(c) => Some(Value::Char(c)),
+                None => {
+                    self.error(expr.get_span(), format!("The constant value {} is not a character.", n), None);
+                    None
+                },
+            },
+
+            _ => {
+                let bits = int_bits(data_type);
+                let mut n = n & ((1i128 << bits) - 1);
+                if is_signed(data_type) && n >= 1i128 << (bits - 1) {
+                    n -= 1i128 << bits;
+                }
+                Some(Value::Int(n))
+            },
+        }
+    }
+
+    fn binary(&mut self, expr : &AstExpression, op : BinaryOp, a : Value, b : Value) -> Option<Value> {
+        let (x, y) = match (&a, &b) {
+            (Value::Int(x), Value::Int(y)) => (*x, *y),
+
+            _ => {
+                return match op {
+                    BinaryOp::Eq => Some(Value::Bool(a == b)),
+                    BinaryOp::Ne => Some(Value::Bool(a != b)),
+
+                    BinaryOp::LGAnd | BinaryOp::LGOr => match (a, b) {
+                        (Value::Bool(x), Value::Bool(y)) if op == BinaryOp::LGAnd => Some(Value::Bool(x && y)),
+                        (Value::Bool(x), Value::Bool(y)) => Some(Value::Bool(x || y)),
+                        _ => None,
+                    },
+
+                    BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Le => match (a, b) {
+                        (Value::Char(x), Value::Char(y)) => Some(Value::Bool(compare(op, x as i128, y as i128))),
+                        _ => None,
+                    },
+
+                    // The type checker has reported anything else
+                    _ => None,
+                };
+            },
+        };
+
+        match op {
+            BinaryOp::Add => self.fit(expr, x.checked_add(y)),
+            BinaryOp::Sub => self.fit(expr, x.checked_sub(y)),
+            BinaryOp::Mul => self.fit(expr, x.checked_mul(y)),
+
+            BinaryOp::Div | BinaryOp::Mod => {
+                if y == 0 {
+                    self.error(expr.get_span(), "Division by zero in a constant expression.".to_string(), None);
+                    return None;
+                }
+                self.fit(expr, if op == BinaryOp::Div { x.checked_div(y) } else { x.checked_rem(y) })
+            },
+
+            BinaryOp::And => self.fit(expr, Some(x & y)),
+            BinaryOp::Or => self.fit(expr, Some(x | y)),
+            BinaryOp::Xor => self.fit(expr, Some(x ^ y)),
+
+            BinaryOp::Lsh | BinaryOp::Rsh => {
+                let bits = self.types.get_type(expr.get_id()).map_or(64, |t| int_bits(&t)) as i128;
+                if y < 0 || y >= bits {
+                    let message = format!("A shift by {} is out of range for a {}-bit value.", y, bits);
+                    self.error(expr.get_span(), message, None);
+                    return None;
+                }
+
+                if op == BinaryOp::Lsh {
+                    self.fit(expr, x.checked_mul(1 << y))
+                } else {
+                    self.fit(expr, Some(x >> y))
+                }
+            },
+
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Le => {
+                Some(Value::Bool(compare(op, x, y)))
+            },
+
+            BinaryOp::LGAnd | BinaryOp::LGOr | BinaryOp::Range => None,
+        }
+    }
+}
+
+// The values a case covers, from low to high, and whether they are characters
+#[derive(Clone, Copy)]
+struct CaseRange {
+    low : i128,
+    high : i128,
+    is_char : bool,
+    is_const : bool,
+}
+
+impl Evaluator<'_> {
+    //
+    // Reports a constant case value that another case of the match already
+    // covers; two literals that overlap have been reported by the parser
+    //
+    fn check_cases(&mut self, cases : &Vec<AstCase>) {
+        let mut ranges : Vec<CaseRange> = Vec::new();
+        for case in cases {
+            for value in case.get_values() {
+                let value = self.file.get_expression(*value);
+                let range = match self.case_range(value) {
+                    Some(range) => range,
+                    None => continue,
+                };
+
+                let overlap = ranges.iter().find(|other| {
+                    (range.is_const || other.is_const) && other.is_char == range.is_char
+                        && range.low <= other.high && other.low <= range.high
+                });
+
+                if let Some(other) = overlap {
+                    let first = range.low.max(other.low);
+                    let note = if range.is_char {
+                        format!("{:?}", to_char(first).unwrap_or('?'))
+                    } else {
+                        format!("{}", first)
+                    };
+                    self.error(value.get_span(), "Duplicate case value in match statement.".to_string(), Some(note));
+                }
+                ranges.push(range);
+            }
+        }
+    }
+
+    fn case_range(&mut self, value : &AstExpression) -> Option<CaseRange> {
+        let point = |n : i128, is_char : bool, is_const : bool| CaseRange {
+            low : n,
+            high : n,
+            is_char : is_char,
+            is_const : is_const,
+        };
+
+        match value.get_kind() {
+            AstExpressionKind::IntLiteral(n) => Some(point(*n as i128, false, false)),
+            AstExpressionKind::CharLiteral(c) => Some(point(*c as i128, true, false)),
+
+            AstExpressionKind::Binary { op : BinaryOp::Range, lhs, rhs } => {
+                match (self.file.get_expression(*lhs).get_kind(), self.file.get_expression(*rhs).get_kind()) {
+                    (AstExpressionKind::IntLiteral(lo), AstExpressionKind::IntLiteral(hi)) if lo <= hi => {
+                        Some(CaseRange { low : *lo as i128, high : *hi as i128, is_char : false, is_const : false })
+                    },
+
+                    (AstExpressionKind::CharLiteral(lo), AstExpressionKind::CharLiteral(hi)) if lo <= hi => {
+                        Some(CaseRange { low : *lo as i128, high : *hi as i128, is_char : true, is_const : false })
+                    },
+
+                    _ => None,
+                }
+            },
+
+            AstExpressionKind::Id(_) => {
+                let decl = self.res.get_binding(value.get_id())?;
+                if self.res.get_decl(decl)?.get_kind() != DeclKind::Const {
+                    return None;
+                }
+
+                match self.constant(decl, value.get_span())? {
+                    Value::Int(n) => Some(point(n, false, true)),
+                    Value::Char(c) => Some(point(c as i128, true, true)),
+                    _ => None,
+                }
+            },
+
+            _ => None,
+        }
+    }
+}
+
+fn to_char(n : i128) -> Option<char> {
+    if n < 0 || n > u32::MAX as i128 {
+        return None;
+    }
+    char::from_u32(n as u32)
+}
+
+fn compare(op : BinaryOp, x : i128, y : i128) -> bool {
+    match op {
+        BinaryOp::Eq => x == y,
+        BinaryOp::Ne => x != y,
+        BinaryOp::Gt => x > y,
+        BinaryOp::Lt => x < y,
+        BinaryOp::Ge => x >= y,
+        _ => x <= y,
+    }
+}
+
+impl Visitor for Evaluator<'_> {
+    fn visit_const(&mut self, _file : &AstFile, c : &AstArg) {
+        self.constant(c.get_id(), c.get_span());
+    }
+
+    // Struct defaults and argument values don't have to be constant
+    fn visit_item(&mut self, _file : &AstFile, _item : &AstArg) {}
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        if let AstStatementKind::ArrayDec { name, size, .. } = stmt.get_kind() {
+            let size = file.get_expression(*size);
+            match self.expr(size) {
+                Some(Value::Int(n)) if n >= 1 => { self.out.array_sizes.insert(stmt.get_id(), n as u64); },
+
+                Some(Value::Int(n)) => {
+                    let message = format!("Array \"{}\" has a size of {}; it needs at least one element.", file.get_str(*name), n);
+                    self.error(size.get_span(), message, None);
+                },
+
+                _ => {},
+            }
+        }
+
+        if let AstStatementKind::Match { cases, .. } = stmt.get_kind() {
+            self.check_cases(cases);
+        }
+        walk_statement(self, file, stmt);
+    }
+}
@@ -0,0 +1,98 @@
+//
+// Diagnostics: the errors and warnings the front end finds
+// Nothing is printed while parsing; each stage collects its diagnostics,
+// and it's up to the caller what to do with them
+//
+use std::fmt;
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    level : Level,
+    message : String,
+    file : String,
+    span : Option<Span>,
+    notes : Vec<String>,
+}
+
+impl Diagnostic {
+    //
+    // Setter functions
+    //
+    pub fn add_note(&mut self, note : String) {
+        self.notes.push(note);
+    }
+
+    //
+    // Getter functions
+    //
+    pub fn get_level(&self) -> Level {
+        self.level
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn get_span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn get_notes(&self) -> &Vec<String> {
+        &self.notes
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+}
+
+//
+// Diagnostics print as "file:line:col: Error: message", with each note
+// on its own line below
+//
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        if self.file.len() > 0 {
+            match self.span {
+                Some(span) => write!(f, "{}:{}: ", self.file, span)?,
+                None => write!(f, "{}: ", self.file)?,
+            }
+        }
+
+        match self.level {
+            Level::Error => write!(f, "Error: {}", self.message)?,
+            Level::Warning => write!(f, "Warning: {}", self.message)?,
+        }
+
+        for note in &self.notes {
+            write!(f, "\n-> {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// A helper function to create a diagnostic
+// The file may be empty when the problem isn't tied to one
+//
+pub fn diag_new(level : Level, file : &str, span : Option<Span>, message : String) -> Diagnostic {
+    Diagnostic {
+        level : level,
+        message : message,
+        file : file.to_string(),
+        span : span,
+        notes : Vec::new(),
+    }
+}
@@ -0,0 +1,378 @@
+//
+// Compares two versions of a file by their trees rather than their text
+//
+// Nodes are compared by their shape, leaving out IDs and spans, so moving or
+// reformatting code isn't a change. The changes are reported per
+// structure, constant and function, in the order they appear.
+//
+use std::fmt;
+
+use crate::ast::*;
+use crate::intern::Symbol;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    SignatureChanged,
+    BodyChanged,
+}
+
+pub struct Change {
+    kind : ChangeKind,
+    what : String,          // "struct", "const" or "func"
+    name : String,
+
+    // Where the change is in each file; for a body this is the first
+    // statement that differs, if the file has one
+    old : Option<(String, Span)>,
+    new : Option<(String, Span)>,
+}
+
+impl Change {
+    //
+    // Getter functions
+    //
+    pub fn get_kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    pub fn get_what(&self) -> &str {
+        &self.what
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_old(&self) -> Option<&(String, Span)> {
+        self.old.as_ref()
+    }
+
+    pub fn get_new(&self) -> Option<&(String, Span)> {
+        self.new.as_ref()
+    }
+}
+
+//
+// Changes print like diagnostics, at the new location if there is one:
+// "new.tl:3:5: func main: body changed", with the old location as a note
+//
+impl fmt::Display for Change {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let description = match self.kind {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::SignatureChanged => "signature changed",
+            ChangeKind::BodyChanged => "body changed",
+        };
+
+        match (&self.old, &self.new) {
+            (_, Some((file, span))) => write!(f, "{}:{}: ", file, span)?,
+            (Some((file, span)), None) => write!(f, "{}:{}: ", file, span)?,
+            (None, None) => {},
+        }
+        write!(f, "{} {}: {}", self.what, self.name, description)?;
+
+        if let (Some((file, span)), Some(_)) = (&self.old, &self.new) {
+            write!(f, "\n-> was {}:{}", file, span)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// Returns the changes from the old file to the new one
+//
+pub fn diff(old : &AstFile, new : &AstFile) -> Vec<Change> {
+    let mut differ = Differ {
+        old : old,
+        new : new,
+        changes : Vec::new(),
+    };
+    let same = Same { old : old, new : new };
+    let old_stmt = |s : &StmtRef| old.get_statement(*s).get_span();
+    let new_stmt = |s : &StmtRef| new.get_statement(*s).get_span();
+
+    // Structures have no signature; their items are the body
+    differ.compare("struct", old.get_structs(), new.get_structs(),
+        |file, s| file.get_str(s.get_name()).to_string(), |s| s.get_span(),
+        |a, b| {
+            let at = first_difference(a.get_items(), b.get_items(), |x, y| same.arg(x, y), AstArg::get_span, AstArg::get_span)?;
+            Some((ChangeKind::BodyChanged, Some(at)))
+        });
+
+    // The type of a constant is its signature, and the value its body
+    differ.compare("const", old.get_consts(), new.get_consts(),
+        |file, c| file.get_str(c.get_name()).to_string(), |c| c.get_span(),
+        |a, b| {
+            if a.get_data_type() != b.get_data_type() {
+                return Some((ChangeKind::SignatureChanged, None));
+            }
+            if !same.value(a.get_expression(), b.get_expression()) {
+                return Some((ChangeKind::BodyChanged, None));
+            }
+            None
+        });
+
+    differ.compare("func", old.get_functions(), new.get_functions(),
+        |file, func| file.get_str(func.get_name()).to_string(), |func| func.get_span(),
+        |a, b| {
+            if !same.args(a.get_args(), b.get_args()) || a.get_data_type() != b.get_data_type() {
+                return Some((ChangeKind::SignatureChanged, None));
+            }
+            if let Some(at) = first_difference(a.get_consts(), b.get_consts(), |x, y| same.arg(x, y), AstArg::get_span, AstArg::get_span) {
+                return Some((ChangeKind::BodyChanged, Some(at)));
+            }
+            if let Some(at) = first_difference(a.get_block(), b.get_block(), |x, y| same.stmt(*x, *y), old_stmt, new_stmt) {
+                return Some((ChangeKind::BodyChanged, Some(at)));
+            }
+            None
+        });
+
+    differ.changes
+}
+
+// The spans of the first pair of statements that differ; a block that
+// just got longer or shorter only has a span on one side
+type Difference = (Option<Span>, Option<Span>);
+
+fn first_difference<T, E, A, B>(old : &[T], new : &[T], same : E, old_span : A, new_span : B) -> Option<Difference>
+    where E : Fn(&T, &T) -> bool,
+          A : Fn(&T) -> Span,
+          B : Fn(&T) -> Span
+{
+    for i in 0 .. old.len().max(new.len()) {
+        let a = old.get(i);
+        let b = new.get(i);
+        let differs = match (a, b) {
+            (Some(a), Some(b)) => !same(a, b),
+            _ => true,
+        };
+        if differs {
+            return Some((a.map(&old_span), b.map(&new_span)));
+        }
+    }
+    None
+}
+
+//
+// Compares nodes of the old file with nodes of the new one
+// Children are looked up in their own file, and names compare by what
+// they spell, since each file has its own symbols
+//
+#[derive(Clone, Copy)]
+struct Same<'a> {
+    old : &'a AstFile,
+    new : &'a AstFile,
+}
+
+impl Same<'_> {
+    fn name(&self, a : Symbol, b : Symbol) -> bool {
+        self.old.get_str(a) == self.new.get_str(b)
+    }
+
+    fn label(&self, a : &Option<Symbol>, b : &Option<Symbol>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.name(*a, *b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn arg(&self, a : &AstArg, b : &AstArg) -> bool {
+        self.name(a.get_name(), b.get_name()) && a.get_data_type() == b.get_data_type()
+            && self.value(a.get_expression(), b.get_expression())
+    }
+
+    fn args(&self, a : &[AstArg], b : &[AstArg]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.arg(a, b))
+    }
+
+    fn value(&self, a : Option<ExprRef>, b : Option<ExprRef>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.expr(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn block(&self, a : &[StmtRef], b : &[StmtRef]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.stmt(*a, *b))
+    }
+
+    fn else_block(&self, a : &Option<Vec<StmtRef>>, b : &Option<Vec<StmtRef>>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.block(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn exprs(&self, a : &[ExprRef], b : &[ExprRef]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.expr(*a, *b))
+    }
+
+    fn stmt(&self, a : StmtRef, b : StmtRef) -> bool {
+        use AstStatementKind::*;
+
+        match (self.old.get_statement(a).get_kind(), self.new.get_statement(b).get_kind()) {
+            (Return(a), Return(b)) => self.value(*a, *b),
+
+            (VarDec { name : n1, data_type : t1, value : v1 }, VarDec { name : n2, data_type : t2, value : v2 }) => {
+                self.name(*n1, *n2) && t1 == t2 && self.value(*v1, *v2)
+            },
+
+            (ArrayDec { name : n1, data_type : t1, size : s1 }, ArrayDec { name : n2, data_type : t2, size : s2 }) => {
+                self.name(*n1, *n2) && t1 == t2 && self.expr(*s1, *s2)
+            },
+
+            (StructDec { name : n1, struct_name : s1 }, StructDec { name : n2, struct_name : s2 }) => {
+                self.name(*n1, *n2) && self.name(*s1, *s2)
+            },
+
+            (CallStmt { name : n1, args : a1 }, CallStmt { name : n2, args : a2 }) => {
+                self.name(*n1, *n2) && self.exprs(a1, a2)
+            },
+
+            (ExprStmt(a), ExprStmt(b)) => self.expr(*a, *b),
+
+            (While { label : l1, cond : c1, block : b1 }, While { label : l2, cond : c2, block : b2 }) => {
+                self.label(l1, l2) && self.expr(*c1, *c2) && self.block(b1, b2)
+            },
+
+            (Loop { label : l1, block : b1 }, Loop { label : l2, block : b2 }) => {
+                self.label(l1, l2) && self.block(b1, b2)
+            },
+
+            (Repeat { label : l1, block : b1, cond : c1 }, Repeat { label : l2, block : b2, cond : c2 }) => {
+                self.label(l1, l2) && self.block(b1, b2) && self.expr(*c1, *c2)
+            },
+
+            (Break(a), Break(b)) | (Continue(a), Continue(b)) => self.label(a, b),
+
+            (If { branches : br1, else_block : e1 }, If { branches : br2, else_block : e2 }) => {
+                br1.len() == br2.len()
+                    && br1.iter().zip(br2).all(|(a, b)| {
+                        self.expr(a.get_cond(), b.get_cond()) && self.block(a.get_block(), b.get_block())
+                    })
+                    && self.else_block(e1, e2)
+            },
+
+            (Match { expr : x1, cases : c1, default : d1 }, Match { expr : x2, cases : c2, default : d2 }) => {
+                self.expr(*x1, *x2)
+                    && c1.len() == c2.len()
+                    && c1.iter().zip(c2).all(|(a, b)| {
+                        self.exprs(a.get_values(), b.get_values()) && self.block(a.get_block(), b.get_block())
+                    })
+                    && self.else_block(d1, d2)
+            },
+
+            _ => false,
+        }
+    }
+
+    fn expr(&self, a : ExprRef, b : ExprRef) -> bool {
+        use AstExpressionKind::*;
+
+        match (self.old.get_expression(a).get_kind(), self.new.get_expression(b).get_kind()) {
+            (Id(a), Id(b)) | (StringLiteral(a), StringLiteral(b)) => self.name(*a, *b),
+            (IntLiteral(a), IntLiteral(b)) => a == b,
+            (CharLiteral(a), CharLiteral(b)) => a == b,
+            (BoolLiteral(a), BoolLiteral(b)) => a == b,
+
+            (ArrayAcc { name : n1, index : i1 }, ArrayAcc { name : n2, index : i2 }) => {
+                self.name(*n1, *n2) && self.expr(*i1, *i2)
+            },
+
+            (StructAcc { name : n1, member : m1 }, StructAcc { name : n2, member : m2 }) => {
+                self.name(*n1, *n2) && self.name(*m1, *m2)
+            },
+
+            (Call { name : n1, args : a1 }, Call { name : n2, args : a2 }) => {
+                self.name(*n1, *n2) && self.exprs(a1, a2)
+            },
+
+            (Cast { data_type : t1, expr : e1 }, Cast { data_type : t2, expr : e2 }) => {
+                t1 == t2 && self.expr(*e1, *e2)
+            },
+
+            (Binary { op : o1, lhs : l1, rhs : r1 }, Binary { op : o2, lhs : l2, rhs : r2 }) => {
+                o1 == o2 && self.expr(*l1, *l2) && self.expr(*r1, *r2)
+            },
+
+            (Assign { op : o1, lhs : l1, rhs : r1 }, Assign { op : o2, lhs : l2, rhs : r2 }) => {
+                o1 == o2 && self.expr(*l1, *l2) && self.expr(*r1, *r2)
+            },
+
+            _ => false,
+        }
+    }
+}
+
+struct Differ<'a> {
+    old : &'a AstFile,
+    new : &'a AstFile,
+    changes : Vec<Change>,
+}
+
+impl Differ<'_> {
+    //
+    // Matches the old and new declarations by name
+    // The check returns what changed between two declarations with the
+    // same name, along with where, if it can narrow it down
+    //
+    fn compare<T, N, S, C>(&mut self, what : &str, old : &[T], new : &[T], name : N, span : S, check : C)
+        where N : Fn(&AstFile, &T) -> String,
+              S : Fn(&T) -> Span,
+              C : Fn(&T, &T) -> Option<(ChangeKind, Option<Difference>)>
+    {
+        let old_name = self.old.get_name().to_string();
+        let new_name = self.new.get_name().to_string();
+
+        for a in old {
+            let b = new.iter().find(|b| name(self.new, b) == name(self.old, a));
+            let mut change = Change {
+                kind : ChangeKind::Removed,
+                what : what.to_string(),
+                name : name(self.old, a),
+                old : Some((old_name.clone(), span(a))),
+                new : None,
+            };
+
+            if let Some(b) = b {
+                match check(a, b) {
+                    Some((kind, at)) => {
+                        change.kind = kind;
+                        change.new = Some((new_name.clone(), span(b)));
+
+                        if let Some((old_at, new_at)) = at {
+                            if let Some(old_at) = old_at {
+                                change.old = Some((old_name.clone(), old_at));
+                            }
+                            if let Some(new_at) = new_at {
+                                change.new = Some((new_name.clone(), new_at));
+                            }
+                        }
+                    },
+
+                    None => continue,
+                }
+            }
+
+            self.changes.push(change);
+        }
+
+        for b in new {
+            if !old.iter().any(|a| name(self.old, a) == name(self.new, b)) {
+                self.changes.push(Change {
+                    kind : ChangeKind::Added,
+                    what : what.to_string(),
+                    name : name(self.new, b),
+                    old : None,
+                    new : Some((new_name.clone(), span(b))),
+                });
+            }
+        }
+    }
+}
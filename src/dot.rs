@@ -0,0 +1,191 @@
+//
+// Writes the AST as a Graphviz digraph
+//
+// Each node of the tree becomes a box, with an edge to each of its children.
+// The boxes are numbered in the order we reach them, so the output stays the
+// same from run to run, and "ordering=out" keeps the children left to right.
+//
+use crate::ast::*;
+use crate::intern::Symbol;
+use crate::visit::*;
+
+pub fn render(file : &AstFile) -> String {
+    let mut writer = DotWriter {
+        out : String::new(),
+        count : 0,
+        parents : Vec::new(),
+    };
+
+    writer.out.push_str("digraph ast {\n");
+    writer.out.push_str("    ordering=out;\n");
+    writer.out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    writer.visit_file(file);
+    writer.out.push_str("}\n");
+    writer.out
+}
+
+struct DotWriter {
+    out : String,
+    count : usize,
+
+    // The nodes we are under; the last one gets the edges
+    parents : Vec<usize>,
+}
+
+impl DotWriter {
+    // Adds a node below the current parent and returns its number
+    fn add_node(&mut self, label : &str) -> usize {
+        let id = self.count;
+        self.count += 1;
+
+        let mut escaped = String::new();
+        for c in label.chars() {
+            match c {
+                '"' | '\\' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                },
+                _ => escaped.push(c),
+            }
+        }
+
+        self.out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escaped));
+        if let Some(parent) = self.parents.last() {
+            self.out.push_str(&format!("    n{} -> n{};\n", parent, id));
+        }
+        id
+    }
+
+    // Adds a node, and puts everything the function adds below it
+    fn with_node<F : FnOnce(&mut DotWriter)>(&mut self, label : &str, children : F) {
+        let id = self.add_node(label);
+        self.parents.push(id);
+        children(self);
+        self.parents.pop();
+    }
+
+    fn arg(&mut self, file : &AstFile, kind : &str, arg : &AstArg) {
+        let label = format!("{} {} : {}", kind, file.get_str(arg.get_name()), arg.get_data_type().name());
+        self.with_node(&label, |v| walk_arg(v, file, arg));
+    }
+}
+
+// Loops show their label in front of them, break and continue after them
+fn loop_label(file : &AstFile, kind : &str, label : &Option<Symbol>) -> String {
+    match label {
+        Some(label) => format!("{}: {}", file.get_str(*label), kind),
+        None => kind.to_string(),
+    }
+}
+
+fn jump_label(file : &AstFile, kind : &str, label : &Option<Symbol>) -> String {
+    match label {
+        Some(label) => format!("{} {}", kind, file.get_str(*label)),
+        None => kind.to_string(),
+    }
+}
+
+impl Visitor for DotWriter {
+    fn visit_file(&mut self, file : &AstFile) {
+        let label = format!("File {}", file.get_name());
+        self.with_node(&label, |v| walk_file(v, file));
+    }
+
+    fn visit_import(&mut self, _file : &AstFile, path : &str) {
+        self.add_node(&format!("Import {}", path));
+    }
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        let label = format!("Struct {}", file.get_str(s.get_name()));
+        self.with_node(&label, |v| walk_struct(v, file, s));
+    }
+
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        self.arg(file, "Item", item);
+    }
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        self.arg(file, "Arg", arg);
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.arg(file, "Const", c);
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        let label = format!("Func {} -> {}", file.get_str(func.get_name()), func.get_data_type().name());
+        self.with_node(&label, |v| walk_function(v, file, func));
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        let label = match stmt.get_kind() {
+            AstStatementKind::Return(_) => "Return".to_string(),
+            AstStatementKind::VarDec { name, data_type, .. } => format!("VarDec {} : {}", file.get_str(*name), data_type.name()),
+            AstStatementKind::ArrayDec { name, data_type, .. } => format!("ArrayDec {} : {}", file.get_str(*name), data_type.name()),
+            AstStatementKind::StructDec { name, struct_name } => format!("StructDec {} : {}", file.get_str(*name), file.get_str(*struct_name)),
+            AstStatementKind::CallStmt { name, .. } => format!("CallStmt {}", file.get_str(*name)),
+            AstStatementKind::ExprStmt(_) => "ExprStmt".to_string(),
+            AstStatementKind::While { label, .. } => loop_label(file, "While", label),
+            AstStatementKind::Loop { label, .. } => loop_label(file, "Loop", label),
+            AstStatementKind::Repeat { label, .. } => loop_label(file, "Repeat", label),
+            AstStatementKind::Break(label) => jump_label(file, "Break", label),
+            AstStatementKind::Continue(label) => jump_label(file, "Continue", label),
+            AstStatementKind::If { .. } => "If".to_string(),
+            AstStatementKind::Match { .. } => "Match".to_string(),
+        };
+
+        // The arms of conditionals get a node of their own
+        self.with_node(&label, |v| {
+            match stmt.get_kind() {
+                AstStatementKind::If { branches, else_block } => {
+                    for br in branches {
+                        v.with_node("Branch", |v| {
+                            v.visit_expression(file, file.get_expression(br.get_cond()));
+                            v.visit_block(file, br.get_block());
+                        });
+                    }
+
+                    if let Some(block) = else_block {
+                        v.with_node("Else", |v| v.visit_block(file, block));
+                    }
+                },
+
+                AstStatementKind::Match { expr, cases, default } => {
+                    v.visit_expression(file, file.get_expression(*expr));
+                    for case in cases {
+                        v.with_node("Case", |v| {
+                            for value in case.get_values() {
+                                v.visit_expression(file, file.get_expression(*value));
+                            }
+                            v.visit_block(file, case.get_block());
+                        });
+                    }
+
+                    if let Some(block) = default {
+                        v.with_node("Else", |v| v.visit_block(file, block));
+                    }
+                },
+
+                _ => walk_statement(v, file, stmt),
+            }
+        });
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        let label = match expr.get_kind() {
+            AstExpressionKind::Id(name) => format!("Id {}", file.get_str(*name)),
+            AstExpressionKind::IntLiteral(val) => format!("Int {}", val),
+            AstExpressionKind::CharLiteral(val) => format!("Char {:?}", val),
+            AstExpressionKind::StringLiteral(val) => format!("String {:?}", file.get_str(*val)),
+            AstExpressionKind::BoolLiteral(val) => format!("Bool {}", val),
+            AstExpressionKind::ArrayAcc { name, .. } => format!("ArrayAcc {}", file.get_str(*name)),
+            AstExpressionKind::StructAcc { name, member } => format!("StructAcc {}.{}", file.get_str(*name), file.get_str(*member)),
+            AstExpressionKind::Call { name, .. } => format!("Call {}", file.get_str(*name)),
+            AstExpressionKind::Cast { data_type, .. } => format!("Cast {}", data_type.name()),
+            AstExpressionKind::Binary { op, .. } => format!("Binary {}", op.symbol()),
+            AstExpressionKind::Assign { op, .. } => format!("Assign {}", op.symbol()),
+        };
+
+        self.with_node(&label, |v| walk_expression(v, file, expr));
+    }
+}
@@ -0,0 +1,190 @@
+//
+// Control flow checks
+//
+// Follows each function block by block, keeping track of whether control
+// can get past each statement. From that it reports statements that can
+// never run, functions with a value that can reach their end without
+// returning one, and a break or continue outside of any loop.
+//
+// A loop is only taken to be endless when it says so: a loop, a while
+// true, or a repeat until false. Labels are checked by the parser, and
+// return values by the type checker.
+//
+use crate::ast::*;
+use crate::diag::*;
+use crate::intern::Symbol;
+use crate::loader::Program;
+
+//
+// Checks every function of a program
+//
+pub fn check(program : &Program) -> Vec<Diagnostic> {
+    let mut flow = Flow {
+        diagnostics : Vec::new(),
+        file : program.get_root(),
+        loops : Vec::new(),
+    };
+
+    for file in program.get_files() {
+        flow.file = file;
+        for func in file.get_functions() {
+            flow.function(func);
+        }
+    }
+    flow.diagnostics
+}
+
+// A loop we are in, and how it has been left so far
+struct LoopState {
+    label : Option<Symbol>,
+    broken : bool,
+    continued : bool,
+}
+
+struct Flow<'a> {
+    diagnostics : Vec<Diagnostic>,
+    file : &'a AstFile,
+    loops : Vec<LoopState>,
+}
+
+impl Flow<'_> {
+    fn report(&mut self, level : Level, span : Span, message : String) {
+        let d = diag_new(level, self.file.get_name(), Some(span), message);
+        self.diagnostics.push(d);
+    }
+
+    fn function(&mut self, func : &AstFunction) {
+        self.loops.clear();
+        let falls_through = self.block(func.get_block());
+
+        if falls_through && func.get_data_type() != DataType::Void {
+            let message = format!("Function \"{}\" can reach its end without returning a value.", self.file.get_str(func.get_name()));
+            self.report(Level::Error, func.get_span(), message);
+        }
+    }
+
+    //
+    // Returns whether control can get past the end of the block
+    // Only the first statement that can't be reached is reported, but the
+    // rest are still checked
+    //
+    fn block(&mut self, block : &Vec<StmtRef>) -> bool {
+        let file = self.file;
+        let mut reachable = true;
+        let mut reported = false;
+        for stmt in block {
+            let stmt = file.get_statement(*stmt);
+            if !reachable && !reported {
+                self.report(Level::Warning, stmt.get_span(), "Unreachable statement.".to_string());
+                reported = true;
+            }
+
+            // Code past a dead statement is dead too, whatever it does
+            let falls_through = self.statement(stmt);
+            reachable = reachable && falls_through;
+        }
+        reachable
+    }
+
+    // The loop a break or continue refers to
+    fn target(&mut self, label : &Option<Symbol>) -> Option<&mut LoopState> {
+        match label {
+            Some(_) => self.loops.iter_mut().rev().find(|l| l.label == *label),
+            None => self.loops.last_mut(),
+        }
+    }
+
+    fn jump(&mut self, stmt : &AstStatement, kind : &str, label : &Option<Symbol>, is_break : bool) {
+        if self.loops.is_empty() {
+            self.report(Level::Error, stmt.get_span(), format!("\"{}\" outside of a loop.", kind));
+            return;
+        }
+
+        if let Some(target) = self.target(label) {
+            if is_break {
+                target.broken = true;
+            } else {
+                target.continued = true;
+            }
+        }
+    }
+
+    // Runs a loop body, and returns how it was left
+    fn loop_body(&mut self, label : &Option<Symbol>, block : &Vec<StmtRef>) -> (bool, LoopState) {
+        self.loops.push(LoopState {
+            label : *label,
+            broken : false,
+            continued : false,
+        });
+        let falls_through = self.block(block);
+        (falls_through, self.loops.pop().unwrap())
+    }
+
+    //
+    // Returns whether control can get past the statement
+    //
+    fn statement(&mut self, stmt : &AstStatement) -> bool {
+        let file = self.file;
+        match stmt.get_kind() {
+            AstStatementKind::Return(_) => false,
+
+            AstStatementKind::Break(label) => {
+                self.jump(stmt, "break", label, true);
+                false
+            },
+
+            AstStatementKind::Continue(label) => {
+                self.jump(stmt, "continue", label, false);
+                false
+            },
+
+            AstStatementKind::While { label, cond, block } => {
+                let (_, state) = self.loop_body(label, block);
+                !is_bool(file.get_expression(*cond), true) || state.broken
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                let (_, state) = self.loop_body(label, block);
+                state.broken
+            },
+
+            // The condition is only reached through the end of the body or a continue
+            AstStatementKind::Repeat { label, block, cond } => {
+                let (falls_through, state) = self.loop_body(label, block);
+                let tested = falls_through || state.continued;
+                (tested && !is_bool(file.get_expression(*cond), false)) || state.broken
+            },
+
+            AstStatementKind::If { branches, else_block } => {
+                let mut falls_through = false;
+                for br in branches {
+                    falls_through |= self.block(br.get_block());
+                }
+
+                match else_block {
+                    Some(block) => self.block(block) || falls_through,
+                    None => true,
+                }
+            },
+
+            AstStatementKind::Match { cases, default, .. } => {
+                let mut falls_through = false;
+                for case in cases {
+                    falls_through |= self.block(case.get_block());
+                }
+
+                match default {
+                    Some(block) => self.block(block) || falls_through,
+                    None => true,
+                }
+            },
+
+            _ => true,
+        }
+    }
+}
+
+// Whether an expression is the literal true or false
+fn is_bool(expr : &AstExpression, value : bool) -> bool {
+    *expr.get_kind() == AstExpressionKind::BoolLiteral(value)
+}
@@ -0,0 +1,291 @@
+//
+// Definite assignment
+//
+// A variable declared without a value has to be given one before it is
+// read, on every path that gets there. Each function is followed statement
+// by statement with the set of such variables that surely have a value by
+// then. Where paths meet, after an if or a match, only what every path
+// assigned counts. A while loop's body might not run at all, so what it
+// assigns doesn't count after it, but a repeat loop's body always runs once.
+//
+// Paths that can't go on (after a return, break or continue) have no set,
+// and don't hold back the others where they meet. Arrays are left alone,
+// since their elements can't be followed one by one.
+//
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::intern::Symbol;
+use crate::loader::Program;
+use crate::resolve::*;
+
+//
+// Checks every function of a program
+//
+pub fn check(program : &Program, res : &Resolution) -> Vec<Diagnostic> {
+    let mut checker = InitChecker {
+        res : res,
+        diagnostics : Vec::new(),
+        file : program.get_root(),
+        declared : HashMap::new(),
+        reported : HashSet::new(),
+        loops : Vec::new(),
+    };
+
+    for file in program.get_files() {
+        checker.file = file;
+        for func in file.get_functions() {
+            checker.loops.clear();
+            checker.block(func.get_block(), Some(HashSet::new()));
+        }
+    }
+    checker.diagnostics
+}
+
+// The variables that surely have a value; None where control can't reach
+type State = Option<HashSet<NodeId>>;
+
+// The state where two paths meet
+fn join(a : State, b : State) -> State {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(&b).copied().collect()),
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+// A loop we are in, and the states its breaks and continues leave from
+struct LoopState {
+    label : Option<Symbol>,
+    breaks : State,
+    continues : State,
+}
+
+struct InitChecker<'a> {
+    res : &'a Resolution,
+    diagnostics : Vec<Diagnostic>,
+    file : &'a AstFile,
+
+    // The variables declared without a value, and where
+    declared : HashMap<NodeId, Span>,
+
+    // Each variable is only reported once
+    reported : HashSet<NodeId>,
+    loops : Vec<LoopState>,
+}
+
+impl<'a> InitChecker<'a> {
+    // The variable without a value that a name refers to, if it is one
+    fn tracked(&self, id : NodeId) -> Option<NodeId> {
+        let decl = self.res.get_binding(id)?;
+        if self.declared.contains_key(&decl) {
+            Some(decl)
+        } else {
+            None
+        }
+    }
+
+    fn read(&mut self, expr : &AstExpression, name : Symbol, state : &State) {
+        let decl = match self.tracked(expr.get_id()) {
+            Some(decl) => decl,
+            None => return,
+        };
+
+        if let Some(assigned) = state {
+            if !assigned.contains(&decl) && self.reported.insert(decl) {
+                let message = format!("The variable \"{}\" might not have a value here.", self.file.get_str(name));
+                let mut d = diag_new(Level::Error, self.file.get_name(), Some(expr.get_span()), message);
+                d.add_note(format!("It is declared without one at {}.", self.declared[&decl]));
+                self.diagnostics.push(d);
+            }
+        }
+    }
+
+    fn block(&mut self, block : &Vec<StmtRef>, mut state : State) -> State {
+        let file = self.file;
+        for stmt in block {
+            state = self.statement(file.get_statement(*stmt), state);
+        }
+        state
+    }
+
+    // Leaves through a break or continue, and returns the state after it
+    fn jump(&mut self, label : &Option<Symbol>, is_break : bool, state : State) -> State {
+        let target = match label {
+            Some(_) => self.loops.iter_mut().rev().find(|l| l.label == *label),
+            None => self.loops.last_mut(),
+        };
+
+        // Jumps outside of a loop are reported by the flow checks
+        if let Some(target) = target {
+            if is_break {
+                target.breaks = join(target.breaks.take(), state);
+            } else {
+                target.continues = join(target.continues.take(), state);
+            }
+        }
+        None
+    }
+
+    fn loop_body(&mut self, label : &Option<Symbol>, block : &Vec<StmtRef>, state : State) -> (State, LoopState) {
+        self.loops.push(LoopState {
+            label : *label,
+            breaks : None,
+            continues : None,
+        });
+        let end = self.block(block, state);
+        (end, self.loops.pop().unwrap())
+    }
+
+    //
+    // Returns the state after a statement
+    //
+    fn statement(&mut self, stmt : &AstStatement, state : State) -> State {
+        let file = self.file;
+        let e = |r : &ExprRef| file.get_expression(*r);
+        match stmt.get_kind() {
+            AstStatementKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(e(value), state);
+                }
+                None
+            },
+
+            AstStatementKind::VarDec { value : Some(value), .. } => self.expr(e(value), state),
+
+            AstStatementKind::VarDec { value : None, .. } => {
+                self.declared.insert(stmt.get_id(), stmt.get_span());
+                state
+            },
+
+            AstStatementKind::ArrayDec { size, .. } => self.expr(e(size), state),
+            AstStatementKind::StructDec { .. } => state,
+
+            AstStatementKind::CallStmt { args, .. } => {
+                let mut state = state;
+                for arg in args {
+                    state = self.expr(e(arg), state);
+                }
+                state
+            },
+
+            AstStatementKind::ExprStmt(expr) => self.expr(e(expr), state),
+
+            AstStatementKind::Break(label) => self.jump(label, true, state),
+            AstStatementKind::Continue(label) => self.jump(label, false, state),
+
+            // Whatever the body does, the loop can end before it runs
+            AstStatementKind::While { label, cond, block } => {
+                let state = self.expr(e(cond), state);
+                let (_, l) = self.loop_body(label, block, state.clone());
+
+                if *e(cond).get_kind() == AstExpressionKind::BoolLiteral(true) {
+                    l.breaks
+                } else {
+                    join(state, l.breaks)
+                }
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                let (_, l) = self.loop_body(label, block, state);
+                l.breaks
+            },
+
+            // The condition is reached through the end of the body or a continue
+            AstStatementKind::Repeat { label, block, cond } => {
+                let (end, l) = self.loop_body(label, block, state);
+                let tested = join(end, l.continues);
+                let after = self.expr(e(cond), tested);
+                join(after, l.breaks)
+            },
+
+            AstStatementKind::If { branches, else_block } => {
+                let mut state = state;
+                let mut out : State = None;
+                for br in branches {
+                    state = self.expr(e(&br.get_cond()), state);
+                    let end = self.block(br.get_block(), state.clone());
+                    out = join(out, end);
+                }
+
+                // Without an else, every condition can be false
+                match else_block {
+                    Some(block) => join(out, self.block(block, state)),
+                    None => join(out, state),
+                }
+            },
+
+            AstStatementKind::Match { expr, cases, default } => {
+                let mut state = self.expr(e(expr), state);
+                let mut out : State = None;
+                for case in cases {
+                    for value in case.get_values() {
+                        state = self.expr(e(value), state);
+                    }
+                    let end = self.block(case.get_block(), state.clone());
+                    out = join(out, end);
+                }
+
+                match default {
+                    Some(block) => join(out, self.block(block, state)),
+                    None => join(out, state),
+                }
+            },
+        }
+    }
+
+    //
+    // Checks the reads of an expression, and returns the state after any
+    // assignment in it
+    //
+    fn expr(&mut self, expr : &AstExpression, state : State) -> State {
+        let file = self.file;
+        let e = |r : &ExprRef| file.get_expression(*r);
+        match expr.get_kind() {
+            AstExpressionKind::Id(name) => {
+                self.read(expr, *name, &state);
+                state
+            },
+
+            AstExpressionKind::ArrayAcc { index, .. } => self.expr(e(index), state),
+
+            AstExpressionKind::Call { args, .. } => {
+                let mut state = state;
+                for arg in args {
+                    state = self.expr(e(arg), state);
+                }
+                state
+            },
+
+            AstExpressionKind::Cast { expr : inner, .. } => self.expr(e(inner), state),
+
+            AstExpressionKind::Binary { lhs, rhs, .. } => {
+                let state = self.expr(e(lhs), state);
+                self.expr(e(rhs), state)
+            },
+
+            // The value comes first; a compound assignment reads the variable too
+            AstExpressionKind::Assign { op, lhs, rhs } => {
+                let mut state = self.expr(e(rhs), state);
+                let lhs = e(lhs);
+                match lhs.get_kind() {
+                    AstExpressionKind::Id(name) => {
+                        if *op != AssignOp::Assign {
+                            self.read(lhs, *name, &state);
+                        }
+
+                        if let (Some(decl), Some(assigned)) = (self.tracked(lhs.get_id()), state.as_mut()) {
+                            assigned.insert(decl);
+                        }
+                        state
+                    },
+
+                    _ => self.expr(lhs, state),
+                }
+            },
+
+            _ => state,
+        }
+    }
+}
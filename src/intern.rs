@@ -0,0 +1,53 @@
+//
+// The identifier interner
+//
+// Each distinct name is stored once, and the tree holds a Symbol, which is
+// only an index. The scanner interns every name and string literal it reads,
+// and the interner goes with the file once it is parsed. Two symbols from the
+// same interner are the same name exactly when they are equal.
+//
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(pub u32);
+
+#[derive(Clone, Default)]
+pub struct Interner {
+    names : Vec<String>,
+    map : HashMap<String, Symbol>,
+}
+
+impl Interner {
+    // Returns the symbol for a name, adding the name if it's new
+    pub fn intern(&mut self, name : &str) -> Symbol {
+        if let Some(sym) = self.map.get(name) {
+            return *sym;
+        }
+
+        let sym = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.map.insert(name.to_string(), sym);
+        sym
+    }
+
+    // Returns the symbol for a name, if it has been interned
+    pub fn find(&self, name : &str) -> Option<Symbol> {
+        self.map.get(name).copied()
+    }
+
+    pub fn resolve(&self, sym : Symbol) -> &str {
+        &self.names[sym.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.len() == 0
+    }
+}
+
+pub fn interner_new() -> Interner {
+    Interner::default()
+}
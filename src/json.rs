@@ -0,0 +1,1089 @@
+//
+// The JSON encoding of the AST
+//
+// The document is an object with "format" set to "tlc-ast", the format "version"
+// and the "file" itself. Every node is an object; statements and expressions name
+// their variant in "kind", and carry their "id" and "span" ([line, col, end line,
+// end col]). Types are written like in source ("i32"), operators by their symbol
+// ("+", "+="), and missing optional parts are null.
+//
+// When decoding, "id" and "span" may be left out, which is handy for generated
+// trees; such nodes get fresh IDs after the highest one in the document.
+//
+use std::collections::HashSet;
+use std::fs;
+
+use crate::ast::*;
+use crate::intern::Symbol;
+
+// Version 2 lets the value of a "var_dec" be null; a version 1 document
+// is still read, since it is a version 2 document that never does that
+pub const VERSION : u64 = 2;
+
+//
+// A JSON value
+// Numbers are unsigned integers, since that is all the tree holds
+//
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+const BINARY_OPS : [BinaryOp; 19] = [
+    BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Mod,
+    BinaryOp::And, BinaryOp::Or, BinaryOp::Xor, BinaryOp::Lsh, BinaryOp::Rsh,
+    BinaryOp::Eq, BinaryOp::Ne, BinaryOp::Gt, BinaryOp::Lt, BinaryOp::Ge, BinaryOp::Le,
+    BinaryOp::LGAnd, BinaryOp::LGOr,
+    BinaryOp::Range,
+];
+
+const ASSIGN_OPS : [AssignOp; 11] = [
+    AssignOp::Assign,
+    AssignOp::Add, AssignOp::Sub, AssignOp::Mul, AssignOp::Div, AssignOp::Mod,
+    AssignOp::And, AssignOp::Or, AssignOp::Xor,
+    AssignOp::Lsh, AssignOp::Rsh,
+];
+
+const DATA_TYPES : [DataType; 12] = [
+    DataType::Void,
+    DataType::I8, DataType::U8, DataType::I16, DataType::U16,
+    DataType::I32, DataType::U32, DataType::I64, DataType::U64,
+    DataType::String, DataType::Char, DataType::Bool,
+];
+
+//
+// Encodes a file as a JSON document
+//
+pub fn encode(file : &AstFile) -> String {
+    let mut structs : Vec<Json> = Vec::new();
+    for s in file.get_structs() {
+        let mut items : Vec<Json> = Vec::new();
+        for item in s.get_items() {
+            items.push(encode_arg(file, item));
+        }
+
+        structs.push(object(vec![
+            ("id", Json::Number(s.get_id().0 as u64)),
+            ("span", encode_span(s.get_span())),
+            ("name", symbol(file, s.get_name())),
+            ("items", Json::Array(items)),
+        ]));
+    }
+
+    let mut functions : Vec<Json> = Vec::new();
+    for func in file.get_functions() {
+        functions.push(object(vec![
+            ("id", Json::Number(func.get_id().0 as u64)),
+            ("span", encode_span(func.get_span())),
+            ("name", symbol(file, func.get_name())),
+            ("type", string(func.get_data_type().name().to_string())),
+            ("args", Json::Array(func.get_args().iter().map(|arg| encode_arg(file, arg)).collect())),
+            ("consts", Json::Array(func.get_consts().iter().map(|c| encode_arg(file, c)).collect())),
+            ("body", encode_block(file, func.get_block())),
+        ]));
+    }
+
+    let doc = object(vec![
+        ("format", string("tlc-ast".to_string())),
+        ("version", Json::Number(VERSION)),
+        ("file", object(vec![
+            ("name", string(file.get_name().to_string())),
+            ("imports", Json::Array(file.get_imports().iter().map(|i| string(i.clone())).collect())),
+            ("structs", Json::Array(structs)),
+            ("consts", Json::Array(file.get_consts().iter().map(|c| encode_arg(file, c)).collect())),
+            ("functions", Json::Array(functions)),
+        ])),
+    ]);
+
+    let mut out = String::new();
+    doc.write(&mut out, 0);
+    out
+}
+
+fn encode_arg(file : &AstFile, arg : &AstArg) -> Json {
+    object(vec![
+        ("id", Json::Number(arg.get_id().0 as u64)),
+        ("span", encode_span(arg.get_span())),
+        ("name", symbol(file, arg.get_name())),
+        ("type", string(arg.get_data_type().name().to_string())),
+        ("value", optional(arg.get_expression().map(|expr| encode_expression(file, expr)))),
+    ])
+}
+
+fn encode_block(file : &AstFile, block : &[StmtRef]) -> Json {
+    Json::Array(block.iter().map(|stmt| encode_statement(file, *stmt)).collect())
+}
+
+fn encode_list(file : &AstFile, list : &[ExprRef]) -> Json {
+    Json::Array(list.iter().map(|expr| encode_expression(file, *expr)).collect())
+}
+
+fn encode_statement(file : &AstFile, stmt : StmtRef) -> Json {
+    let stmt = file.get_statement(stmt);
+    let mut fields : Vec<(&str, Json)> = Vec::new();
+
+    match stmt.get_kind() {
+        AstStatementKind::Return(expr) => {
+            fields.push(("kind", string("return".to_string())));
+            fields.push(("value", optional(expr.map(|expr| encode_expression(file, expr)))));
+        },
+
+        AstStatementKind::VarDec { name, data_type, value } => {
+            fields.push(("kind", string("var_dec".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("type", string(data_type.name().to_string())));
+            fields.push(("value", optional(value.map(|value| encode_expression(file, value)))));
+        },
+
+        AstStatementKind::ArrayDec { name, data_type, size } => {
+            fields.push(("kind", string("array_dec".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("type", string(data_type.name().to_string())));
+            fields.push(("size", encode_expression(file, *size)));
+        },
+
+        AstStatementKind::StructDec { name, struct_name } => {
+            fields.push(("kind", string("struct_dec".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("struct", symbol(file, *struct_name)));
+        },
+
+        AstStatementKind::CallStmt { name, args } => {
+            fields.push(("kind", string("call".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("args", encode_list(file, args)));
+        },
+
+        AstStatementKind::ExprStmt(expr) => {
+            fields.push(("kind", string("expr".to_string())));
+            fields.push(("value", encode_expression(file, *expr)));
+        },
+
+        AstStatementKind::While { label, cond, block } => {
+            fields.push(("kind", string("while".to_string())));
+            fields.push(("label", optional(label.map(|label| symbol(file, label)))));
+            fields.push(("cond", encode_expression(file, *cond)));
+            fields.push(("body", encode_block(file, block)));
+        },
+
+        AstStatementKind::Loop { label, block } => {
+            fields.push(("kind", string("loop".to_string())));
+            fields.push(("label", optional(label.map(|label| symbol(file, label)))));
+            fields.push(("body", encode_block(file, block)));
+        },
+
+        AstStatementKind::Repeat { label, block, cond } => {
+            fields.push(("kind", string("repeat".to_string())));
+            fields.push(("label", optional(label.map(|label| symbol(file, label)))));
+            fields.push(("body", encode_block(file, block)));
+            fields.push(("cond", encode_expression(file, *cond)));
+        },
+
+        AstStatementKind::Break(label) => {
+            fields.push(("kind", string("break".to_string())));
+            fields.push(("label", optional(label.map(|label| symbol(file, label)))));
+        },
+
+        AstStatementKind::Continue(label) => {
+            fields.push(("kind", string("continue".to_string())));
+            fields.push(("label", optional(label.map(|label| symbol(file, label)))));
+        },
+
+        AstStatementKind::If { branches, else_block } => {
+            let mut list : Vec<Json> = Vec::new();
+            for br in branches {
+                list.push(object(vec![
+                    ("cond", encode_expression(file, br.get_cond())),
+                    ("body", encode_block(file, br.get_block())),
+                ]));
+            }
+
+            fields.push(("kind", string("if".to_string())));
+            fields.push(("branches", Json::Array(list)));
+            fields.push(("else", optional(else_block.as_deref().map(|block| encode_block(file, block)))));
+        },
+
+        AstStatementKind::Match { expr, cases, default } => {
+            let mut list : Vec<Json> = Vec::new();
+            for case in cases {
+                list.push(object(vec![
+                    ("values", encode_list(file, case.get_values())),
+                    ("body", encode_block(file, case.get_block())),
+                ]));
+            }
+
+            fields.push(("kind", string("match".to_string())));
+            fields.push(("value", encode_expression(file, *expr)));
+            fields.push(("cases", Json::Array(list)));
+            fields.push(("else", optional(default.as_deref().map(|block| encode_block(file, block)))));
+        },
+    }
+
+    fields.insert(1, ("id", Json::Number(stmt.get_id().0 as u64)));
+    fields.insert(2, ("span", encode_span(stmt.get_span())));
+    object(fields)
+}
+
+fn encode_expression(file : &AstFile, expr : ExprRef) -> Json {
+    let expr = file.get_expression(expr);
+    let mut fields : Vec<(&str, Json)> = Vec::new();
+
+    match expr.get_kind() {
+        AstExpressionKind::Id(name) => {
+            fields.push(("kind", string("id".to_string())));
+            fields.push(("name", symbol(file, *name)));
+        },
+
+        AstExpressionKind::IntLiteral(val) => {
+            fields.push(("kind", string("int".to_string())));
+            fields.push(("value", Json::Number(*val)));
+        },
+
+        AstExpressionKind::CharLiteral(val) => {
+            fields.push(("kind", string("char".to_string())));
+            fields.push(("value", string(val.to_string())));
+        },
+
+        AstExpressionKind::StringLiteral(val) => {
+            fields.push(("kind", string("string".to_string())));
+            fields.push(("value", symbol(file, *val)));
+        },
+
+        AstExpressionKind::BoolLiteral(val) => {
+            fields.push(("kind", string("bool".to_string())));
+            fields.push(("value", Json::Bool(*val)));
+        },
+
+        AstExpressionKind::ArrayAcc { name, index } => {
+            fields.push(("kind", string("array_access".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("index", encode_expression(file, *index)));
+        },
+
+        AstExpressionKind::StructAcc { name, member } => {
+            fields.push(("kind", string("struct_access".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("member", symbol(file, *member)));
+        },
+
+        AstExpressionKind::Call { name, args } => {
+            fields.push(("kind", string("call".to_string())));
+            fields.push(("name", symbol(file, *name)));
+            fields.push(("args", encode_list(file, args)));
+        },
+
+        AstExpressionKind::Cast { data_type, expr } => {
+            fields.push(("kind", string("cast".to_string())));
+            fields.push(("type", string(data_type.name().to_string())));
+            fields.push(("value", encode_expression(file, *expr)));
+        },
+
+        AstExpressionKind::Binary { op, lhs, rhs } => {
+            fields.push(("kind", string("binary".to_string())));
+            fields.push(("op", string(op.symbol().to_string())));
+            fields.push(("lhs", encode_expression(file, *lhs)));
+            fields.push(("rhs", encode_expression(file, *rhs)));
+        },
+
+        AstExpressionKind::Assign { op, lhs, rhs } => {
+            fields.push(("kind", string("assign".to_string())));
+            fields.push(("op", string(op.symbol().to_string())));
+            fields.push(("lhs", encode_expression(file, *lhs)));
+            fields.push(("rhs", encode_expression(file, *rhs)));
+        },
+    }
+
+    fields.insert(1, ("id", Json::Number(expr.get_id().0 as u64)));
+    fields.insert(2, ("span", encode_span(expr.get_span())));
+    object(fields)
+}
+
+fn encode_span(span : Span) -> Json {
+    Json::Array(vec![
+        Json::Number(span.line as u64),
+        Json::Number(span.col as u64),
+        Json::Number(span.end_line as u64),
+        Json::Number(span.end_col as u64),
+    ])
+}
+
+//
+// Helpers for building values
+//
+fn object(fields : Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn string(val : String) -> Json {
+    Json::String(val)
+}
+
+// Names are written out as the text they stand for
+fn symbol(file : &AstFile, sym : Symbol) -> Json {
+    Json::String(file.get_str(sym).to_string())
+}
+
+fn optional(val : Option<Json>) -> Json {
+    val.unwrap_or(Json::Null)
+}
+
+//
+// Loads a file from a JSON document on disk
+//
+pub fn load(path : &str) -> Result<AstFile, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => decode(&text),
+        Err(e) => Err(format!("Unable to read {}: {}", path, e)),
+    }
+}
+
+//
+// Rebuilds a file from a JSON document
+//
+pub fn decode(text : &str) -> Result<AstFile, String> {
+    let mut reader = JsonReader {
+        chars : text.chars().collect(),
+        pos : 0,
+        depth : 0,
+    };
+    let doc = reader.read_document()?;
+
+    if get_str(&doc, "format")? != "tlc-ast" {
+        return Err("Not a tlc AST document.".to_string());
+    }
+
+    let version = get_number(&doc, "version")?;
+    if version == 0 || version > VERSION {
+        return Err(format!("Unsupported AST version {} (versions 1 to {} are supported).", version, VERSION));
+    }
+
+    // Nodes without an ID are numbered after the highest one we were given
+    let root = get_field(&doc, "file")?;
+    let mut decoder = Decoder {
+        file : ast_new_file(get_str(root, "name")?),
+        next_id : max_id(root).map(|id| id + 1).unwrap_or(0),
+        seen : HashSet::new(),
+    };
+    decoder.decode_file(root)?;
+    Ok(decoder.file)
+}
+
+//
+// Builds the file as it reads the document; nodes are added to it as
+// they are decoded, and names are interned into it
+//
+struct Decoder {
+    file : AstFile,
+    next_id : u32,
+    seen : HashSet<u32>,
+}
+
+impl Decoder {
+    fn decode_file(&mut self, obj : &Json) -> Result<(), String> {
+        for path in get_array(obj, "imports")? {
+            match path {
+                Json::String(path) => self.file.add_import(path.clone()),
+                _ => return Err("Expected a string in \"imports\".".to_string()),
+            }
+        }
+
+        for s in get_array(obj, "structs")? {
+            let mut ast_struct = ast_new_struct(self.get_symbol(s, "name")?, self.decode_id(s)?);
+            ast_struct.set_span(decode_span(s)?);
+            for item in get_array(s, "items")? {
+                ast_struct.add_item(self.decode_arg(item)?);
+            }
+            self.file.add_struct(ast_struct);
+        }
+
+        for c in get_array(obj, "consts")? {
+            let c = self.decode_arg(c)?;
+            self.file.add_const(c);
+        }
+
+        for f in get_array(obj, "functions")? {
+            let mut func = ast_new_function(self.get_symbol(f, "name")?, self.decode_id(f)?);
+            func.set_span(decode_span(f)?);
+            func.set_data_type(decode_data_type(f)?);
+            for arg in get_array(f, "args")? {
+                func.add_arg(self.decode_arg(arg)?);
+            }
+            for c in get_array(f, "consts")? {
+                func.add_const(self.decode_arg(c)?);
+            }
+            func.set_block(self.decode_block(f, "body")?);
+            self.file.add_function(func);
+        }
+
+        self.file.set_next_id(self.next_id);
+        Ok(())
+    }
+
+    fn decode_arg(&mut self, obj : &Json) -> Result<AstArg, String> {
+        let mut arg = ast_new_arg(self.get_symbol(obj, "name")?, decode_data_type(obj)?, self.decode_id(obj)?);
+        arg.set_span(decode_span(obj)?);
+        if let Some(value) = get_optional(obj, "value") {
+            arg.set_expression(self.decode_expression(value)?);
+        }
+        Ok(arg)
+    }
+
+    fn decode_block(&mut self, obj : &Json, key : &str) -> Result<Vec<StmtRef>, String> {
+        let mut block : Vec<StmtRef> = Vec::new();
+        for stmt in get_array(obj, key)? {
+            block.push(self.decode_statement(stmt)?);
+        }
+        Ok(block)
+    }
+
+    fn decode_statement(&mut self, obj : &Json) -> Result<StmtRef, String> {
+        let kind_name = get_str(obj, "kind")?;
+        let kind = match kind_name.as_str() {
+            "return" => {
+                let mut expr : Option<ExprRef> = None;
+                if let Some(value) = get_optional(obj, "value") {
+                    expr = Some(self.decode_expression(value)?);
+                }
+                AstStatementKind::Return(expr)
+            },
+
+            "var_dec" => {
+                let mut value : Option<ExprRef> = None;
+                if let Some(v) = get_optional(obj, "value") {
+                    value = Some(self.decode_expression(v)?);
+                }
+                AstStatementKind::VarDec {
+                    name : self.get_symbol(obj, "name")?,
+                    data_type : decode_data_type(obj)?,
+                    value : value,
+                }
+            },
+
+            "array_dec" => AstStatementKind::ArrayDec {
+                name : self.get_symbol(obj, "name")?,
+                data_type : decode_data_type(obj)?,
+                size : self.decode_expression(get_field(obj, "size")?)?,
+            },
+
+            "struct_dec" => AstStatementKind::StructDec {
+                name : self.get_symbol(obj, "name")?,
+                struct_name : self.get_symbol(obj, "struct")?,
+            },
+
+            "call" => AstStatementKind::CallStmt {
+                name : self.get_symbol(obj, "name")?,
+                args : self.decode_list(obj, "args")?,
+            },
+
+            "expr" => AstStatementKind::ExprStmt(self.decode_expression(get_field(obj, "value")?)?),
+
+            "while" => AstStatementKind::While {
+                label : self.get_label(obj)?,
+                cond : self.decode_expression(get_field(obj, "cond")?)?,
+                block : self.decode_block(obj, "body")?,
+            },
+
+            "loop" => AstStatementKind::Loop {
+                label : self.get_label(obj)?,
+                block : self.decode_block(obj, "body")?,
+            },
+
+            "repeat" => AstStatementKind::Repeat {
+                label : self.get_label(obj)?,
+                block : self.decode_block(obj, "body")?,
+                cond : self.decode_expression(get_field(obj, "cond")?)?,
+            },
+
+            "break" => AstStatementKind::Break(self.get_label(obj)?),
+            "continue" => AstStatementKind::Continue(self.get_label(obj)?),
+
+            "if" => {
+                let mut branches : Vec<AstBranch> = Vec::new();
+                for br in get_array(obj, "branches")? {
+                    let cond = self.decode_expression(get_field(br, "cond")?)?;
+                    let block = self.decode_block(br, "body")?;
+                    branches.push(ast_new_branch(cond, block));
+                }
+                if branches.len() == 0 {
+                    return Err("An \"if\" needs at least one branch.".to_string());
+                }
+
+                let mut else_block : Option<Vec<StmtRef>> = None;
+                if get_optional(obj, "else").is_some() {
+                    else_block = Some(self.decode_block(obj, "else")?);
+                }
+
+                AstStatementKind::If { branches : branches, else_block : else_block }
+            },
+
+            "match" => {
+                let expr = self.decode_expression(get_field(obj, "value")?)?;
+                let mut cases : Vec<AstCase> = Vec::new();
+                for case in get_array(obj, "cases")? {
+                    let values = self.decode_list(case, "values")?;
+                    let block = self.decode_block(case, "body")?;
+                    cases.push(ast_new_case(values, block));
+                }
+
+                let mut default : Option<Vec<StmtRef>> = None;
+                if get_optional(obj, "else").is_some() {
+                    default = Some(self.decode_block(obj, "else")?);
+                }
+
+                AstStatementKind::Match { expr : expr, cases : cases, default : default }
+            },
+
+            _ => return Err(format!("Unknown statement kind \"{}\".", kind_name)),
+        };
+
+        let stmt = ast_new_statement(kind, decode_span(obj)?, self.decode_id(obj)?);
+        Ok(self.file.add_statement(stmt))
+    }
+
+    fn decode_list(&mut self, obj : &Json, key : &str) -> Result<Vec<ExprRef>, String> {
+        let mut list : Vec<ExprRef> = Vec::new();
+        for expr in get_array(obj, key)? {
+            list.push(self.decode_expression(expr)?);
+        }
+        Ok(list)
+    }
+
+    fn decode_expression(&mut self, obj : &Json) -> Result<ExprRef, String> {
+        let kind_name = get_str(obj, "kind")?;
+        let kind = match kind_name.as_str() {
+            "id" => AstExpressionKind::Id(self.get_symbol(obj, "name")?),
+            "int" => AstExpressionKind::IntLiteral(get_number(obj, "value")?),
+            "string" => AstExpressionKind::StringLiteral(self.get_symbol(obj, "value")?),
+
+            "char" => {
+                let val = get_str(obj, "value")?;
+                let mut chars = val.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => AstExpressionKind::CharLiteral(c),
+                    _ => return Err(format!("Expected a single character, found \"{}\".", val)),
+                }
+            },
+
+            "bool" => match get_field(obj, "value")? {
+                Json::Bool(val) => AstExpressionKind::BoolLiteral(*val),
+                _ => return Err("Expected a boolean in \"value\".".to_string()),
+            },
+
+            "array_access" => AstExpressionKind::ArrayAcc {
+                name : self.get_symbol(obj, "name")?,
+                index : self.decode_expression(get_field(obj, "index")?)?,
+            },
+
+            "struct_access" => AstExpressionKind::StructAcc {
+                name : self.get_symbol(obj, "name")?,
+                member : self.get_symbol(obj, "member")?,
+            },
+
+            "call" => AstExpressionKind::Call {
+                name : self.get_symbol(obj, "name")?,
+                args : self.decode_list(obj, "args")?,
+            },
+
+            "cast" => AstExpressionKind::Cast {
+                data_type : decode_data_type(obj)?,
+                expr : self.decode_expression(get_field(obj, "value")?)?,
+            },
+
+            "binary" => {
+                let symbol = get_str(obj, "op")?;
+                let op = match BINARY_OPS.iter().find(|op| op.symbol() == symbol) {
+                    Some(op) => *op,
+                    None => return Err(format!("Unknown binary operator \"{}\".", symbol)),
+                };
+
+                AstExpressionKind::Binary {
+                    op : op,
+                    lhs : self.decode_expression(get_field(obj, "lhs")?)?,
+                    rhs : self.decode_expression(get_field(obj, "rhs")?)?,
+                }
+            },
+
+            "assign" => {
+                let symbol = get_str(obj, "op")?;
+                let op = match ASSIGN_OPS.iter().find(|op| op.symbol() == symbol) {
+                    Some(op) => *op,
+                    None => return Err(format!("Unknown assignment operator \"{}\".", symbol)),
+                };
+
+                AstExpressionKind::Assign {
+                    op : op,
+                    lhs : self.decode_expression(get_field(obj, "lhs")?)?,
+                    rhs : self.decode_expression(get_field(obj, "rhs")?)?,
+                }
+            },
+
+            _ => return Err(format!("Unknown expression kind \"{}\".", kind_name)),
+        };
+
+        let expr = ast_new_expression(kind, decode_span(obj)?, self.decode_id(obj)?);
+        Ok(self.file.add_expression(expr))
+    }
+
+    // Reads a name and interns it into the file
+    fn get_symbol(&mut self, obj : &Json, key : &str) -> Result<Symbol, String> {
+        let name = get_str(obj, key)?;
+        Ok(self.file.intern(&name))
+    }
+
+    fn get_label(&mut self, obj : &Json) -> Result<Option<Symbol>, String> {
+        match get_optional(obj, "label") {
+            Some(Json::String(label)) => Ok(Some(self.file.intern(label))),
+            Some(_) => Err("Expected a string in \"label\".".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    // Takes the ID of a node, or hands out a new one
+    fn decode_id(&mut self, obj : &Json) -> Result<NodeId, String> {
+        let id : u32;
+        match get_optional(obj, "id") {
+            Some(Json::Number(val)) if *val <= u32::MAX as u64 => id = *val as u32,
+            Some(_) => return Err("Expected a node ID in \"id\".".to_string()),
+
+            None => {
+                id = self.next_id;
+                self.next_id += 1;
+            },
+        }
+
+        if !self.seen.insert(id) {
+            return Err(format!("Node ID {} is used more than once.", id));
+        }
+        Ok(NodeId(id))
+    }
+}
+
+fn decode_span(obj : &Json) -> Result<Span, String> {
+    let list = match get_optional(obj, "span") {
+        Some(Json::Array(list)) if list.len() == 4 => list,
+        Some(_) => return Err("Expected [line, col, end line, end col] in \"span\".".to_string()),
+        None => return Ok(Span::default()),
+    };
+
+    let mut values : Vec<usize> = Vec::new();
+    for item in list {
+        match item {
+            Json::Number(val) => values.push(*val as usize),
+            _ => return Err("Expected numbers in \"span\".".to_string()),
+        }
+    }
+
+    Ok(Span {
+        line : values[0],
+        col : values[1],
+        end_line : values[2],
+        end_col : values[3],
+    })
+}
+
+fn decode_data_type(obj : &Json) -> Result<DataType, String> {
+    let name = get_str(obj, "type")?;
+    match DATA_TYPES.iter().find(|t| t.name() == name) {
+        Some(data_type) => Ok(*data_type),
+        None => Err(format!("Unknown data type \"{}\".", name)),
+    }
+}
+
+// Finds the highest node ID in a document
+fn max_id(value : &Json) -> Option<u32> {
+    let mut max : Option<u32> = None;
+    match value {
+        Json::Array(items) => {
+            for item in items {
+                max = max.max(max_id(item));
+            }
+        },
+
+        Json::Object(fields) => {
+            for (key, item) in fields {
+                if let (true, Json::Number(id)) = (key == "id", item) {
+                    if *id <= u32::MAX as u64 {
+                        max = max.max(Some(*id as u32));
+                    }
+                } else {
+                    max = max.max(max_id(item));
+                }
+            }
+        },
+
+        _ => {},
+    }
+    max
+}
+
+//
+// Helpers for reading objects
+//
+fn get_optional<'a>(obj : &'a Json, key : &str) -> Option<&'a Json> {
+    if let Json::Object(fields) = obj {
+        for (name, value) in fields {
+            if name == key {
+                if let Json::Null = value {
+                    return None;
+                }
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+fn get_field<'a>(obj : &'a Json, key : &str) -> Result<&'a Json, String> {
+    match obj {
+        Json::Object(_) => {
+            get_optional(obj, key).ok_or(format!("Missing field \"{}\".", key))
+        },
+
+        _ => Err(format!("Expected an object with \"{}\".", key)),
+    }
+}
+
+fn get_str(obj : &Json, key : &str) -> Result<String, String> {
+    match get_field(obj, key)? {
+        Json::String(val) => Ok(val.clone()),
+        _ => Err(format!("Expected a string in \"{}\".", key)),
+    }
+}
+
+fn get_number(obj : &Json, key : &str) -> Result<u64, String> {
+    match get_field(obj, key)? {
+        Json::Number(val) => Ok(*val),
+        _ => Err(format!("Expected a number in \"{}\".", key)),
+    }
+}
+
+fn get_array<'a>(obj : &'a Json, key : &str) -> Result<&'a Vec<Json>, String> {
+    match get_field(obj, key)? {
+        Json::Array(list) => Ok(list),
+        _ => Err(format!("Expected an array in \"{}\".", key)),
+    }
+}
+
+//
+// Writing JSON
+// Arrays of plain values stay on one line, everything else is indented
+//
+impl Json {
+    fn write(&self, out : &mut String, indent : usize) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(val) => out.push_str(if *val { "true" } else { "false" }),
+            Json::Number(val) => out.push_str(&val.to_string()),
+            Json::String(val) => write_string(out, val),
+
+            Json::Array(items) => {
+                if items.len() == 0 {
+                    out.push_str("[]");
+                    return;
+                }
+
+                let flat = items.iter().all(|item| match item {
+                    Json::Array(_) | Json::Object(_) => false,
+                    _ => true,
+                });
+
+                out.push('[');
+                let mut index : usize = 0;
+                for item in items {
+                    if index > 0 { out.push(','); }
+                    if flat {
+                        if index > 0 { out.push(' '); }
+                    } else {
+                        write_indent(out, indent + 2);
+                    }
+                    item.write(out, indent + 2);
+                    index += 1;
+                }
+                if !flat { write_indent(out, indent); }
+                out.push(']');
+            },
+
+            Json::Object(fields) => {
+                if fields.len() == 0 {
+                    out.push_str("{}");
+                    return;
+                }
+
+                out.push('{');
+                let mut index : usize = 0;
+                for (key, value) in fields {
+                    if index > 0 { out.push(','); }
+                    write_indent(out, indent + 2);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 2);
+                    index += 1;
+                }
+                write_indent(out, indent);
+                out.push('}');
+            },
+        }
+    }
+}
+
+fn write_indent(out : &mut String, indent : usize) {
+    out.push('\n');
+    for _i in 0 .. indent {
+        out.push(' ');
+    }
+}
+
+fn write_string(out : &mut String, val : &str) {
+    out.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+//
+// Reading JSON
+//
+// How deep objects and arrays can be nested; documents come from other
+// tools, so a runaway one has to be an error rather than a stack overflow
+const MAX_DEPTH : usize = 512;
+
+struct JsonReader {
+    chars : Vec<char>,
+    pos : usize,
+    depth : usize,
+}
+
+impl JsonReader {
+    fn read_document(&mut self) -> Result<Json, String> {
+        let value = self.read_value()?;
+        self.skip_whitespace();
+        if self.pos < self.chars.len() {
+            return Err(self.error("Unexpected text after the document."));
+        }
+        Ok(value)
+    }
+
+    fn read_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') | Some('[') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("The document is nested too deeply."));
+                }
+
+                self.depth += 1;
+                let value = if self.peek() == Some('{') { self.read_object() } else { self.read_array() };
+                self.depth -= 1;
+                value
+            },
+            Some('"') => Ok(Json::String(self.read_string()?)),
+            Some('t') => self.read_word("true", Json::Bool(true)),
+            Some('f') => self.read_word("false", Json::Bool(false)),
+            Some('n') => self.read_word("null", Json::Null),
+            Some(c) if c.is_ascii_digit() => self.read_number(),
+            Some('-') => Err(self.error("Negative numbers are not part of the format.")),
+            Some(_) => Err(self.error("Expected a value.")),
+            None => Err(self.error("Unexpected end of the document.")),
+        }
+    }
+
+    fn read_object(&mut self) -> Result<Json, String> {
+        let mut fields : Vec<(String, Json)> = Vec::new();
+        self.pos += 1;
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a field name."));
+            }
+            let key = self.read_string()?;
+
+            self.skip_whitespace();
+            if self.peek() != Some(':') {
+                return Err(self.error("Expected \':\' after the field name."));
+            }
+            self.pos += 1;
+
+            let value = self.read_value()?;
+            fields.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                },
+                _ => return Err(self.error("Expected \',\' or \'}\' in object.")),
+            }
+        }
+    }
+
+    fn read_array(&mut self) -> Result<Json, String> {
+        let mut items : Vec<Json> = Vec::new();
+        self.pos += 1;
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.read_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                },
+                _ => return Err(self.error("Expected \',\' or \']\' in array.")),
+            }
+        }
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let mut val = String::new();
+        self.pos += 1;
+
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string.")),
+            };
+
+            match c {
+                '"' => return Ok(val),
+
+                '\\' => {
+                    match self.next() {
+                        Some('"') => val.push('"'),
+                        Some('\\') => val.push('\\'),
+                        Some('/') => val.push('/'),
+                        Some('b') => val.push('\u{8}'),
+                        Some('f') => val.push('\u{c}'),
+                        Some('n') => val.push('\n'),
+                        Some('r') => val.push('\r'),
+                        Some('t') => val.push('\t'),
+
+                        Some('u') => {
+                            let mut code = self.read_hex()?;
+
+                            // Characters outside the basic plane come as a surrogate pair
+                            if (0xD800 .. 0xDC00).contains(&code) {
+                                if self.next() != Some('\\') || self.next() != Some('u') {
+                                    return Err(self.error("Expected the second half of a surrogate pair."));
+                                }
+                                let low = self.read_hex()?;
+                                if !(0xDC00 .. 0xE000).contains(&low) {
+                                    return Err(self.error("Invalid surrogate pair."));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+
+                            match std::char::from_u32(code) {
+                                Some(c) => val.push(c),
+                                None => return Err(self.error("Invalid character escape.")),
+                            }
+                        },
+
+                        _ => return Err(self.error("Invalid escape in string.")),
+                    }
+                },
+
+                c => val.push(c),
+            }
+        }
+    }
+
+    fn read_hex(&mut self) -> Result<u32, String> {
+        let mut code : u32 = 0;
+        for _i in 0 .. 4 {
+            match self.next().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.error("Expected four hex digits.")),
+            }
+        }
+        Ok(code)
+    }
+
+    fn read_number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() { break; }
+            self.pos += 1;
+        }
+
+        if let Some('.') | Some('e') | Some('E') = self.peek() {
+            return Err(self.error("Only whole numbers are part of the format."));
+        }
+
+        let text : String = self.chars[start .. self.pos].iter().collect();
+        match text.parse::<u64>() {
+            Ok(val) => Ok(Json::Number(val)),
+            Err(_) => Err(self.error("Number is too large.")),
+        }
+    }
+
+    fn read_word(&mut self, word : &str, value : Json) -> Result<Json, String> {
+        for c in word.chars() {
+            if self.next() != Some(c) {
+                return Err(self.error("Expected a value."));
+            }
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() { break; }
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    // Builds an error message pointing at where we are in the document
+    fn error(&self, msg : &str) -> String {
+        let mut line : usize = 1;
+        let mut col : usize = 1;
+        for c in &self.chars[.. self.pos.min(self.chars.len())] {
+            if *c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        format!("JSON {}:{}: {}", line, col, msg)
+    }
+}
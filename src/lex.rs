@@ -1,6 +1,10 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
+use crate::ast::Span;
+use crate::diag::*;
+use crate::intern::*;
+
 //
 // Defines the tokens
 //
@@ -17,12 +21,16 @@ pub enum Token {
     Var,
     While,
     Do,
+    Loop,
+    Repeat, Until,
     If, Elif, Else,
+    Match, Case,
     Then,
     Break, Continue,
     Struct,
     Const,
     Import,
+    As,
     
     // Type keywords
     I8, U8,
@@ -40,16 +48,21 @@ pub enum Token {
     Colon,
     Comma,
     Dot,
+    Range,
     Arrow,
     Assign,
+    AddAssign, SubAssign, MulAssign, DivAssign, ModAssign,
+    AndAssign, OrAssign, XorAssign,
+    LshAssign, RshAssign,
     Add, Sub, Mul, Div, Mod,
     And, Or, Xor,
+    Lsh, Rsh,
     Eq, Ne, Gt, Lt, Ge, Le,
     LGAnd, LGOr,
     
-    // Literals
-    Id(String),
-    StringL(String),
+    // Literals; names and strings are interned by the scanner
+    Id(Symbol),
+    StringL(Symbol),
     IntL(u64),
     CharL(char),
     True, False,
@@ -61,9 +74,24 @@ pub enum Token {
 pub struct Scanner {
     pub file_name : String,
     contents : String,
+    chars : Vec<char>,          // The contents, for reading by position
     pos : usize,
     buffer : String,
-    stack : Vec<Token>,
+    stack : Vec<(Token, Span)>,
+
+    // Where each line starts in the contents: (position, line, column)
+    lines : Vec<(usize, usize, usize)>,
+
+    // Where the buffer started, and the spans of the last two tokens
+    start : usize,
+    span : Span,
+    prev_span : Span,
+
+    // Errors in the text itself, such as a literal that never ends
+    diagnostics : Vec<Diagnostic>,
+
+    // Every name and string literal read so far
+    names : Interner,
 }
 
 impl Scanner {
@@ -75,71 +103,154 @@ impl Scanner {
     }
     
     // Inits the lexer and loads a file
-    pub fn init(&mut self) {
-        match File::open(self.file_name.clone()) {
-            Ok(file) => {
-                let reader = BufReader::new(&file);
+    pub fn init(&mut self) -> Result<(), String> {
+        let file = match File::open(self.file_name.clone()) {
+            Ok(file) => file,
+            Err(e) => return Err(e.to_string()),
+        };
 
-                for ln in reader.lines() {
-                    let line = ln.unwrap().trim().to_string();
-                    if line.len() > 0 {
-                        self.contents += &line;
-                        self.contents += &" ".to_string();
-                    }
-                }
+        let reader = BufReader::new(&file);
+        let mut line_no : usize = 0;
+        for ln in reader.lines() {
+            line_no += 1;
+            match ln {
+                Ok(raw) => self.add_line(&raw, line_no),
+                Err(e) => return Err(e.to_string()),
             }
+        }
+        Ok(())
+    }
 
-            Err(e) => {
-                println!("Fatal Error!");
-                println!("{}", e);
-            }
-        };
+    // Inits the lexer from source text instead of a file
+    pub fn init_source(&mut self, source : &str) {
+        for (i, raw) in source.lines().enumerate() {
+            self.add_line(raw, i + 1);
+        }
+    }
+
+    // Blank lines are dropped, and the rest are trimmed and joined with spaces
+    fn add_line(&mut self, raw : &str, line_no : usize) {
+        let line = raw.trim();
+        if line.len() == 0 {
+            return;
+        }
+
+        let col = raw.chars().count() - raw.trim_start().chars().count() + 1;
+        let offset = self.chars.len();
+        self.lines.push((offset, line_no, col));
+
+        self.contents += line;
+        self.contents += " ";
+        self.chars.extend(line.chars());
+        self.chars.push(' ');
     }
     
     // Unget the last token
     pub fn unget(&mut self, token : Token) {
-        self.stack.push(token);
+        self.stack.push((token, self.span));
+        self.span = self.prev_span;
+    }
+    
+    // Returns the span of the last token
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    // Hands over the errors found so far
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    pub fn get_names(&self) -> &Interner {
+        &self.names
+    }
+
+    // For names the parser makes up, such as the empty name it puts in
+    // place of a missing one
+    pub fn intern(&mut self, name : &str) -> Symbol {
+        self.names.intern(name)
+    }
+
+    // Hands over the names, for the file that was parsed
+    pub fn take_names(&mut self) -> Interner {
+        std::mem::take(&mut self.names)
+    }
+
+    // A token the way it is written in messages, with names spelled out
+    pub fn describe(&self, token : &Token) -> String {
+        match token {
+            Token::Id(sym) => format!("Id({:?})", self.names.resolve(*sym)),
+            Token::StringL(sym) => format!("StringL({:?})", self.names.resolve(*sym)),
+            _ => format!("{:?}", token),
+        }
     }
     
     // Gets the next token in sequence
     pub fn get_next(&mut self) -> Token {
-        loop {
-            if self.stack.len() > 0 {
-                return self.stack.pop().unwrap();
-            }
+        self.prev_span = self.span;
+        if let Some((token, span)) = self.stack.pop() {
+            self.span = span;
+            return token;
+        }
         
-            if self.pos >= self.contents.len() {
-                return Token::Eof;
+        let (token, start, end) = self.read_token();
+        self.span = self.make_span(start, end);
+        token
+    }
+    
+    // Reads a token from the contents, along with where it starts and ends
+    fn read_token(&mut self) -> (Token, usize, usize) {
+        loop {
+            if self.pos >= self.chars.len() {
+                return (Token::Eof, self.pos, self.pos);
             }
             
             let mut c = self.get_char();
             
             // Check string literals
             if c == '\"' {
+                let start = self.pos - 1;
                 let mut val = String::new();
-                c = self.get_char();
-                while c != '\"' {
-                    val.push(c);
+                loop {
+                    if self.pos >= self.chars.len() {
+                        return self.unterminated(start);
+                    }
+
                     c = self.get_char();
+                    if c == '\"' {
+                        break;
+                    }
+                    val.push(c);
                 }
-                return Token::StringL(val);
+                return (Token::StringL(self.names.intern(&val)), start, self.pos);
             }
             
             // Check character literals
             if c == '\'' {
+                let start = self.pos - 1;
+                if self.pos + 2 > self.chars.len() {
+                    return self.unterminated(start);
+                }
+
                 let c2 = self.get_char();
-                self.get_char();        // Assume '
-                return Token::CharL(c2);
+                if self.get_char() != '\'' {
+                    self.pos -= 1;
+                    self.error(start, self.pos, "Unterminated literal.");
+                }
+                return (Token::CharL(c2), start, self.pos);
             }
             
             if self.is_separator(c) || self.is_symbol(c) {
+                let end = self.pos - 1;
+                
                 // If we have a symbol, get it and check the buffer
                 if self.is_symbol(c) {
                     let token1 = self.get_symbol(c);
                     if self.buffer.len() == 0 {
-                        return token1;
+                        return (token1, end, self.pos);
                     } else {
-                        self.stack.push(token1);
+                        let span = self.make_span(end, self.pos);
+                        self.stack.push((token1, span));
                     }
                 }
                 
@@ -152,14 +263,14 @@ impl Scanner {
                 let mut token : Token = self.get_keyword();
                 if token != Token::None {
                     self.buffer = String::new();
-                    return token;
+                    return (token, self.start, end);
                 }
                 
                 // See if we have an integer
                 if self.is_integer() {
                     token = Token::IntL(self.get_integer());
                     self.buffer = String::new();
-                    return token;
+                    return (token, self.start, end);
                 }
                 
                 // See if we have a hex literal
@@ -168,25 +279,70 @@ impl Scanner {
                         let base = self.buffer.trim_start_matches("0x");
                         token = Token::IntL(u64::from_str_radix(base, 16).unwrap());
                         self.buffer = String::new();
-                        return token;
+                        return (token, self.start, end);
                     },
                     
                     _ => {},
                 }
                 
                 // Otherwise, we have an indentifier
-                token = Token::Id(self.buffer.clone());
+                token = Token::Id(self.names.intern(&self.buffer));
                 self.buffer = String::new();
-                return token;
+                return (token, self.start, end);
             } else {
+                if self.buffer.len() == 0 {
+                    self.start = self.pos - 1;
+                }
                 self.buffer.push(c);
             }
         }
     }
     
+    // Reports a literal that runs into the end of the input, which ends
+    // the scan
+    fn unterminated(&mut self, start : usize) -> (Token, usize, usize) {
+        self.error(start, self.chars.len(), "Unterminated literal.");
+        self.pos = self.chars.len();
+        (Token::Eof, self.pos, self.pos)
+    }
+
+    fn error(&mut self, start : usize, end : usize, message : &str) {
+        let span = self.make_span(start, end);
+        let d = diag_new(Level::Error, &self.file_name, Some(span), message.to_string());
+        self.diagnostics.push(d);
+    }
+
+    // Maps a position in the contents back to a line and column
+    fn get_location(&self, pos : usize) -> (usize, usize) {
+        let index = self.lines.partition_point(|(offset, _, _)| *offset <= pos);
+        if index == 0 {
+            return (1, 1);
+        }
+        let (offset, line, col) = self.lines[index - 1];
+        (line, col + pos - offset)
+    }
+    
+    // Builds a span from a start and end position; the end is exclusive
+    fn make_span(&self, start : usize, end : usize) -> Span {
+        let (line, col) = self.get_location(start);
+        let (end_line, end_col) = if end > start {
+            let (l, c) = self.get_location(end - 1);
+            (l, c + 1)
+        } else {
+            (line, col)
+        };
+        
+        Span {
+            line : line,
+            col : col,
+            end_line : end_line,
+            end_col : end_col,
+        }
+    }
+    
     // A helper function for getting the next character in the stream
     fn get_char(&mut self) -> char {
-        let c : char = self.contents.chars().nth(self.pos).unwrap();
+        let c : char = self.chars[self.pos];
         self.pos += 1;
         c
     }
@@ -234,23 +390,73 @@ impl Scanner {
             ']' => return Token::RBracket,
             ';' => return Token::SemiColon,
             ',' => return Token::Comma,
-            '.' => return Token::Dot,
-            '+' => return Token::Add,
-            '*' => return Token::Mul,
-            '/' => return Token::Div,
-            '%' => return Token::Mod,
-            '^' => return Token::Xor,
             '=' => return Token::Eq,
             
+            '.' => {
+                let c2 = self.get_char();
+                if c2 == '.' {
+                    return Token::Range;
+                }
+                self.pos -= 1;
+                return Token::Dot;
+            },
+            
+            '+' => {
+                let c2 = self.get_char();
+                if c2 == '=' {
+                    return Token::AddAssign;
+                }
+                self.pos -= 1;
+                return Token::Add;
+            },
+            
             '-' => {
                 let c2 = self.get_char();
                 if c2 == '>' {
                     return Token::Arrow;
+                } else if c2 == '=' {
+                    return Token::SubAssign;
                 }
                 self.pos -= 1;
                 return Token::Sub;
             },
             
+            '*' => {
+                let c2 = self.get_char();
+                if c2 == '=' {
+                    return Token::MulAssign;
+                }
+                self.pos -= 1;
+                return Token::Mul;
+            },
+            
+            '/' => {
+                let c2 = self.get_char();
+                if c2 == '=' {
+                    return Token::DivAssign;
+                }
+                self.pos -= 1;
+                return Token::Div;
+            },
+            
+            '%' => {
+                let c2 = self.get_char();
+                if c2 == '=' {
+                    return Token::ModAssign;
+                }
+                self.pos -= 1;
+                return Token::Mod;
+            },
+            
+            '^' => {
+                let c2 = self.get_char();
+                if c2 == '=' {
+                    return Token::XorAssign;
+                }
+                self.pos -= 1;
+                return Token::Xor;
+            },
+            
             ':' => {
                 let c2 = self.get_char();
                 if c2 == '=' {
@@ -273,6 +479,13 @@ impl Scanner {
                 let c2 = self.get_char();
                 if c2 == '=' {
                     return Token::Ge;
+                } else if c2 == '>' {
+                    let c3 = self.get_char();
+                    if c3 == '=' {
+                        return Token::RshAssign;
+                    }
+                    self.pos -= 1;
+                    return Token::Rsh;
                 }
                 self.pos -= 1;
                 return Token::Gt;
@@ -282,6 +495,13 @@ impl Scanner {
                 let c2 = self.get_char();
                 if c2 == '=' {
                     return Token::Le;
+                } else if c2 == '<' {
+                    let c3 = self.get_char();
+                    if c3 == '=' {
+                        return Token::LshAssign;
+                    }
+                    self.pos -= 1;
+                    return Token::Lsh;
                 }
                 self.pos -= 1;
                 return Token::Lt;
@@ -291,6 +511,8 @@ impl Scanner {
                 let c2 = self.get_char();
                 if c2 == '&' {
                     return Token::LGAnd;
+                } else if c2 == '=' {
+                    return Token::AndAssign;
                 }
                 self.pos -= 1;
                 return Token::And;
@@ -300,6 +522,8 @@ impl Scanner {
                 let c2 = self.get_char();
                 if c2 == '|' {
                     return Token::LGOr;
+                } else if c2 == '=' {
+                    return Token::OrAssign;
                 }
                 self.pos -= 1;
                 return Token::Or;
@@ -311,36 +535,7 @@ impl Scanner {
     
     // A helper function for converting the buffer to a token
     fn get_keyword(&self) -> Token {
-        if self.buffer == "func" { return Token::Func; }
-        else if self.buffer == "is" { return Token::Is; }
-        else if self.buffer == "end" { return Token::End; }
-        else if self.buffer == "return" { return Token::Return; }
-        else if self.buffer == "var" { return Token::Var; }
-        else if self.buffer == "while" { return Token::While; }
-        else if self.buffer == "do" { return Token::Do; }
-        else if self.buffer == "if" { return Token::If; }
-        else if self.buffer == "elif" { return Token::Elif; }
-        else if self.buffer == "else" { return Token::Else; }
-        else if self.buffer == "then" { return Token::Then; }
-        else if self.buffer == "break" { return Token::Break; }
-        else if self.buffer == "continue" { return Token::Continue; }
-        else if self.buffer == "struct" { return Token::Struct; }
-        else if self.buffer == "const" { return Token::Const; }
-        else if self.buffer == "import" { return Token::Import; }
-        else if self.buffer == "i8" { return Token::I8; }
-        else if self.buffer == "u8" { return Token::U8; }
-        else if self.buffer == "i16" { return Token::I16; }
-        else if self.buffer == "u16" { return Token::U16; }
-        else if self.buffer == "i32" { return Token::I32; }
-        else if self.buffer == "u32" { return Token::U32; }
-        else if self.buffer == "i64" { return Token::I64; }
-        else if self.buffer == "u64" { return Token::U64; }
-        else if self.buffer == "string" { return Token::String; }
-        else if self.buffer == "char" { return Token::Char; }
-        else if self.buffer == "bool" { return Token::Bool; }
-        else if self.buffer == "true" { return Token::True; }
-        else if self.buffer == "false" { return Token::False; }
-        Token::None
+        keyword(&self.buffer)
     }
     
     // A helper function for converting a buffer into an integer
@@ -353,6 +548,48 @@ impl Scanner {
     }
 }
 
+//
+// Maps a word to its keyword token, or Token::None if it isn't one
+//
+pub fn keyword(word : &str) -> Token {
+    if word == "func" { return Token::Func; }
+    else if word == "is" { return Token::Is; }
+    else if word == "end" { return Token::End; }
+    else if word == "return" { return Token::Return; }
+    else if word == "var" { return Token::Var; }
+    else if word == "while" { return Token::While; }
+    else if word == "do" { return Token::Do; }
+    else if word == "loop" { return Token::Loop; }
+    else if word == "repeat" { return Token::Repeat; }
+    else if word == "until" { return Token::Until; }
+    else if word == "if" { return Token::If; }
+    else if word == "elif" { return Token::Elif; }
+    else if word == "else" { return Token::Else; }
+    else if word == "match" { return Token::Match; }
+    else if word == "case" { return Token::Case; }
+    else if word == "then" { return Token::Then; }
+    else if word == "break" { return Token::Break; }
+    else if word == "continue" { return Token::Continue; }
+    else if word == "struct" { return Token::Struct; }
+    else if word == "const" { return Token::Const; }
+    else if word == "import" { return Token::Import; }
+    else if word == "as" { return Token::As; }
+    else if word == "i8" { return Token::I8; }
+    else if word == "u8" { return Token::U8; }
+    else if word == "i16" { return Token::I16; }
+    else if word == "u16" { return Token::U16; }
+    else if word == "i32" { return Token::I32; }
+    else if word == "u32" { return Token::U32; }
+    else if word == "i64" { return Token::I64; }
+    else if word == "u64" { return Token::U64; }
+    else if word == "string" { return Token::String; }
+    else if word == "char" { return Token::Char; }
+    else if word == "bool" { return Token::Bool; }
+    else if word == "true" { return Token::True; }
+    else if word == "false" { return Token::False; }
+    Token::None
+}
+
 //
 // A helper function for creating a lexer object
 //
@@ -360,9 +597,16 @@ pub fn lex_new(file_name : String) -> Scanner {
     Scanner {
         file_name : file_name,
         contents : String::new(),
+        chars : Vec::new(),
         pos : 0,
         buffer : String::new(),
         stack : Vec::new(),
+        lines : Vec::new(),
+        start : 0,
+        span : Span::default(),
+        prev_span : Span::default(),
+        diagnostics : Vec::new(),
+        names : interner_new(),
     }
 }
 
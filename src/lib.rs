@@ -0,0 +1,88 @@
+//! The Tiny Lang front end
+//!
+//! Everything tlc does is available here: the scanner, the parser, the AST
+//! and its visitors, the module loader, and the writers that turn a tree back
+//! into source or into one of the AST views. Errors are collected as
+//! diagnostics rather than printed, so tools can decide what to do with them.
+//!
+//! Parsing a string and walking the tree:
+//!
+//! ```
+//! use tlc::ast::*;
+//! use tlc::parser::parser_new;
+//! use tlc::visit::*;
+//!
+//! // Collects the name of every function called
+//! struct Calls {
+//!     names : Vec<String>,
+//! }
+//!
+//! impl Visitor for Calls {
+//!     fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+//!         if let AstStatementKind::CallStmt { name, .. } = stmt.get_kind() {
+//!             self.names.push(file.get_str(*name).to_string());
+//!         }
+//!         walk_statement(self, file, stmt);
+//!     }
+//!
+//!     fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+//!         if let AstExpressionKind::Call { name, .. } = expr.get_kind() {
+//!             self.names.push(file.get_str(*name).to_string());
+//!         }
+//!         walk_expression(self, file, expr);
+//!     }
+//! }
+//!
+//! let source = "
+//!     func main -> i32 is
+//!         var x : i32 := add(1, 2);
+//!         print(x);
+//!         return 0;
+//!     end
+//! ";
+//!
+//! let mut parser = parser_new("main.tl".to_string());
+//! parser.init_source(source);
+//! parser.run();
+//! assert!(!parser.has_errors());
+//!
+//! let file = parser.get_file();
+//! let mut calls = Calls { names : Vec::new() };
+//! calls.visit_file(file);
+//! assert_eq!(calls.names, vec!["add", "print"]);
+//!
+//! // And back to source
+//! let text = tlc::unwriter::render(file);
+//! assert!(text.starts_with("func main -> i32 is"));
+//! ```
+
+// The code base spells out returns, field names and counters on purpose
+#![allow(clippy::needless_return, clippy::redundant_field_names,
+         clippy::needless_late_init, clippy::len_zero,
+         clippy::println_empty_string, clippy::explicit_counter_loop,
+         clippy::single_match, clippy::match_like_matches_macro,
+         clippy::collapsible_if)]
+
+pub mod ast;
+pub mod build;
+pub mod collide;
+pub mod consteval;
+pub mod diag;
+pub mod diff;
+pub mod dot;
+pub mod flow;
+pub mod init;
+pub mod intern;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lex;
+pub mod lint;
+pub mod loader;
+pub mod parser;
+pub mod resolve;
+pub mod sexp;
+pub mod signature;
+pub mod structs;
+pub mod typeck;
+pub mod unwriter;
+pub mod visit;
@@ -0,0 +1,55 @@
+//
+// bool_compare: comparing a bool with true, or for inequality with false,
+// which is the same as the bool itself
+//
+// There is no "not" operator, so "x = false" is how a bool is negated, and
+// is left alone.
+//
+use crate::ast::*;
+use crate::lint::{finding_new, Finding, Lint, LintContext};
+use crate::visit::*;
+
+pub struct BoolCompare;
+
+impl Lint for BoolCompare {
+    fn name(&self) -> &'static str {
+        "bool_compare"
+    }
+
+    fn description(&self) -> &'static str {
+        "a comparison with true, or with false for inequality, that does nothing"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let mut finder = Finder { found : Vec::new() };
+        finder.visit_file(cx.get_file());
+        finder.found
+    }
+}
+
+struct Finder {
+    found : Vec<Finding>,
+}
+
+impl Visitor for Finder {
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        if let AstExpressionKind::Binary { op, lhs, rhs } = expr.get_kind() {
+            let literal = match (file.get_expression(*lhs).get_kind(), file.get_expression(*rhs).get_kind()) {
+                (AstExpressionKind::BoolLiteral(b), _) | (_, AstExpressionKind::BoolLiteral(b)) => Some(*b),
+                _ => None,
+            };
+
+            let redundant = match (op, literal) {
+                (BinaryOp::Eq, Some(true)) => Some("= true"),
+                (BinaryOp::Ne, Some(false)) => Some("!= false"),
+                _ => None,
+            };
+
+            if let Some(what) = redundant {
+                let message = format!("Comparing with \"{}\" does nothing; use the value itself.", what);
+                self.found.push(finding_new(Some(expr.get_span()), message));
+            }
+        }
+        walk_expression(self, file, expr);
+    }
+}
@@ -0,0 +1,139 @@
+//
+// constant_condition: a condition that is known while compiling
+//
+// A branch that is always or never taken, a while loop that never runs,
+// and a loop whose condition never lets it end unless something breaks out
+// of it. "while true" with a break or a return is left alone, as a loop
+// with its exit in the middle.
+//
+use crate::ast::*;
+use crate::consteval::Value;
+use crate::intern::Symbol;
+use crate::lint::{finding_new, Finding, Lint, LintContext};
+use crate::resolve::DeclKind;
+use crate::visit::*;
+
+pub struct ConstantCondition;
+
+impl Lint for ConstantCondition {
+    fn name(&self) -> &'static str {
+        "constant_condition"
+    }
+
+    fn description(&self) -> &'static str {
+        "a condition that is always true or always false"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let mut finder = Finder {
+            cx : cx,
+            found : Vec::new(),
+        };
+        finder.visit_file(cx.get_file());
+        finder.found
+    }
+}
+
+struct Finder<'a, 'b> {
+    cx : &'a LintContext<'b>,
+    found : Vec<Finding>,
+}
+
+impl Finder<'_, '_> {
+    // The value of a condition that is a literal or a constant
+    fn value(&self, cond : &AstExpression) -> Option<bool> {
+        match cond.get_kind() {
+            AstExpressionKind::BoolLiteral(b) => Some(*b),
+
+            AstExpressionKind::Id(_) => {
+                let res = self.cx.get_resolution();
+                let decl = res.get_binding(cond.get_id())?;
+                if res.get_decl(decl)?.get_kind() != DeclKind::Const {
+                    return None;
+                }
+
+                match self.cx.get_values().get_value(decl) {
+                    Some(Value::Bool(b)) => Some(*b),
+                    _ => None,
+                }
+            },
+
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, cond : &AstExpression, message : String) {
+        self.found.push(finding_new(Some(cond.get_span()), message));
+    }
+}
+
+impl Visitor for Finder<'_, '_> {
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        match stmt.get_kind() {
+            AstStatementKind::If { branches, .. } => {
+                for br in branches {
+                    let cond = file.get_expression(br.get_cond());
+                    if let Some(b) = self.value(cond) {
+                        let message = format!("The condition is always {}, so the branch is {} taken.", b,
+                            if b { "always" } else { "never" });
+                        self.report(cond, message);
+                    }
+                }
+            },
+
+            AstStatementKind::While { cond, block, .. } => match self.value(file.get_expression(*cond)) {
+                Some(false) => self.report(file.get_expression(*cond), "The condition is always false, so the loop never runs.".to_string()),
+                Some(true) if !breaks(file, block, &[]) => {
+                    self.report(file.get_expression(*cond), "The condition is always true, and nothing breaks out of the loop.".to_string());
+                },
+                _ => {},
+            },
+
+            AstStatementKind::Repeat { block, cond, .. } => match self.value(file.get_expression(*cond)) {
+                Some(true) => self.report(file.get_expression(*cond), "The condition is always true, so the loop runs once.".to_string()),
+                Some(false) if !breaks(file, block, &[]) => {
+                    self.report(file.get_expression(*cond), "The condition is always false, and nothing breaks out of the loop.".to_string());
+                },
+                _ => {},
+            },
+
+            _ => {},
+        }
+        walk_statement(self, file, stmt);
+    }
+}
+
+//
+// Whether a break or return in a block leaves the loop the block belongs to
+// The labels are those of the loops within that loop that the block is in.
+// An unlabeled break in one of them only leaves that one, and so does a
+// break aimed at one of them; any other break leaves the loop, even if it
+// goes further. A return leaves every loop
+//
+fn breaks(file : &AstFile, block : &[StmtRef], inner : &[Option<Symbol>]) -> bool {
+    block.iter().any(|stmt| match file.get_statement(*stmt).get_kind() {
+        AstStatementKind::Break(None) => inner.len() == 0,
+        AstStatementKind::Break(target) => !inner.contains(target),
+        AstStatementKind::Return(_) => true,
+
+        AstStatementKind::While { label, block, .. }
+        | AstStatementKind::Loop { label, block }
+        | AstStatementKind::Repeat { label, block, .. } => {
+            let mut labels = inner.to_vec();
+            labels.push(*label);
+            breaks(file, block, &labels)
+        },
+
+        AstStatementKind::If { branches, else_block } => {
+            branches.iter().any(|br| breaks(file, br.get_block(), inner))
+                || else_block.as_ref().is_some_and(|b| breaks(file, b, inner))
+        },
+
+        AstStatementKind::Match { cases, default, .. } => {
+            cases.iter().any(|case| breaks(file, case.get_block(), inner))
+                || default.as_ref().is_some_and(|b| breaks(file, b, inner))
+        },
+
+        _ => false,
+    })
+}
@@ -0,0 +1,75 @@
+//
+// empty_block: a function, branch, case or loop with nothing in it
+//
+use crate::ast::*;
+use crate::lint::{finding_new, Finding, Lint, LintContext};
+use crate::visit::*;
+
+pub struct EmptyBlock;
+
+impl Lint for EmptyBlock {
+    fn name(&self) -> &'static str {
+        "empty_block"
+    }
+
+    fn description(&self) -> &'static str {
+        "a function, branch, case or loop body with no statements"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let mut finder = Finder { found : Vec::new() };
+        finder.visit_file(cx.get_file());
+        finder.found
+    }
+}
+
+struct Finder {
+    found : Vec<Finding>,
+}
+
+impl Finder {
+    fn check(&mut self, block : &[StmtRef], span : Span, message : &str) {
+        if block.is_empty() {
+            self.found.push(finding_new(Some(span), message.to_string()));
+        }
+    }
+}
+
+impl Visitor for Finder {
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        let message = format!("The function \"{}\" is empty.", file.get_str(func.get_name()));
+        self.check(func.get_block(), func.get_span(), &message);
+        walk_function(self, file, func);
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        let span = stmt.get_span();
+        match stmt.get_kind() {
+            AstStatementKind::While { block, .. }
+            | AstStatementKind::Loop { block, .. }
+            | AstStatementKind::Repeat { block, .. } => self.check(block, span, "The body of this loop is empty."),
+
+            AstStatementKind::If { branches, else_block } => {
+                for br in branches {
+                    self.check(br.get_block(), file.get_expression(br.get_cond()).get_span(), "This branch is empty.");
+                }
+                if let Some(block) = else_block {
+                    self.check(block, span, "The else branch of this if is empty.");
+                }
+            },
+
+            AstStatementKind::Match { cases, default, .. } => {
+                for case in cases {
+                    let at = case.get_values().first().map_or(span, |v| file.get_expression(*v).get_span());
+                    self.check(case.get_block(), at, "This case is empty.");
+                }
+                if let Some(block) = default {
+                    self.check(block, span, "The else branch of this match is empty.");
+                }
+            },
+
+            _ => {},
+        }
+        walk_statement(self, file, stmt);
+    }
+}
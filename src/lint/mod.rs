@@ -0,0 +1,209 @@
+//
+// Lints
+//
+// Warnings about code that is legal, but probably not what was meant. Each
+// lint is a small module of its own, with a name, the level it starts at,
+// and a check that looks over the root file of a checked program and
+// returns what it found. The level of each lint can be changed by name:
+// allow turns it off, warn reports it, and deny makes it an error.
+//
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::consteval::ConstValues;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::resolve::Resolution;
+
+mod bool_compare;
+mod constant_condition;
+mod empty_block;
+mod unused_args;
+mod unused_consts;
+mod unused_imports;
+mod unused_variables;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        }
+    }
+}
+
+//
+// Something a lint found; the linter gives it a level
+// Imports have no span, so neither do their findings
+//
+pub struct Finding {
+    span : Option<Span>,
+    message : String,
+}
+
+pub fn finding_new(span : Option<Span>, message : String) -> Finding {
+    Finding {
+        span : span,
+        message : message,
+    }
+}
+
+//
+// What a lint gets to look at
+//
+pub struct LintContext<'a> {
+    program : &'a Program,
+    res : &'a Resolution,
+    values : &'a ConstValues,
+
+    // The declarations something refers to
+    used : HashMap<NodeId, usize>,
+}
+
+impl LintContext<'_> {
+    //
+    // Getter functions
+    //
+    pub fn get_program(&self) -> &Program {
+        self.program
+    }
+
+    pub fn get_file(&self) -> &AstFile {
+        self.program.get_root()
+    }
+
+    pub fn get_resolution(&self) -> &Resolution {
+        self.res
+    }
+
+    pub fn get_values(&self) -> &ConstValues {
+        self.values
+    }
+
+    // Whether anything in the program refers to a declaration
+    pub fn is_used(&self, decl : NodeId) -> bool {
+        self.used.contains_key(&decl)
+    }
+}
+
+pub fn context_new<'a>(program : &'a Program, res : &'a Resolution, values : &'a ConstValues) -> LintContext<'a> {
+    let mut used : HashMap<NodeId, usize> = HashMap::new();
+    for decl in res.get_bindings().values() {
+        *used.entry(*decl).or_insert(0) += 1;
+    }
+
+    LintContext {
+        program : program,
+        res : res,
+        values : values,
+        used : used,
+    }
+}
+
+pub trait Lint {
+    fn name(&self) -> &'static str;
+
+    // One line on what the lint looks for
+    fn description(&self) -> &'static str;
+
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding>;
+}
+
+//
+// Every lint, and the level each one is at
+//
+pub struct Linter {
+    lints : Vec<Box<dyn Lint>>,
+    levels : HashMap<&'static str, LintLevel>,
+}
+
+impl Linter {
+    //
+    // Setter functions
+    //
+    pub fn set_level(&mut self, name : &str, level : LintLevel) -> Result<(), String> {
+        match self.lints.iter().find(|lint| lint.name() == name) {
+            Some(lint) => {
+                self.levels.insert(lint.name(), level);
+                Ok(())
+            },
+
+            None => Err(format!("Unknown lint \"{}\".", name)),
+        }
+    }
+
+    //
+    // Getter functions
+    //
+    pub fn get_lints(&self) -> &Vec<Box<dyn Lint>> {
+        &self.lints
+    }
+
+    pub fn get_level(&self, name : &str) -> LintLevel {
+        self.levels.get(name).copied().unwrap_or(LintLevel::Allow)
+    }
+
+    //
+    // Runs every lint that isn't allowed, and returns what they found in
+    // the order of the file
+    // Each message ends with the name of its lint
+    //
+    pub fn run(&self, cx : &LintContext) -> Vec<Diagnostic> {
+        let file_name = cx.get_file().get_name();
+        let mut found : Vec<(Option<Span>, Diagnostic)> = Vec::new();
+        for lint in &self.lints {
+            let level = match self.get_level(lint.name()) {
+                LintLevel::Allow => continue,
+                LintLevel::Warn => Level::Warning,
+                LintLevel::Deny => Level::Error,
+            };
+
+            for finding in lint.check(cx) {
+                let message = format!("{} [{}]", finding.message, lint.name());
+                found.push((finding.span, diag_new(level, file_name, finding.span, message)));
+            }
+        }
+
+        found.sort_by_key(|(span, _)| span.map(|s| (s.line, s.col)));
+        found.into_iter().map(|(_, d)| d).collect()
+    }
+}
+
+pub fn linter_new() -> Linter {
+    let lints : Vec<Box<dyn Lint>> = vec![
+        Box::new(unused_variables::UnusedVariables),
+        Box::new(unused_args::UnusedArgs),
+        Box::new(unused_consts::UnusedConsts),
+        Box::new(unused_imports::UnusedImports),
+        Box::new(constant_condition::ConstantCondition),
+        Box::new(bool_compare::BoolCompare),
+        Box::new(empty_block::EmptyBlock),
+    ];
+
+    let mut levels : HashMap<&'static str, LintLevel> = HashMap::new();
+    for lint in &lints {
+        levels.insert(lint.name(), lint.default_level());
+    }
+
+    Linter {
+        lints : lints,
+        levels : levels,
+    }
+}
+
+// Names starting with an underscore are meant to go unused
+fn is_ignored(name : &str) -> bool {
+    name.starts_with('_')
+}
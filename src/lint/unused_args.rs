@@ -0,0 +1,31 @@
+//
+// unused_args: an argument the function never refers to
+//
+use crate::lint::{finding_new, is_ignored, Finding, Lint, LintContext};
+
+pub struct UnusedArgs;
+
+impl Lint for UnusedArgs {
+    fn name(&self) -> &'static str {
+        "unused_args"
+    }
+
+    fn description(&self) -> &'static str {
+        "a function argument that is never used"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let mut found : Vec<Finding> = Vec::new();
+        let file = cx.get_file();
+        for func in file.get_functions() {
+            for arg in func.get_args() {
+                let name = file.get_str(arg.get_name());
+                if !cx.is_used(arg.get_id()) && !is_ignored(name) {
+                    let message = format!("The argument \"{}\" of \"{}\" is never used.", name, file.get_str(func.get_name()));
+                    found.push(finding_new(Some(arg.get_span()), message));
+                }
+            }
+        }
+        found
+    }
+}
@@ -0,0 +1,31 @@
+//
+// unused_consts: a constant, global or local, that nothing refers to
+//
+use crate::lint::{finding_new, is_ignored, Finding, Lint, LintContext};
+
+pub struct UnusedConsts;
+
+impl Lint for UnusedConsts {
+    fn name(&self) -> &'static str {
+        "unused_consts"
+    }
+
+    fn description(&self) -> &'static str {
+        "a constant that is never used"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let file = cx.get_file();
+        let locals = file.get_functions().iter().flat_map(|func| func.get_consts());
+
+        let mut found : Vec<Finding> = Vec::new();
+        for c in file.get_consts().iter().chain(locals) {
+            let name = file.get_str(c.get_name());
+            if !cx.is_used(c.get_id()) && !is_ignored(name) {
+                let message = format!("The constant \"{}\" is never used.", name);
+                found.push(finding_new(Some(c.get_span()), message));
+            }
+        }
+        found
+    }
+}
@@ -0,0 +1,89 @@
+//
+// unused_imports: a module the file imports, but uses nothing from
+// Using something from a module that the import pulled in counts too
+//
+use crate::ast::*;
+use crate::lint::{finding_new, Finding, Lint, LintContext};
+use crate::visit::*;
+
+pub struct UnusedImports;
+
+impl Lint for UnusedImports {
+    fn name(&self) -> &'static str {
+        "unused_imports"
+    }
+
+    fn description(&self) -> &'static str {
+        "an imported module that nothing is used from"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        // The files the declarations used by the root file are in
+        let mut collector = Collector {
+            cx : cx,
+            files : Vec::new(),
+        };
+        collector.visit_file(cx.get_file());
+
+        let program = cx.get_program();
+        let mut found : Vec<Finding> = Vec::new();
+        for module in program.get_imports(0) {
+            let reached = reachable(cx, *module);
+            if !collector.files.iter().any(|f| reached.contains(f)) {
+                let message = format!("The module \"{}\" is imported, but nothing from it is used.", program.get_name(*module));
+                found.push(finding_new(None, message));
+            }
+        }
+        found
+    }
+}
+
+// A module and everything it pulls in
+fn reachable(cx : &LintContext, module : usize) -> Vec<usize> {
+    let mut found : Vec<usize> = Vec::new();
+    let mut stack : Vec<usize> = vec![module];
+    while let Some(next) = stack.pop() {
+        if next == 0 || found.contains(&next) {
+            continue;
+        }
+        found.push(next);
+        stack.extend(cx.get_program().get_imports(next).iter());
+    }
+    found
+}
+
+struct Collector<'a, 'b> {
+    cx : &'a LintContext<'b>,
+    files : Vec<usize>,
+}
+
+impl Collector<'_, '_> {
+    fn use_of(&mut self, id : NodeId) {
+        let res = self.cx.get_resolution();
+        if let Some(decl) = res.get_binding(id).and_then(|d| res.get_decl(d)) {
+            if !self.files.contains(&decl.get_file()) {
+                self.files.push(decl.get_file());
+            }
+        }
+    }
+}
+
+impl Visitor for Collector<'_, '_> {
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        self.use_of(stmt.get_id());
+
+        // A structure variable uses the structure it is declared with
+        let res = self.cx.get_resolution();
+        if let Some(decl) = res.get_type_binding(stmt.get_id()).and_then(|d| res.get_decl(d)) {
+            if !self.files.contains(&decl.get_file()) {
+                self.files.push(decl.get_file());
+            }
+        }
+        walk_statement(self, file, stmt);
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        self.use_of(expr.get_id());
+        walk_expression(self, file, expr);
+    }
+}
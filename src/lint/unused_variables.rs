@@ -0,0 +1,53 @@
+//
+// unused_variables: a variable, array or structure variable that nothing
+// refers to
+//
+use crate::ast::*;
+use crate::lint::{finding_new, is_ignored, Finding, Lint, LintContext};
+use crate::visit::*;
+
+pub struct UnusedVariables;
+
+impl Lint for UnusedVariables {
+    fn name(&self) -> &'static str {
+        "unused_variables"
+    }
+
+    fn description(&self) -> &'static str {
+        "a local variable that is never used"
+    }
+
+    fn check(&self, cx : &LintContext) -> Vec<Finding> {
+        let mut finder = Finder {
+            cx : cx,
+            found : Vec::new(),
+        };
+        finder.visit_file(cx.get_file());
+        finder.found
+    }
+}
+
+struct Finder<'a, 'b> {
+    cx : &'a LintContext<'b>,
+    found : Vec<Finding>,
+}
+
+impl Visitor for Finder<'_, '_> {
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        let declared = match stmt.get_kind() {
+            AstStatementKind::VarDec { name, .. } => Some(("variable", name)),
+            AstStatementKind::ArrayDec { name, .. } => Some(("array", name)),
+            AstStatementKind::StructDec { name, .. } => Some(("structure variable", name)),
+            _ => None,
+        };
+
+        if let Some((what, name)) = declared {
+            let name = file.get_str(*name);
+            if !self.cx.is_used(stmt.get_id()) && !is_ignored(name) {
+                let message = format!("The {} \"{}\" is never used.", what, name);
+                self.found.push(finding_new(Some(stmt.get_span()), message));
+            }
+        }
+        walk_statement(self, file, stmt);
+    }
+}
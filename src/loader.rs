@@ -0,0 +1,204 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::parser;
+
+//
+// The errors the loader can run into
+//
+pub enum LoadError {
+    // The module could not be found in any search directory
+    // (module path, importing module)
+    Missing(String, String),
+
+    // The modules import each other; the chain starts and ends
+    // with the same module
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Missing(module, from) => {
+                write!(f, "Module \"{}\" imported by \"{}\" not found.", module, from)
+            },
+
+            LoadError::Cycle(chain) => {
+                write!(f, "Import cycle: {}", chain.join(" -> "))
+            },
+        }
+    }
+}
+
+//
+// A complete program: the root file and every module it pulls in
+// The root is always the first file
+//
+pub struct Program {
+    names : Vec<String>,
+    files : Vec<AstFile>,
+    imports : Vec<Vec<usize>>,
+}
+
+impl Program {
+    pub fn get_root(&self) -> &AstFile {
+        &self.files[0]
+    }
+
+    pub fn get_files(&self) -> &Vec<AstFile> {
+        &self.files
+    }
+
+    // Returns the module name of a file ("std/io"); the root uses its path
+    pub fn get_name(&self, index : usize) -> &str {
+        &self.names[index]
+    }
+
+    // Returns the indices of the files a file imports
+    pub fn get_imports(&self, index : usize) -> &Vec<usize> {
+        &self.imports[index]
+    }
+}
+
+//
+// The loader context
+//
+pub struct Loader {
+    search_dirs : Vec<PathBuf>,
+    errors : Vec<LoadError>,
+
+    // What the parser found in each module we loaded
+    diagnostics : Vec<Diagnostic>,
+
+    // The resolved path of each loaded file, indexed like Program::files
+    paths : Vec<PathBuf>,
+}
+
+impl Loader {
+    pub fn add_search_dir(&mut self, dir : String) {
+        self.search_dirs.push(PathBuf::from(dir));
+    }
+
+    pub fn get_errors(&self) -> &Vec<LoadError> {
+        &self.errors
+    }
+
+    pub fn get_diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    //
+    // Loads every module reachable from an already parsed root file
+    // The directory of the root is searched before the search directories
+    //
+    pub fn load(&mut self, root : AstFile) -> Program {
+        let root_path = PathBuf::from(root.get_name());
+        if let Some(dir) = root_path.parent() {
+            self.search_dirs.insert(0, dir.to_path_buf());
+        }
+
+        self.paths.clear();
+        self.paths.push(root_path.canonicalize().unwrap_or(root_path));
+
+        let mut program = Program {
+            names : vec![root.get_name().to_string()],
+            files : vec![root],
+            imports : vec![Vec::new()],
+        };
+
+        let mut stack : Vec<usize> = Vec::new();
+        let mut done : Vec<bool> = vec![false];
+        self.load_imports(&mut program, 0, &mut stack, &mut done);
+
+        program
+    }
+
+    //
+    // Walks the imports of a file depth first
+    // The stack holds the chain of files we are currently in, so
+    // reaching one of them again means we have a cycle
+    //
+    fn load_imports(&mut self, program : &mut Program, index : usize, stack : &mut Vec<usize>, done : &mut Vec<bool>) {
+        stack.push(index);
+
+        let imports = program.files[index].get_imports().clone();
+        for module in imports {
+            let path : PathBuf;
+            match self.resolve(&module) {
+                Some(p) => path = p,
+                None => {
+                    self.errors.push(LoadError::Missing(module, program.names[index].clone()));
+                    continue;
+                },
+            }
+
+            // Each module is only parsed once, however it is reached
+            let key = path.canonicalize().unwrap_or(path.clone());
+            match self.paths.iter().position(|p| *p == key) {
+                Some(next) => {
+                    program.imports[index].push(next);
+
+                    if !done[next] {
+                        let start = stack.iter().position(|i| *i == next).unwrap();
+                        let mut chain : Vec<String> = Vec::new();
+                        for i in &stack[start ..] {
+                            chain.push(program.names[*i].clone());
+                        }
+                        chain.push(program.names[next].clone());
+                        self.errors.push(LoadError::Cycle(chain));
+                    }
+                },
+
+                None => {
+                    // Node IDs go on from where the last file stopped, so
+                    // they stay unique across the program
+                    let mut parser = parser::parser_new(path.to_string_lossy().to_string());
+                    parser.ast.set_next_id(program.files.last().unwrap().get_next_id());
+                    parser.init();
+                    parser.run();
+                    self.diagnostics.extend(parser.get_diagnostics().iter().cloned());
+
+                    let next = program.files.len();
+                    program.names.push(module);
+                    program.files.push(parser.take_file());
+                    program.imports.push(Vec::new());
+                    program.imports[index].push(next);
+                    self.paths.push(key);
+                    done.push(false);
+
+                    self.load_imports(program, next, stack, done);
+                },
+            }
+        }
+
+        stack.pop();
+        done[index] = true;
+    }
+
+    // Maps a module path ("std/io") to a file in the search directories
+    // The path is kept as found, so diagnostics name the file the way the
+    // search directory was given
+    fn resolve(&self, module : &str) -> Option<PathBuf> {
+        for dir in &self.search_dirs {
+            let path = dir.join(Path::new(&format!("{}.tl", module)));
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+        None
+    }
+}
+
+//
+// A helper function to create the loader
+//
+pub fn loader_new() -> Loader {
+    Loader {
+        search_dirs : Vec::new(),
+        errors : Vec::new(),
+        diagnostics : Vec::new(),
+        paths : Vec::new(),
+    }
+}
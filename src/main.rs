@@ -1,25 +1,82 @@
-use std::env;
+// The code base spells out returns, field names and counters on purpose
+#![allow(clippy::needless_return, clippy::redundant_field_names,
+         clippy::needless_late_init, clippy::len_zero,
+         clippy::println_empty_string, clippy::explicit_counter_loop,
+         clippy::single_match, clippy::match_like_matches_macro,
+         clippy::collapsible_if)]
 
-mod ast;
-mod lex;
-mod parser;
-mod unwriter;
+use std::env;
+use std::process;
 
-use crate::ast::*;
+use tlc::ast::*;
+use tlc::{collide, consteval, diff, dot, flow, init, lint, loader, parser, resolve, sexp, structs, typeck, unwriter};
+use tlc::diag::{Diagnostic, Level};
+use tlc::lint::{Linter, LintLevel};
+use tlc::loader::Program;
+#[cfg(feature = "json")]
+use tlc::json;
 
 fn main() {
-    let mut ast_debug = false;
+    let mut ast_format = String::new();
     let mut input = String::new();
+    let mut loader = loader::loader_new();
+    let mut shadowing = Some(Level::Warning);
 
     let args : Vec<String> = env::args().collect();
-    let mut index = 0;
-    for arg in args {
+    if args.len() > 1 && args[1] == "diff" {
+        run_diff(&args[2 ..]);
+        return;
+    }
+
+    // "tlc check file.tl" runs the semantic checks instead of printing, and
+    // "tlc lint file.tl" the lints
+    let check = args.len() > 1 && args[1] == "check";
+    let linting = args.len() > 1 && args[1] == "lint";
+    let mut linter = lint::linter_new();
+    let mut index = if check || linting { 2 } else { 1 };
+    while index < args.len() {
+        let arg = args[index].clone();
         if arg == "--ast" {
-            ast_debug = true;
-        } else {
-            if index > 0 {
-                input = arg;
+            ast_format = "debug".to_string();
+        } else if let Some(format) = arg.strip_prefix("--ast=") {
+            ast_format = format.to_string();
+        } else if let Some(level) = arg.strip_prefix("--shadow=") {
+            shadowing = match level {
+                "allow" => None,
+                "warn" => Some(Level::Warning),
+                "deny" => Some(Level::Error),
+                _ => {
+                    println!("Error: Unknown shadowing level \"{}\"; use allow, warn or deny.", level);
+                    return;
+                },
+            };
+        } else if arg == "-A" || arg == "-W" || arg == "-D" {
+            let level = match arg.as_str() {
+                "-A" => LintLevel::Allow,
+                "-W" => LintLevel::Warn,
+                _ => LintLevel::Deny,
+            };
+
+            index += 1;
+            let name = args.get(index).cloned().unwrap_or_default();
+            if let Err(e) = linter.set_level(&name, level) {
+                println!("Error: {}", e);
+                process::exit(1);
             }
+        } else if arg == "--list" && linting {
+            for l in linter.get_lints() {
+                println!("{:<20} {:<6} {}", l.name(), linter.get_level(l.name()).name(), l.description());
+            }
+            return;
+        } else if arg == "-I" {
+            index += 1;
+            if index < args.len() {
+                loader.add_search_dir(args[index].clone());
+            }
+        } else if let Some(dir) = arg.strip_prefix("-I") {
+            loader.add_search_dir(dir.to_string());
+        } else {
+            input = arg;
         }
         index += 1;
     }
@@ -41,17 +98,184 @@ fn main() {
     }
     println!("{:?}", token);*/
     
-    let mut parser = parser::parser_new(input);
-    parser.init();
-    parser.run();
+    // The input is either source, or a tree encoded as JSON by another tool
+    let json_input = input.ends_with(".json");
+    let mut parser = parser::parser_new(input.clone());
+    let file : AstFile;
+    if json_input {
+        match load_json(&input) {
+            Ok(f) => file = f,
+            Err(e) => {
+                println!("Error: {}", e);
+                process::exit(1);
+            },
+        }
+    } else {
+        parser.init();
+        parser.run();
+        file = parser.take_file();
+    }
+    
+    let mut errors = report(parser.get_diagnostics());
     
-    if ast_debug {
-        parser.debug();
+    // Pull in everything the file imports
+    let program = loader.load(file);
+    errors += report(loader.get_diagnostics());
+    for e in loader.get_errors() {
+        println!("Error: {}", e);
+        errors += 1;
+    }
+    
+    // A failed check fails the command, so scripts can tell
+    if check {
+        errors += run_check(&program, shadowing);
+        if errors > 0 {
+            process::exit(1);
+        }
+        return;
+    } else if linting {
+        errors += run_lint(&program, &linter);
+        if errors > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+    
+    if ast_format == "json" {
+        print_json(program.get_root());
+    } else if ast_format == "dot" {
+        print!("{}", dot::render(program.get_root()));
+    } else if ast_format == "sexp" {
+        print!("{}", sexp::render(program.get_root()));
+    } else if ast_format == "debug" {
+        if !json_input {
+            parser.debug_source();
+            println!("=================");
+        }
+        program.get_root().print();
+        
+        for i in 1 .. program.get_files().len() {
+            println!("=================");
+            print!("MODULE {}", program.get_name(i));
+            for j in program.get_imports(i) {
+                print!(" -> {}", program.get_name(*j));
+            }
+            println!("");
+            program.get_files()[i].print();
+        }
+    } else if ast_format.len() > 0 {
+        println!("Error: Unknown AST format \"{}\".", ast_format);
     } else {
         // Currently, we use an unwriter to print
-        let file : AstFile = parser.get_file();
-        unwriter::unwrite(file);
+        unwriter::unwrite(program.get_root());
     }
 }
 
+//
+// Prints diagnostics, and returns how many of them are errors
+//
+fn report(diagnostics : &[Diagnostic]) -> usize {
+    let mut errors = 0;
+    for d in diagnostics {
+        println!("{}", d);
+        if d.get_level() == Level::Error {
+            errors += 1;
+        }
+    }
+    errors
+}
 
+//
+// Runs the semantic checks over a loaded program, and returns the number
+// of errors found
+//
+fn run_check(program : &Program, shadowing : Option<Level>) -> usize {
+    let resolution = resolve::resolve(program);
+    let mut errors = report(resolution.get_diagnostics());
+    errors += report(&collide::check(program, shadowing));
+    errors += report(&structs::check(program, &resolution));
+
+    let types = typeck::check(program, &resolution);
+    errors += report(types.get_diagnostics());
+
+    let values = consteval::evaluate(program, &resolution, &types);
+    errors += report(values.get_diagnostics());
+
+    errors += report(&flow::check(program));
+    errors += report(&init::check(program, &resolution));
+    errors
+}
+
+//
+// Runs the lints over the root file; the checks run first, but it's up
+// to "tlc check" to report what they find
+// Returns the number of findings of denied lints
+//
+fn run_lint(program : &Program, linter : &Linter) -> usize {
+    let resolution = resolve::resolve(program);
+    let types = typeck::check(program, &resolution);
+    let values = consteval::evaluate(program, &resolution, &types);
+
+    let cx = lint::context_new(program, &resolution, &values);
+    report(&linter.run(&cx))
+}
+
+//
+// "tlc diff old.tl new.tl" compares two versions of a file by their trees
+//
+fn run_diff(args : &[String]) {
+    if args.len() != 2 {
+        println!("Error: Usage: tlc diff old.tl new.tl");
+        process::exit(1);
+    }
+
+    let mut files : Vec<AstFile> = Vec::new();
+    let mut errors = false;
+    for path in args {
+        let mut parser = parser::parser_new(path.clone());
+        parser.init();
+        parser.run();
+        for d in parser.get_diagnostics() {
+            println!("{}", d);
+        }
+
+        errors = errors || parser.has_errors();
+        files.push(parser.take_file());
+    }
+
+    // A tree with errors in it would only give noise
+    if errors {
+        process::exit(1);
+    }
+
+    let changes = diff::diff(&files[0], &files[1]);
+    if changes.len() == 0 {
+        println!("No changes.");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+}
+
+//
+// The JSON support can be left out of the build
+//
+#[cfg(feature = "json")]
+fn load_json(path : &str) -> Result<AstFile, String> {
+    json::load(path)
+}
+
+#[cfg(not(feature = "json"))]
+fn load_json(_path : &str) -> Result<AstFile, String> {
+    Err("tlc was built without JSON support.".to_string())
+}
+
+#[cfg(feature = "json")]
+fn print_json(file : &AstFile) {
+    println!("{}", json::encode(file));
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json(_file : &AstFile) {
+    println!("Error: tlc was built without JSON support.");
+}
@@ -1,5 +1,7 @@
 use crate::lex::*;
 use crate::ast::*;
+use crate::diag::*;
+use crate::intern::*;
 
 //
 // The parser context
@@ -9,24 +11,64 @@ pub struct Parser {
     pub ast : AstFile,
     scanner : Scanner,
     local_consts : Vec<AstArg>,
+
+    // The labels of the loops we are in, and the label waiting for the
+    // next loop
+    loops : Vec<Option<Symbol>>,
+    label : Option<Symbol>,
+
+    // Whether we are reading the values of a case, the only place a range
+    // can be used
+    case_values : bool,
+
+    diagnostics : Vec<Diagnostic>,
 }
 
 impl Parser {
     // Handy utility functions
     pub fn init(&mut self) {
-        self.scanner.init();
+        if let Err(e) = self.scanner.init() {
+            let mut d = diag_new(Level::Error, &self.file_name, None, "Unable to read file.".to_string());
+            d.add_note(e);
+            self.diagnostics.push(d);
+        }
     }
-    
+
+    // Parses source text rather than a file; the file name is only used
+    // for naming the AST and in diagnostics
+    pub fn init_source(&mut self, source : &str) {
+        self.scanner.init_source(source);
+    }
+
     pub fn debug(&self) {
-        self.scanner.debug();
+        self.debug_source();
         println!("=================");
         self.ast.print();
     }
-    
-    pub fn get_file(&self) -> AstFile {
-        self.ast.clone()
+
+    pub fn debug_source(&self) {
+        self.scanner.debug();
+    }
+
+    pub fn get_file(&self) -> &AstFile {
+        &self.ast
+    }
+
+    // Hands over the tree without copying it; the parser is left with an
+    // empty file
+    pub fn take_file(&mut self) -> AstFile {
+        let empty = ast_new_file(self.file_name.clone());
+        std::mem::replace(&mut self.ast, empty)
+    }
+
+    pub fn get_diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.is_error())
     }
-    
+
     //
     // The main run function
     // This operates on the global scope
@@ -37,156 +79,171 @@ impl Parser {
             match token {
                 Token::Func => self.build_function(),
                 Token::Struct => self.build_struct_def(),
-                
+
                 Token::Const => {
-                    let c = self.build_const();
-                    self.ast.add_const(c);
+                    if let Some(c) = self.build_const() {
+                        self.ast.add_const(c);
+                    }
                 },
-                
+
                 Token::Import => {
                     let mut path = String::new();
                     let mut token = self.scanner.get_next();
                     while token != Token::SemiColon {
                         match token {
-                            Token::Id(val) => path.push_str(&val.clone()),
+                            Token::Id(val) => path.push_str(self.scanner.get_names().resolve(val)),
                             Token::Dot => path.push('/'),
-                            
+
                             _ => {},
                         }
-                        
+
                         token = self.scanner.get_next();
                     }
-                    
+
                     self.ast.add_import(path);
                 },
-                
+
                 _ => {
-                    println!("Error: Unknown token in global scope.");
-                    println!("-> {:?}", token);
+                    self.error_with("Unknown token in global scope.", self.scanner.describe(&token));
                 },
             }
-            
+
             token = self.scanner.get_next();
         }
+
+        let rest = self.scanner.take_diagnostics();
+        self.diagnostics.extend(rest);
+
+        // The names the scanner found go with the file
+        let names = self.scanner.take_names();
+        self.ast.set_names(names);
     }
-    
+
     //
     // Builds a structure definition
     //
     pub fn build_struct_def(&mut self) {
+        let start = self.scanner.get_span();
         let mut token = self.scanner.get_next();
-        let struct_name : String;
+        let struct_name : Symbol;
         match token {
             Token::Id(name) => struct_name = name,
-            
+
             _ => {
-                println!("Error: Expected structure name.");
+                self.error("Expected structure name.");
                 return;
             },
         }
-        
+
         token = self.scanner.get_next();
         if token != Token::Is {
-            println!("Error: Expected \"is\".");
+            self.error("Expected \"is\".");
             return;
         }
-        
+
         // Create the element
-        let mut ast_struct = ast_new_struct(struct_name);
-        
+        let mut ast_struct = ast_new_struct(struct_name, self.ast.new_id());
+
         // Now, parse the block
         token = self.scanner.get_next();
         while token != Token::End && token != Token::Eof {
             // First token is name
-            let name : String;
+            let item_start = self.scanner.get_span();
+            let name : Symbol;
             match token {
                 Token::Id(val) => name = val,
-                
+
                 _ => {
-                    println!("Error: Expected item name.");
+                    self.error("Expected item name.");
                     return;
                 },
             }
             let colon_token = self.scanner.get_next();
             let data_type = self.build_data_type();
-            
+
             if colon_token != Token::Colon {
-                println!("Error: Expected \':\' in structure item.");
+                self.error("Expected \':\' in structure item.");
                 return;
             }
-            
-            // This is for the assignment operation
-            let mut lval = ast_new_expression(AstType::Id);
-            lval.set_name(name.clone());
-            
-            let mut expr = self.build_expression(Token::SemiColon);
-            expr.set_lval(lval);
-            
-            // Build the AST element
-            let mut arg = ast_new_arg(name, data_type);
-            arg.set_expression(expr);
+
+            // Build the AST element, along with the default value if there is one
+            let mut arg = ast_new_arg(name, data_type, self.ast.new_id());
+            token = self.scanner.get_next();
+            if token == Token::Assign {
+                if let Some(expr) = self.expect_expression(Token::SemiColon) {
+                    arg.set_expression(expr);
+                }
+            } else if token != Token::SemiColon {
+                self.error_with("Expected assignment operator or terminator.", self.scanner.describe(&token));
+                return;
+            }
+            arg.set_span(self.span_from(item_start));
             ast_struct.add_item(arg);
-            
+
             // Get the next token
             token = self.scanner.get_next();
         }
-        
+
         // Add the structure to the tree
+        ast_struct.set_span(self.span_from(start));
         self.ast.add_struct(ast_struct)
     }
-    
+
     //
     // Builds a function
     //
     pub fn build_function(&mut self) {
+        let start = self.scanner.get_span();
         let mut token = self.scanner.get_next();
-        let function_name : String;
+        let function_name : Symbol;
         match token {
             Token::Id(name) => function_name = name,
-            
+
             _ => {
-                println!("Error: Expected function name.");
+                self.error("Expected function name.");
                 return;
             },
         }
-        
+
         // Function arguments
         token = self.scanner.get_next();
         let mut args : Vec<AstArg> = Vec::new();
         if token == Token::LParen {
             while token != Token::RParen {
                 let name_token = self.scanner.get_next();
+                let arg_start = self.scanner.get_span();
                 let colon_token = self.scanner.get_next();
                 let data_type = self.build_data_type();
+                let arg_span = self.span_from(arg_start);
                 token = self.scanner.get_next();
-                
+
                 if colon_token != Token::Colon {
-                    println!("Error: Expected colon in function argument.");
-                    println!(" -> {:?}", colon_token);
+                    self.error_with("Expected colon in function argument.", self.scanner.describe(&colon_token));
                     return;
                 }
-                
-                let name : String;
-                match &name_token {
-                    Token::Id(val) => name = val.clone(),
+
+                let name : Symbol;
+                match name_token {
+                    Token::Id(val) => name = val,
                     _ => {
-                        println!("Error: Expected argument name.");
+                        self.error("Expected argument name.");
                         return;
                     },
                 }
-                
+
                 if token != Token::Comma && token != Token::RParen {
-                    println!("Error: Expected \',\' or \'(\' after argument.");
+                    self.error("Expected \',\' or \'(\' after argument.");
                     return;
                 }
-                
-                let arg = ast_new_arg(name, data_type);
+
+                let mut arg = ast_new_arg(name, data_type, self.ast.new_id());
+                arg.set_span(arg_span);
                 args.push(arg);
             }
-            
+
             token = self.scanner.get_next();
         }
-        
+
         // Check function return
         let data_type : DataType;
         if token == Token::Arrow {
@@ -195,455 +252,824 @@ impl Parser {
         } else {
             data_type = DataType::Void;
         }
-        
+
         // Finally, a block start
         if token != Token::Is {
-            println!("Error: Expected \"is\".");
+            self.error("Expected \"is\".");
             return;
         }
-        
+
         // Build the block
-        let block = self.build_block();
-        
+        let (block, _) = self.build_block(&[]);
+
         // Build the AST element
-        let mut func : AstFunction = ast_new_function(function_name);
+        let mut func : AstFunction = ast_new_function(function_name, self.ast.new_id());
+        func.set_span(self.span_from(start));
         func.set_data_type(data_type);
         func.set_block(block);
         for arg in args { func.add_arg(arg); }
-        for c in self.local_consts.clone() { func.add_const(c); }
+        for c in std::mem::take(&mut self.local_consts) { func.add_const(c); }
         self.ast.add_function(func);
-        self.local_consts.clear();
     }
-    
+
     //
     // Builds a statement block
+    // Besides "end", the block may be ended by any of the keywords in stop;
+    // the one that ended it is returned so the caller can go on from there
     //
-    fn build_block(&mut self) -> AstStatement {
-        let mut block = ast_new_statement(AstType::Block);
+    fn build_block(&mut self, stop : &[Token]) -> (Vec<StmtRef>, Token) {
+        let mut block : Vec<StmtRef> = Vec::new();
         let mut token = self.scanner.get_next();
-        
+
         while token != Token::End && token != Token::Eof {
+            if stop.contains(&token) {
+                return (block, token);
+            }
+
+            // Where the statement starts
+            let start = self.scanner.get_span();
+
             match token {
                 Token::Return => {
                     let expr = self.build_expression(Token::SemiColon);
-                    let mut stmt = ast_new_statement(AstType::Return);
-                    stmt.set_expression(expr);
-                    block.add_statement(stmt);
+                    let stmt = self.new_statement(AstStatementKind::Return(expr), start);
+                    block.push(stmt);
                 },
-            
+
                 Token::Var => {
-                    let stmt = self.build_variable_dec();
-                    block.add_statement(stmt);
+                    if let Some(stmt) = self.build_variable_dec() {
+                        block.push(stmt);
+                    }
                 },
-                
+
                 Token::Const => {
-                    let c = self.build_const();
-                    self.local_consts.push(c);
+                    if let Some(c) = self.build_const() {
+                        self.local_consts.push(c);
+                    }
                 },
-                
+
                 Token::Struct => {
                     token = self.scanner.get_next();
-                    let var_name : String;
+                    let var_name : Symbol;
                     match token {
                         Token::Id(val) => var_name = val,
-                        
+
                         _ => {
-                            println!("Error: Expected variable name in structure declaration.");
-                            var_name = String::new();
+                            self.error("Expected variable name in structure declaration.");
+                            var_name = self.scanner.intern("");
                             //return;
                         },
                     }
-                    
+
                     token = self.scanner.get_next();
                     if token != Token::Colon {
-                        println!("Error: Expected \':\' between structure variable name and structure name.");
+                        self.error("Expected \':\' between structure variable name and structure name.");
                         //return;
                     }
-                    
-                    // Create the AST elements
-                    let struct_id = self.build_expression(Token::SemiColon);        // TODO: Make sure this is only ID
-                    let mut stmt = ast_new_statement(AstType::StructDec);
-                    stmt.set_name(var_name);
-                    stmt.set_expression(struct_id);
-                    block.add_statement(stmt);
+
+                    token = self.scanner.get_next();
+                    match token {
+                        Token::Id(struct_name) => {
+                            token = self.scanner.get_next();
+                            if token != Token::SemiColon {
+                                self.error_with("Expected terminator.", self.scanner.describe(&token));
+                            }
+
+                            let kind = AstStatementKind::StructDec {
+                                name : var_name,
+                                struct_name : struct_name,
+                            };
+                            let stmt = self.new_statement(kind, start);
+                            block.push(stmt);
+                        },
+
+                        _ => {
+                            self.error("Expected structure name in structure declaration.");
+                            self.scanner.unget(token);
+                            self.build_expression(Token::SemiColon);
+                        },
+                    }
                 },
-            
+
                 Token::Id(name) => {
                     token = self.scanner.get_next();
-                    let mut sub_expr : AstExpression;
-                    let ast_type : AstType;
+
+                    // A label for the loop that follows
+                    if token == Token::Colon {
+                        if self.loops.contains(&Some(name)) {
+                            let note = self.scanner.get_names().resolve(name).to_string();
+                            self.error_with("Label is already in use by an enclosing loop.", note);
+                        }
+
+                        // Go around again with the loop keyword
+                        token = self.scanner.get_next();
+                        match token {
+                            Token::While | Token::Loop | Token::Repeat => self.label = Some(name),
+                            _ => self.error("Expected loop after label."),
+                        }
+                        continue;
+                    }
+
+                    // A function call
+                    if token == Token::LParen {
+                        let args = self.build_expression_list(Token::RParen);
+                        token = self.scanner.get_next();
+                        if token != Token::SemiColon {
+                            self.error_with("Expected terminator.", self.scanner.describe(&token));
+                        }
+
+                        let kind = AstStatementKind::CallStmt { name : name, args : args };
+                        let stmt = self.new_statement(kind, start);
+                        block.push(stmt);
+                        token = self.scanner.get_next();
+                        continue;
+                    }
+
+                    // Otherwise, an assignment; start with what we assign to
+                    let lval : ExprRef;
                     if token == Token::LBracket {
-                        sub_expr = self.build_expression(Token::RBracket);
-                        ast_type = AstType::ArrayAcc;
+                        match self.expect_expression(Token::RBracket) {
+                            Some(index) => {
+                                let kind = AstExpressionKind::ArrayAcc { name : name, index : index };
+                                let span = self.span_from(start);
+                                lval = self.new_expression(kind, span);
+                            },
+
+                            None => {
+                                self.build_expression(Token::SemiColon);
+                                token = self.scanner.get_next();
+                                continue;
+                            },
+                        }
+                        token = self.scanner.get_next();
                     } else if token == Token::Dot {
                         token = self.scanner.get_next();
-                        let item_name : String;
+                        let item_name : Symbol;
                         match token {
                             Token::Id(val) => item_name = val,
-                            
+
                             _ => {
-                                println!("Error: Expected item name in structure access.");
-                                item_name = String::new();
+                                self.error("Expected item name in structure access.");
+                                item_name = self.scanner.intern("");
                                 //return;
                             },
                         }
-                        
-                        sub_expr = ast_new_expression(AstType::Id);
-                        sub_expr.set_name(item_name);
-                        ast_type = AstType::StructAcc;
+
+                        let kind = AstExpressionKind::StructAcc { name : name, member : item_name };
+                        let span = self.span_from(start);
+                        lval = self.new_expression(kind, span);
+                        token = self.scanner.get_next();
                     } else {
-                        self.scanner.unget(token);
-                        sub_expr = ast_new_expression(AstType::None);
-                        ast_type = AstType::Id;
+                        lval = self.new_expression(AstExpressionKind::Id(name), start);
                     }
-                
-                    let mut expr = self.build_expression(Token::SemiColon);
-                    if expr.get_type() == AstType::Assign {
-                        let mut lval = ast_new_expression(ast_type);
-                        lval.set_name(name);
-                        lval.set_arg(sub_expr);
-                        expr.set_lval(lval);
-                        
-                        let mut stmt = ast_new_statement(AstType::ExprStmt);
-                        stmt.set_expression(expr);
-                        block.add_statement(stmt);
-                    } else {
-                        let mut stmt = ast_new_statement(AstType::CallStmt);
-                        stmt.set_name(name);
-                        stmt.set_expression(expr);
-                        block.add_statement(stmt);
+
+                    match self.get_assign_op(&token) {
+                        Some(op) => {
+                            if let Some(rval) = self.expect_expression(Token::SemiColon) {
+                                let span = self.span_of(lval).to(self.span_of(rval));
+                                let kind = AstExpressionKind::Assign {
+                                    op : op,
+                                    lhs : lval,
+                                    rhs : rval,
+                                };
+                                let expr = self.new_expression(kind, span);
+                                let stmt = self.new_statement(AstStatementKind::ExprStmt(expr), start);
+                                block.push(stmt);
+                            }
+                        },
+
+                        None => {
+                            self.error_with("Expected assignment or function call.", self.scanner.describe(&token));
+                            if token != Token::SemiColon {
+                                self.build_expression(Token::SemiColon);
+                            }
+                        },
                     }
                 },
-                
+
                 Token::While => {
-                    let expr = self.build_expression(Token::Do);
-                    let mut stmt = ast_new_statement(AstType::While);
-                    stmt.set_expression(expr);
-                    
-                    let sub_block = self.build_block();
-                    stmt.add_sub_block(sub_block);
-                    
-                    block.add_statement(stmt);
+                    let label = self.take_label();
+                    let cond = self.expect_expression(Token::Do);
+
+                    self.loops.push(label);
+                    let (sub_block, _) = self.build_block(&[]);
+                    self.loops.pop();
+
+                    if let Some(cond) = cond {
+                        let kind = AstStatementKind::While {
+                            label : label,
+                            cond : cond,
+                            block : sub_block,
+                        };
+                        let stmt = self.new_statement(kind, start);
+                        block.push(stmt);
+                    }
+                },
+
+                Token::Loop => {
+                    let label = self.take_label();
+
+                    self.loops.push(label);
+                    let (sub_block, _) = self.build_block(&[]);
+                    self.loops.pop();
+
+                    let kind = AstStatementKind::Loop {
+                        label : label,
+                        block : sub_block,
+                    };
+                    let stmt = self.new_statement(kind, start);
+                    block.push(stmt);
+                },
+
+                // The body of a repeat loop ends at "until" instead of "end"
+                Token::Repeat => {
+                    let label = self.take_label();
+
+                    self.loops.push(label);
+                    let (sub_block, end) = self.build_block(&[Token::Until]);
+                    self.loops.pop();
+
+                    if end != Token::Until {
+                        self.error("Expected \"until\" at the end of repeat loop.");
+                    } else if let Some(cond) = self.expect_expression(Token::SemiColon) {
+                        let kind = AstStatementKind::Repeat {
+                            label : label,
+                            block : sub_block,
+                            cond : cond,
+                        };
+                        let stmt = self.new_statement(kind, start);
+                        block.push(stmt);
+                    }
                 },
-                
+
+                //
+                // Each block of the if is ended by the elif or else after it,
+                // and the last one by "end"
+                //
                 Token::If => {
-                    let expr = self.build_expression(Token::Then);
-                    let mut stmt = ast_new_statement(AstType::If);
-                    stmt.set_expression(expr);
-                    
-                    let sub_block = self.build_block();
-                    for br in sub_block.get_branches() {
-                        stmt.add_branch(br.clone());
+                    let mut branches : Vec<AstBranch> = Vec::new();
+                    let mut else_block : Option<Vec<StmtRef>> = None;
+                    let mut valid = true;
+
+                    let mut end = Token::Elif;
+                    while end == Token::Elif {
+                        let cond = self.expect_expression(Token::Then);
+                        let (sub_block, next) = self.build_block(&[Token::Elif, Token::Else]);
+                        match cond {
+                            Some(cond) => branches.push(ast_new_branch(cond, sub_block)),
+                            None => valid = false,
+                        }
+                        end = next;
+                    }
+
+                    if end == Token::Else {
+                        let (sub_block, _) = self.build_block(&[]);
+                        else_block = Some(sub_block);
+                    }
+
+                    if valid {
+                        let kind = AstStatementKind::If {
+                            branches : branches,
+                            else_block : else_block,
+                        };
+                        let stmt = self.new_statement(kind, start);
+                        block.push(stmt);
                     }
-                    stmt.add_sub_block(sub_block);
-                    
-                    block.add_statement(stmt);
                 },
-                
-                Token::Elif => {
-                    let expr = self.build_expression(Token::Then);
-                    let mut stmt = ast_new_statement(AstType::Elif);
-                    stmt.set_expression(expr);
-                    
-                    let sub_block = self.build_block();
-                    for br in sub_block.get_branches() {
-                        stmt.add_branch(br.clone());
+
+                //
+                // The cases work like the branches of an if; the body
+                // itself should be empty
+                //
+                Token::Match => {
+                    let expr = self.expect_expression(Token::Is);
+                    let mut cases : Vec<AstCase> = Vec::new();
+                    let mut default : Option<Vec<StmtRef>> = None;
+
+                    let (body, mut end) = self.build_block(&[Token::Case, Token::Else]);
+                    if body.len() > 0 {
+                        self.error("Expected \"case\" in match statement.");
+                    }
+
+                    while end == Token::Case {
+                        self.case_values = true;
+                        let values = self.build_expression_list(Token::Then);
+                        self.case_values = false;
+                        if values.len() == 0 {
+                            self.error("Expected case value.");
+                        }
+
+                        let (sub_block, next) = self.build_block(&[Token::Case, Token::Else]);
+                        cases.push(ast_new_case(values, sub_block));
+                        end = next;
+                    }
+
+                    if end == Token::Else {
+                        let (sub_block, _) = self.build_block(&[]);
+                        default = Some(sub_block);
+                    }
+
+                    self.check_match_cases(&cases);
+
+                    if let Some(expr) = expr {
+                        let kind = AstStatementKind::Match {
+                            expr : expr,
+                            cases : cases,
+                            default : default,
+                        };
+                        let stmt = self.new_statement(kind, start);
+                        block.push(stmt);
                     }
-                    stmt.add_sub_block(sub_block);
-                    
-                    block.add_branch(stmt);
-                    return block;
                 },
-                
+
+                //
+                // These only end the block of the statement they belong to
+                // Anywhere else, we skip over them and keep going
+                //
+                Token::Elif => {
+                    self.error("\"elif\" without a matching \"if\".");
+                    self.build_expression(Token::Then);
+                },
+
                 Token::Else => {
-                    let mut stmt = ast_new_statement(AstType::Else);
-                    let sub_block = self.build_block();
-                    /*for br in sub_block.get_branches() {
-                        stmt.add_branch(br.clone());
-                    }*/
-                    stmt.add_sub_block(sub_block);
-                    
-                    block.add_branch(stmt);
-                    return block;
+                    self.error("\"else\" without a matching \"if\" or \"match\".");
+                },
+
+                Token::Case => {
+                    self.error("\"case\" outside of a match statement.");
+                    self.build_expression_list(Token::Then);
+                },
+
+                Token::Until => {
+                    self.error("\"until\" outside of a repeat loop.");
+                    self.build_expression(Token::SemiColon);
                 },
-                
+
                 Token::Break | Token::Continue => {
                     let keyword = token;
                     token = self.scanner.get_next();
-                    if token != Token::SemiColon {
-                        println!("Error: Expected terminator.");
-                        println!("{:?}", token);
+
+                    // An optional label for the loop to leave
+                    let mut label : Option<Symbol> = None;
+                    if let Token::Id(val) = token {
+                        token = self.scanner.get_next();
+
+                        if !self.loops.contains(&Some(val)) {
+                            let note = self.scanner.get_names().resolve(val).to_string();
+                            self.error_with("Unknown loop label.", note);
+                        }
+                        label = Some(val);
                     }
-                    
-                    if keyword == Token::Break {
-                        let stmt = ast_new_statement(AstType::Break);
-                        block.add_statement(stmt);
-                    } else if keyword == Token::Continue {
-                        let stmt = ast_new_statement(AstType::Continue);
-                        block.add_statement(stmt);
+
+                    if token != Token::SemiColon {
+                        self.error_with("Expected terminator.", self.scanner.describe(&token));
                     }
+
+                    let kind = if keyword == Token::Break {
+                        AstStatementKind::Break(label)
+                    } else {
+                        AstStatementKind::Continue(label)
+                    };
+                    let stmt = self.new_statement(kind, start);
+                    block.push(stmt);
                 },
-                
+
                 _ => {
-                    println!("Error: Invalid token statement.");
-                    println!("{:?}", token);
+                    self.error_with("Invalid token statement.", self.scanner.describe(&token));
                 },
             }
-            
+
             token = self.scanner.get_next();
         }
-        
-        block
+
+        (block, token)
     }
-    
+
     // Builds a variable declaration
-    fn build_variable_dec(&mut self) -> AstStatement {
+    fn build_variable_dec(&mut self) -> Option<StmtRef> {
+        let start = self.scanner.get_span();
         let mut token = self.scanner.get_next();
-        let name : String;
+        let name : Symbol;
         match token {
             Token::Id(value) => name = value,
             _ => {
-                println!("Error: Expected name in variable declaration.");
-                return ast_new_statement(AstType::None);
+                self.error("Expected name in variable declaration.");
+                return None;
             },
         }
-        
+
         token = self.scanner.get_next();
         if token != Token::Colon {
-            println!("Error: Expected colon.");
-            return ast_new_statement(AstType::None);
+            self.error("Expected colon.");
+            return None;
         }
-        
+
         let data_type = self.build_data_type();
-        
+
         token = self.scanner.get_next();
         if token == Token::LBracket {
-            let mut stmt = ast_new_statement(AstType::ArrayDec);
-            stmt.set_name(name.clone());
-            stmt.set_data_type(data_type);
-            
-            let expr = self.build_expression(Token::RBracket);
-            stmt.set_expression(expr);
-            
+            let size = self.expect_expression(Token::RBracket)?;
+
             token = self.scanner.get_next();
             if token != Token::SemiColon {
-                println!("Error: Expected terminator.");
-                return ast_new_statement(AstType::None);
+                self.error("Expected terminator.");
+                return None;
             }
-            
-            stmt
+
+            let kind = AstStatementKind::ArrayDec {
+                name : name,
+                data_type : data_type,
+                size : size,
+            };
+            Some(self.new_statement(kind, start))
         } else {
-            self.scanner.unget(token);
-        
-            let mut stmt = ast_new_statement(AstType::VarDec);
-            stmt.set_name(name.clone());
-            stmt.set_data_type(data_type);
-            
-            // This is for the assignment operation
-            //
-            let mut lval = ast_new_expression(AstType::Id);
-            lval.set_name(name);
-            
-            let mut expr = self.build_expression(Token::SemiColon);
-            expr.set_lval(lval);
-            stmt.set_expression(expr);
-            
-            stmt
+            // The value can be left out, and given later
+            let mut value : Option<ExprRef> = None;
+            if token == Token::Assign {
+                value = Some(self.expect_expression(Token::SemiColon)?);
+            } else if token != Token::SemiColon {
+                self.error("Expected assignment operator.");
+                return None;
+            }
+
+            let kind = AstStatementKind::VarDec {
+                name : name,
+                data_type : data_type,
+                value : value,
+            };
+            Some(self.new_statement(kind, start))
         }
     }
-    
+
     // Builds a constant declaration
-    fn build_const(&mut self) -> AstArg {
+    fn build_const(&mut self) -> Option<AstArg> {
+        let start = self.scanner.get_span();
         let mut token = self.scanner.get_next();
-        let name : String;
+        let name : Symbol;
         match token {
             Token::Id(value) => name = value,
             _ => {
-                println!("Error: Expected name in variable declaration.");
-                name = String::new();
+                self.error("Expected name in variable declaration.");
+                name = self.scanner.intern("");
                 //return ast_new_statement(AstType::None);
             },
         }
-        
+
         token = self.scanner.get_next();
         if token != Token::Colon {
-            println!("Error: Expected colon.");
+            self.error("Expected colon.");
             //return ast_new_statement(AstType::None);
         }
-        
+
         let data_type = self.build_data_type();
-        
+
         token = self.scanner.get_next();
         if token != Token::Assign {
-            println!("Error: Expected assignment operator.");
+            self.error("Expected assignment operator.");
             //return ast_new_statement(AstType::None);
         }
 
-        let expr = self.build_expression(Token::SemiColon);
-        let mut c = ast_new_arg(name, data_type);
+        let expr = self.expect_expression(Token::SemiColon)?;
+        let mut c = ast_new_arg(name, data_type, self.ast.new_id());
         c.set_expression(expr);
-        c
+        c.set_span(self.span_from(start));
+        Some(c)
     }
-    
+
     //
     // Builds an expression
     //
-    fn process_expression(&mut self, stack : &mut Vec<AstExpression>, op_stack : &mut Vec<AstExpression>) {
+    fn process_expression(&mut self, stack : &mut Vec<ExprRef>, op_stack : &mut Vec<BinaryOp>) {
         while op_stack.len() > 0 {
-            let mut op = op_stack.pop().unwrap();
-            if op.get_type() == AstType::Assign {
-                let rval = stack.pop().unwrap();
-                op.set_rval(rval);
-                stack.push(op);
-            } else {
-                let rval = stack.pop().unwrap();
-                let lval = stack.pop().unwrap();
-                op.set_lval(lval);
-                op.set_rval(rval);
-                stack.push(op);
+            let op = op_stack.pop().unwrap();
+            if stack.len() < 2 {
+                self.error(&format!("Expected operand for \"{}\".", op.symbol()));
+                return;
             }
+
+            let rval = stack.pop().unwrap();
+            let lval = stack.pop().unwrap();
+            let span = self.span_of(lval).to(self.span_of(rval));
+            if op == BinaryOp::Range && !self.case_values {
+                self.error_at(span, "Ranges can only be used as case values.", None);
+            }
+
+            let kind = AstExpressionKind::Binary {
+                op : op,
+                lhs : lval,
+                rhs : rval,
+            };
+            let expr = self.new_expression(kind, span);
+            stack.push(expr);
+        }
+    }
+
+    // Builds a single expression, if there is one
+    fn build_expression(&mut self, stop : Token) -> Option<ExprRef> {
+        let mut list = self.build_expression_list(stop);
+        if list.len() > 1 {
+            self.error("Unexpected \',\' in expression.");
+        }
+        if list.len() == 0 {
+            return None;
+        }
+        Some(list.remove(0))
+    }
+
+    // Builds an expression that has to be there
+    fn expect_expression(&mut self, stop : Token) -> Option<ExprRef> {
+        let expr = self.build_expression(stop);
+        if expr.is_none() {
+            self.error("Expected expression.");
         }
+        expr
     }
-    
-    fn build_expression(&mut self, stop : Token) -> AstExpression {
-        let mut stack : Vec<AstExpression> = Vec::new();
-        let mut op_stack : Vec<AstExpression> = Vec::new();
+
+    // Builds a comma separated list of expressions
+    fn build_expression_list(&mut self, stop : Token) -> Vec<ExprRef> {
+        let mut stack : Vec<ExprRef> = Vec::new();
+        let mut op_stack : Vec<BinaryOp> = Vec::new();
+        let mut list : Vec<ExprRef> = Vec::new();
         let mut token = self.scanner.get_next();
-        
-        let mut list_expr = ast_new_expression(AstType::ExprList);
-        let mut is_list = false;
-        
+
         while token != stop && token != Token::Eof {
             match token {
                 Token::LParen => {
-                    let expr = self.build_expression(Token::RParen);
-                    stack.push(expr);
+                    if let Some(expr) = self.expect_expression(Token::RParen) {
+                        stack.push(expr);
+                    }
                 },
-                
+
                 Token::Comma => {
-                    is_list = true;
                     self.process_expression(&mut stack, &mut op_stack);
-                    list_expr.add_list_item(stack.pop().unwrap());
+                    match stack.pop() {
+                        Some(expr) => list.push(expr),
+                        None => self.error("Expected expression before \',\'."),
+                    }
                 },
-                
-                
+
+
                 //
                 // Literals
                 //
                 Token::Id(val) => {
+                    let start = self.scanner.get_span();
                     token = self.scanner.get_next();
                     if token == Token::LParen {
-                        let sub_expr = self.build_expression(Token::RParen);
-                        let mut expr = ast_new_expression(AstType::Call);
-                        expr.set_name(val);
-                        expr.set_arg(sub_expr);
-                        stack.push(expr);
+                        let args = self.build_expression_list(Token::RParen);
+                        let kind = AstExpressionKind::Call { name : val, args : args };
+                        let span = self.span_from(start);
+                        stack.push(self.new_expression(kind, span));
                     } else if token == Token::LBracket {
-                        let sub_expr = self.build_expression(Token::RBracket);
-                        let mut expr = ast_new_expression(AstType::ArrayAcc);
-                        expr.set_name(val);
-                        expr.set_arg(sub_expr);
-                        stack.push(expr);
+                        if let Some(index) = self.expect_expression(Token::RBracket) {
+                            let kind = AstExpressionKind::ArrayAcc { name : val, index : index };
+                            let span = self.span_from(start);
+                            stack.push(self.new_expression(kind, span));
+                        }
                     } else if token == Token::Dot {
                         token = self.scanner.get_next();
-                        let item_name : String;
+                        let item_name : Symbol;
                         match token {
                             Token::Id(val) => item_name = val,
-                            
+
                             _ => {
-                                println!("Error: Expected item name in structure access.");
-                                item_name = String::new();
+                                self.error("Expected item name in structure access.");
+                                item_name = self.scanner.intern("");
                                 //return;
                             },
                         }
-                        
-                        let mut sub_expr = ast_new_expression(AstType::Id);
-                        sub_expr.set_name(item_name);
-                        let mut expr = ast_new_expression(AstType::StructAcc);
-                        expr.set_name(val);
-                        expr.set_arg(sub_expr);
-                        stack.push(expr);
+
+                        let kind = AstExpressionKind::StructAcc { name : val, member : item_name };
+                        let span = self.span_from(start);
+                        stack.push(self.new_expression(kind, span));
                     } else {
                         self.scanner.unget(token);
-                        let mut expr = ast_new_expression(AstType::Id);
-                        expr.set_name(val);
-                        stack.push(expr);
+                        stack.push(self.new_expression(AstExpressionKind::Id(val), start));
                     }
                 },
-                
-                Token::IntL(val) => {
-                    let mut expr = ast_new_expression(AstType::IntLiteral);
-                    expr.set_int(val);
-                    stack.push(expr);
-                },
-                
-                Token::StringL(val) => {
-                    let mut expr = ast_new_expression(AstType::StringLiteral);
-                    expr.set_string(val);
-                    stack.push(expr);
-                },
-                
-                Token::CharL(val) => {
-                    let mut expr = ast_new_expression(AstType::CharLiteral);
-                    expr.set_char(val);
-                    stack.push(expr);
+
+                Token::IntL(val) => stack.push(self.new_literal(AstExpressionKind::IntLiteral(val))),
+                Token::StringL(val) => stack.push(self.new_literal(AstExpressionKind::StringLiteral(val))),
+                Token::CharL(val) => stack.push(self.new_literal(AstExpressionKind::CharLiteral(val))),
+                Token::True => stack.push(self.new_literal(AstExpressionKind::BoolLiteral(true))),
+                Token::False => stack.push(self.new_literal(AstExpressionKind::BoolLiteral(false))),
+
+                //
+                // Casts bind to the operand right before them, so they
+                // are applied here rather than going on the operator stack
+                //
+                Token::As => {
+                    let data_type = self.build_data_type();
+                    if stack.len() == 0 {
+                        self.error("Expected expression before cast.");
+                    } else {
+                        let arg = stack.pop().unwrap();
+                        let span = self.span_from(self.span_of(arg));
+                        let kind = AstExpressionKind::Cast { data_type : data_type, expr : arg };
+                        stack.push(self.new_expression(kind, span));
+                    }
                 },
-                
-                Token::True => stack.push(ast_new_expression(AstType::BoolLiteral(true))),
-                Token::False => stack.push(ast_new_expression(AstType::BoolLiteral(false))),
-                
+
                 //
                 // Operators
                 //
-                Token::Assign => op_stack.push(ast_new_expression(AstType::Assign)),
-                Token::Add => op_stack.push(ast_new_expression(AstType::Add)),
-                Token::Sub => op_stack.push(ast_new_expression(AstType::Sub)),
-                Token::Mul => op_stack.push(ast_new_expression(AstType::Mul)),
-                Token::Div => op_stack.push(ast_new_expression(AstType::Div)),
-                Token::Mod => op_stack.push(ast_new_expression(AstType::Mod)),
-                Token::And => op_stack.push(ast_new_expression(AstType::And)),
-                Token::Or => op_stack.push(ast_new_expression(AstType::Or)),
-                Token::Xor => op_stack.push(ast_new_expression(AstType::Xor)),
-                
-                Token::Eq => op_stack.push(ast_new_expression(AstType::Eq)),
-                Token::Ne => op_stack.push(ast_new_expression(AstType::Ne)),
-                Token::Gt => op_stack.push(ast_new_expression(AstType::Gt)),
-                Token::Ge => op_stack.push(ast_new_expression(AstType::Ge)),
-                Token::Lt => op_stack.push(ast_new_expression(AstType::Lt)),
-                Token::Le => op_stack.push(ast_new_expression(AstType::Le)),
-                
-                Token::LGAnd => op_stack.push(ast_new_expression(AstType::LGAnd)),
-                Token::LGOr => op_stack.push(ast_new_expression(AstType::LGOr)),
-                
+                Token::Add => op_stack.push(BinaryOp::Add),
+                Token::Sub => op_stack.push(BinaryOp::Sub),
+                Token::Mul => op_stack.push(BinaryOp::Mul),
+                Token::Div => op_stack.push(BinaryOp::Div),
+                Token::Mod => op_stack.push(BinaryOp::Mod),
+                Token::And => op_stack.push(BinaryOp::And),
+                Token::Or => op_stack.push(BinaryOp::Or),
+                Token::Xor => op_stack.push(BinaryOp::Xor),
+                Token::Lsh => op_stack.push(BinaryOp::Lsh),
+                Token::Rsh => op_stack.push(BinaryOp::Rsh),
+
+                Token::Eq => op_stack.push(BinaryOp::Eq),
+                Token::Ne => op_stack.push(BinaryOp::Ne),
+                Token::Gt => op_stack.push(BinaryOp::Gt),
+                Token::Ge => op_stack.push(BinaryOp::Ge),
+                Token::Lt => op_stack.push(BinaryOp::Lt),
+                Token::Le => op_stack.push(BinaryOp::Le),
+
+                Token::LGAnd => op_stack.push(BinaryOp::LGAnd),
+                Token::LGOr => op_stack.push(BinaryOp::LGOr),
+
+                Token::Range => op_stack.push(BinaryOp::Range),
+
                 _ => {
-                    println!("Error: Invalid token in expression.");
-                    println!("{:?}", token);
+                    self.error_with("Invalid token in expression.", self.scanner.describe(&token));
                 },
             }
-        
+
             token = self.scanner.get_next();
         }
-        
-        if stop == Token::RParen && is_list {
-            if stack.len() > 0 {
-                self.process_expression(&mut stack, &mut op_stack);
-                list_expr.add_list_item(stack.pop().unwrap());
-            }
-        }
-        
+
         // Processing
         self.process_expression(&mut stack, &mut op_stack);
-        
-        // Return the final expression
-        if is_list {
-            return list_expr;
+        if let Some(expr) = stack.pop() {
+            list.push(expr);
         }
-        if stack.len() == 0 {
-            return ast_new_expression(AstType::None);
+
+        list
+    }
+
+    //
+    // Checks the case values of a match statement
+    // Two cases may not cover the same value, including through ranges
+    // Constants are left to the constant evaluator, which knows their values
+    //
+    fn check_match_cases(&mut self, cases : &Vec<AstCase>) {
+        // Literal values are kept as (low, high, is_char) ranges
+        let mut ranges : Vec<(u64, u64, bool)> = Vec::new();
+        let mut names : Vec<(Symbol, Symbol)> = Vec::new();
+
+        for case in cases {
+            for value in case.get_values() {
+                let value = self.ast.get_expression(*value).clone();
+                let range : (u64, u64, bool);
+                match value.get_kind() {
+                    AstExpressionKind::IntLiteral(val) => range = (*val, *val, false),
+                    AstExpressionKind::CharLiteral(val) => range = (*val as u64, *val as u64, true),
+
+                    AstExpressionKind::Binary { op : BinaryOp::Range, lhs, rhs } => {
+                        match (self.ast.get_expression(*lhs).get_kind(), self.ast.get_expression(*rhs).get_kind()) {
+                            (AstExpressionKind::IntLiteral(lo), AstExpressionKind::IntLiteral(hi)) => {
+                                range = (*lo, *hi, false);
+                            },
+
+                            (AstExpressionKind::CharLiteral(lo), AstExpressionKind::CharLiteral(hi)) => {
+                                range = (*lo as u64, *hi as u64, true);
+                            },
+
+                            _ => {
+                                self.error_at(value.get_span(), "Case ranges must be two integer or two character literals.", None);
+                                continue;
+                            },
+                        }
+
+                        if range.0 > range.1 {
+                            self.error_at(value.get_span(), "Empty range in case.", None);
+                            continue;
+                        }
+                    },
+
+                    // Constants are compared by value once they are known
+                    AstExpressionKind::Id(_) => continue,
+
+                    AstExpressionKind::StructAcc { name, member } => {
+                        if names.contains(&(*name, *member)) {
+                            let names = self.scanner.get_names();
+                            let note = format!("{}.{}", names.resolve(*name), names.resolve(*member));
+                            self.error_at(value.get_span(), "Duplicate case value in match statement.", Some(note));
+                        } else {
+                            names.push((*name, *member));
+                        }
+                        continue;
+                    },
+
+                    _ => {
+                        self.error_at(value.get_span(), "Invalid case value in match statement.", None);
+                        continue;
+                    },
+                }
+
+                let overlap = ranges.iter().find(|other| {
+                    other.2 == range.2 && range.0 <= other.1 && other.0 <= range.1
+                });
+                if let Some(other) = overlap {
+                    let note = if range.2 {
+                        format!("{:?}", std::char::from_u32(range.0.max(other.0) as u32).unwrap_or('?'))
+                    } else {
+                        format!("{}", range.0.max(other.0))
+                    };
+                    self.error_at(value.get_span(), "Duplicate case value in match statement.", Some(note));
+                }
+                ranges.push(range);
+            }
         }
-        stack.pop().unwrap()
     }
-    
+
+    //
+    // Helpers for reporting errors
+    // Unless given a span, an error points at the last token we read
+    //
+    fn error(&mut self, message : &str) {
+        let span = self.scanner.get_span();
+        self.error_at(span, message, None);
+    }
+
+    fn error_with(&mut self, message : &str, note : String) {
+        let span = self.scanner.get_span();
+        self.error_at(span, message, Some(note));
+    }
+
+    fn error_at(&mut self, span : Span, message : &str, note : Option<String>) {
+        // What the scanner found comes first, since it was read first
+        let scanned = self.scanner.take_diagnostics();
+        self.diagnostics.extend(scanned);
+
+        let mut d = diag_new(Level::Error, &self.file_name, Some(span), message.to_string());
+        if let Some(note) = note {
+            d.add_note(note);
+        }
+        self.diagnostics.push(d);
+    }
+
+    //
+    // Helpers for creating nodes
+    // A node spans from where it started to the last token we read
+    //
+    fn span_from(&self, start : Span) -> Span {
+        start.to(self.scanner.get_span())
+    }
+
+    fn new_statement(&mut self, kind : AstStatementKind, start : Span) -> StmtRef {
+        let span = self.span_from(start);
+        let id = self.ast.new_id();
+        self.ast.add_statement(ast_new_statement(kind, span, id))
+    }
+
+    fn new_expression(&mut self, kind : AstExpressionKind, span : Span) -> ExprRef {
+        let id = self.ast.new_id();
+        self.ast.add_expression(ast_new_expression(kind, span, id))
+    }
+
+    fn span_of(&self, expr : ExprRef) -> Span {
+        self.ast.get_expression(expr).get_span()
+    }
+
+    // Literals are a single token
+    fn new_literal(&mut self, kind : AstExpressionKind) -> ExprRef {
+        let span = self.scanner.get_span();
+        self.new_expression(kind, span)
+    }
+
+    // Hands out the pending loop label
+    fn take_label(&mut self) -> Option<Symbol> {
+        self.label.take()
+    }
+
+    //
+    // A utility function for mapping a token to an assignment operator
+    //
+    fn get_assign_op(&self, token : &Token) -> Option<AssignOp> {
+        match token {
+            Token::Assign => Some(AssignOp::Assign),
+            Token::AddAssign => Some(AssignOp::Add),
+            Token::SubAssign => Some(AssignOp::Sub),
+            Token::MulAssign => Some(AssignOp::Mul),
+            Token::DivAssign => Some(AssignOp::Div),
+            Token::ModAssign => Some(AssignOp::Mod),
+            Token::AndAssign => Some(AssignOp::And),
+            Token::OrAssign => Some(AssignOp::Or),
+            Token::XorAssign => Some(AssignOp::Xor),
+            Token::LshAssign => Some(AssignOp::Lsh),
+            Token::RshAssign => Some(AssignOp::Rsh),
+
+            _ => None,
+        }
+    }
+
     //
     // A utility function for building a data type
     //
@@ -661,11 +1087,10 @@ impl Parser {
             Token::String => DataType::String,
             Token::Char => DataType::Char,
             Token::Bool => DataType::Bool,
-            
+
             _ => {
-                println!("Error: Unknown data type token.");
-                println!("{:?}", token);
-                
+                self.error_with("Unknown data type token.", self.scanner.describe(&token));
+
                 DataType::Void
             },
         }
@@ -681,6 +1106,9 @@ pub fn parser_new(file_name : String) -> Parser {
         ast : ast_new_file(file_name.clone()),
         scanner : lex_new(file_name),
         local_consts : Vec::new(),
+        loops : Vec::new(),
+        label : None,
+        case_values : false,
+        diagnostics : Vec::new(),
     }
 }
-
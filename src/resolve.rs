@@ -0,0 +1,436 @@
+//
+// Name resolution
+//
+// Binds every name that is used (variables, arrays, structure variables,
+// called functions and structure types) to the node that declares it. Each
+// file sees its own globals and those of every module it pulls in, whatever
+// order they come in. Below that, a function has a scope for its arguments,
+// one for its constants, and one for each block, so a variable is only
+// visible from its declaration to the end of its block.
+//
+// Declarations are known by the ID of the node that makes them: the function,
+// structure or argument itself, or the statement for a variable.
+// Calls to the runtime's builtins are left unbound, unless the program has
+// a function of the same name.
+//
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::signature::find_builtin;
+use crate::visit::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclKind {
+    Function,
+    Struct,
+    Const,
+    Arg,
+    Var,
+    Array,
+    StructVar,
+}
+
+impl DeclKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeclKind::Function => "function",
+            DeclKind::Struct => "structure",
+            DeclKind::Const => "constant",
+            DeclKind::Arg => "argument",
+            DeclKind::Var => "variable",
+            DeclKind::Array => "array",
+            DeclKind::StructVar => "structure variable",
+        }
+    }
+
+    // The name with its article: "a function", "an array"
+    pub fn a_name(&self) -> String {
+        match self {
+            DeclKind::Arg | DeclKind::Array => format!("an {}", self.name()),
+            _ => format!("a {}", self.name()),
+        }
+    }
+
+    // Whether the name stands for a value, as opposed to a function or type
+    pub fn is_value(&self) -> bool {
+        match self {
+            DeclKind::Function | DeclKind::Struct => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decl {
+    name : String,
+    kind : DeclKind,
+
+    // The type of a value, the return type of a function, and void for
+    // structures and structure variables
+    data_type : DataType,
+    file : usize,
+    span : Span,
+}
+
+impl Decl {
+    //
+    // Getter functions
+    //
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_kind(&self) -> DeclKind {
+        self.kind
+    }
+
+    pub fn get_data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    // The index of the declaring file in the program
+    pub fn get_file(&self) -> usize {
+        self.file
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+//
+// What the pass found
+// A use is the ID of an expression, or of a call statement; the structure
+// named by a structure variable is kept apart, since that statement is
+// also a declaration
+//
+pub struct Resolution {
+    decls : HashMap<NodeId, Decl>,
+    bindings : HashMap<NodeId, NodeId>,
+    type_bindings : HashMap<NodeId, NodeId>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl Resolution {
+    //
+    // Getter functions
+    //
+    pub fn get_decl(&self, id : NodeId) -> Option<&Decl> {
+        self.decls.get(&id)
+    }
+
+    pub fn get_decls(&self) -> &HashMap<NodeId, Decl> {
+        &self.decls
+    }
+
+    // Returns the declaration a use is bound to
+    pub fn get_binding(&self, id : NodeId) -> Option<NodeId> {
+        self.bindings.get(&id).copied()
+    }
+
+    pub fn get_bindings(&self) -> &HashMap<NodeId, NodeId> {
+        &self.bindings
+    }
+
+    // Returns the structure a structure variable is declared with
+    pub fn get_type_binding(&self, id : NodeId) -> Option<NodeId> {
+        self.type_bindings.get(&id).copied()
+    }
+
+    pub fn get_diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+}
+
+//
+// Resolves every file of a program
+//
+pub fn resolve(program : &Program) -> Resolution {
+    let mut resolver = Resolver {
+        res : Resolution {
+            decls : HashMap::new(),
+            bindings : HashMap::new(),
+            type_bindings : HashMap::new(),
+            diagnostics : Vec::new(),
+        },
+        globals : Vec::new(),
+        scopes : Vec::new(),
+        file : 0,
+        file_name : String::new(),
+    };
+
+    // The globals of each file come first, so they can be used anywhere
+    let mut file_globals : Vec<HashMap<String, NodeId>> = Vec::new();
+    for (index, file) in program.get_files().iter().enumerate() {
+        let mut globals : HashMap<String, NodeId> = HashMap::new();
+        for s in file.get_structs() {
+            resolver.declare_in(&mut globals, index, file.get_str(s.get_name()), s.get_id(), DeclKind::Struct, DataType::Void, s.get_span());
+        }
+        for c in file.get_consts() {
+            resolver.declare_in(&mut globals, index, file.get_str(c.get_name()), c.get_id(), DeclKind::Const, c.get_data_type(), c.get_span());
+        }
+        for func in file.get_functions() {
+            resolver.declare_in(&mut globals, index, file.get_str(func.get_name()), func.get_id(), DeclKind::Function, func.get_data_type(), func.get_span());
+        }
+        file_globals.push(globals);
+    }
+
+    for (index, file) in program.get_files().iter().enumerate() {
+        // A file sees its own globals first, then those of its imports
+        resolver.globals.clear();
+        resolver.globals.push(file_globals[index].clone());
+        for module in reachable(program, index) {
+            resolver.globals.push(file_globals[module].clone());
+        }
+
+        resolver.file = index;
+        resolver.file_name = file.get_name().to_string();
+        resolver.visit_file(file);
+    }
+
+    resolver.res
+}
+
+// The modules a file pulls in, directly or through other modules
+fn reachable(program : &Program, index : usize) -> Vec<usize> {
+    let mut found : Vec<usize> = Vec::new();
+    let mut stack : Vec<usize> = program.get_imports(index).clone();
+    while let Some(next) = stack.pop() {
+        if next == index || found.contains(&next) {
+            continue;
+        }
+        found.push(next);
+        stack.extend(program.get_imports(next).iter());
+    }
+    found.sort();
+    found
+}
+
+struct Resolver {
+    res : Resolution,
+
+    // The globals visible from the current file, and the local scopes
+    // from the outermost in
+    globals : Vec<HashMap<String, NodeId>>,
+    scopes : Vec<HashMap<String, NodeId>>,
+
+    file : usize,
+    file_name : String,
+}
+
+impl Resolver {
+    #[allow(clippy::too_many_arguments)]
+    fn declare_in(&mut self, scope : &mut HashMap<String, NodeId>, file : usize, name : &str, id : NodeId,
+                  kind : DeclKind, data_type : DataType, span : Span) {
+        scope.insert(name.to_string(), id);
+        self.res.decls.insert(id, Decl {
+            name : name.to_string(),
+            kind : kind,
+            data_type : data_type,
+            file : file,
+            span : span,
+        });
+    }
+
+    // Declares a name in the innermost scope
+    fn declare(&mut self, name : &str, id : NodeId, kind : DeclKind, data_type : DataType, span : Span) {
+        let mut scope = self.scopes.pop().unwrap_or_default();
+        self.declare_in(&mut scope, self.file, name, id, kind, data_type, span);
+        self.scopes.push(scope);
+    }
+
+    fn declare_arg(&mut self, file : &AstFile, arg : &AstArg, kind : DeclKind) {
+        self.declare(file.get_str(arg.get_name()), arg.get_id(), kind, arg.get_data_type(), arg.get_span());
+    }
+
+    // Finds the innermost declaration of a name that fits
+    fn lookup(&self, name : &str, fits : fn(DeclKind) -> bool) -> Option<NodeId> {
+        for scope in self.scopes.iter().rev().chain(self.globals.iter()) {
+            if let Some(id) = scope.get(name) {
+                if fits(self.res.decls[id].kind) {
+                    return Some(*id);
+                }
+            }
+        }
+        None
+    }
+
+    //
+    // Binds a use to its declaration, or reports it
+    // A name that is declared, but as something else, is reported as such
+    //
+    fn bind(&mut self, use_id : NodeId, name : &str, span : Span, what : &str, fits : fn(DeclKind) -> bool) -> Option<NodeId> {
+        if let Some(decl) = self.lookup(name, fits) {
+            self.res.bindings.insert(use_id, decl);
+            return Some(decl);
+        }
+
+        match self.lookup(name, |_| true) {
+            Some(other) => {
+                let kind = self.res.decls[&other].kind;
+                let message = format!("\"{}\" is {}, not a {}.", name, kind.a_name(), what);
+                self.error(span, message, None);
+            },
+
+            None => {
+                let note = self.suggest(name, fits).map(|s| format!("Did you mean \"{}\"?", s));
+                self.error(span, format!("Undefined {} \"{}\".", what, name), note);
+            },
+        }
+        None
+    }
+
+    // A call to a builtin has nothing to bind to; the name is enough
+    fn bind_call(&mut self, use_id : NodeId, name : &str, span : Span) {
+        if self.lookup(name, is_function).is_none() && find_builtin(name).is_some() {
+            return;
+        }
+        self.bind(use_id, name, span, "function", is_function);
+    }
+
+    // The visible name of the right kind closest to the one we couldn't find
+    fn suggest(&self, name : &str, fits : fn(DeclKind) -> bool) -> Option<String> {
+        let mut best : Option<(usize, String)> = None;
+        for scope in self.scopes.iter().rev().chain(self.globals.iter()) {
+            for (candidate, id) in scope {
+                if !fits(self.res.decls[id].kind) {
+                    continue;
+                }
+
+                let distance = edit_distance(name, candidate);
+                let better = match &best {
+                    Some((d, c)) => distance < *d || (distance == *d && candidate < c),
+                    None => true,
+                };
+                if better {
+                    best = Some((distance, candidate.clone()));
+                }
+            }
+        }
+
+        // Only names that are close enough to be a typo; anything is
+        // close to a name of a letter or two
+        let limit = name.chars().count() / 3;
+        match best {
+            Some((distance, candidate)) if distance <= limit => Some(candidate),
+            _ => None,
+        }
+    }
+
+    fn error(&mut self, span : Span, message : String, note : Option<String>) {
+        let mut d = diag_new(Level::Error, &self.file_name, Some(span), message);
+        if let Some(note) = note {
+            d.add_note(note);
+        }
+        self.res.diagnostics.push(d);
+    }
+}
+
+fn is_value(kind : DeclKind) -> bool {
+    kind.is_value()
+}
+
+fn is_function(kind : DeclKind) -> bool {
+    kind == DeclKind::Function
+}
+
+fn is_struct(kind : DeclKind) -> bool {
+    kind == DeclKind::Struct
+}
+
+//
+// The number of single character edits between two names
+//
+pub fn edit_distance(a : &str, b : &str) -> usize {
+    let a : Vec<char> = a.chars().collect();
+    let b : Vec<char> = b.chars().collect();
+    let mut row : Vec<usize> = (0 ..= b.len()).collect();
+
+    for i in 1 ..= a.len() {
+        let mut prev = row[0];
+        row[0] = i;
+        for j in 1 ..= b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let next = (row[j] + 1).min(row[j - 1] + 1).min(prev + cost);
+            prev = row[j];
+            row[j] = next;
+        }
+    }
+    row[b.len()]
+}
+
+impl Visitor for Resolver {
+    // The globals are already declared; imports have nothing to resolve
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        self.scopes.push(HashMap::new());
+        for arg in func.get_args() {
+            self.declare_arg(file, arg, DeclKind::Arg);
+        }
+
+        // The constants are gathered from anywhere in the body, so they
+        // are visible anywhere in it
+        self.scopes.push(HashMap::new());
+        for c in func.get_consts() {
+            walk_arg(self, file, c);
+            self.declare_arg(file, c, DeclKind::Const);
+        }
+
+        self.visit_block(file, func.get_block());
+        self.scopes.pop();
+        self.scopes.pop();
+    }
+
+    fn visit_block(&mut self, file : &AstFile, block : &Vec<StmtRef>) {
+        self.scopes.push(HashMap::new());
+        walk_block(self, file, block);
+        self.scopes.pop();
+    }
+
+    // The value of a declaration is resolved before the name is declared
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        if let AstStatementKind::CallStmt { name, .. } = stmt.get_kind() {
+            self.bind_call(stmt.get_id(), file.get_str(*name), stmt.get_span());
+        }
+
+        walk_statement(self, file, stmt);
+
+        match stmt.get_kind() {
+            AstStatementKind::VarDec { name, data_type, .. } => {
+                self.declare(file.get_str(*name), stmt.get_id(), DeclKind::Var, *data_type, stmt.get_span());
+            },
+
+            AstStatementKind::ArrayDec { name, data_type, .. } => {
+                self.declare(file.get_str(*name), stmt.get_id(), DeclKind::Array, *data_type, stmt.get_span());
+            },
+
+            AstStatementKind::StructDec { name, struct_name } => {
+                if let Some(s) = self.bind(stmt.get_id(), file.get_str(*struct_name), stmt.get_span(), "structure", is_struct) {
+                    // The statement is a declaration as well as a use
+                    self.res.bindings.remove(&stmt.get_id());
+                    self.res.type_bindings.insert(stmt.get_id(), s);
+                }
+                self.declare(file.get_str(*name), stmt.get_id(), DeclKind::StructVar, DataType::Void, stmt.get_span());
+            },
+
+            _ => {},
+        }
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        match expr.get_kind() {
+            AstExpressionKind::Id(name)
+            | AstExpressionKind::ArrayAcc { name, .. }
+            | AstExpressionKind::StructAcc { name, .. } => {
+                self.bind(expr.get_id(), file.get_str(*name), expr.get_span(), "variable", is_value);
+            },
+
+            AstExpressionKind::Call { name, .. } => self.bind_call(expr.get_id(), file.get_str(*name), expr.get_span()),
+
+            _ => {},
+        }
+
+        walk_expression(self, file, expr);
+    }
+}
@@ -0,0 +1,284 @@
+//
+// Writes the AST as S-expressions
+//
+// Every node is a list that starts with what it is: "(var x i32 (+ y 1))".
+// Statements each go on their own line, indented under their parent, while
+// expressions stay on one line. Loop labels are written as ":label".
+//
+use crate::ast::*;
+use crate::intern::Symbol;
+use crate::visit::*;
+
+pub fn render(file : &AstFile) -> String {
+    let mut writer = SexpWriter {
+        out : String::new(),
+        indent : 0,
+    };
+    writer.visit_file(file);
+    writer.out.push('\n');
+    writer.out
+}
+
+struct SexpWriter {
+    out : String,
+    indent : usize,
+}
+
+impl SexpWriter {
+    // Starts a list on a new line
+    fn open(&mut self, head : &str) {
+        if self.out.len() > 0 {
+            self.out.push('\n');
+        }
+        for _i in 0 .. self.indent {
+            self.out.push(' ');
+        }
+        self.out.push('(');
+        self.out.push_str(head);
+        self.indent += 2;
+    }
+
+    fn close(&mut self) {
+        self.out.push(')');
+        self.indent -= 2;
+    }
+
+    fn word(&mut self, word : &str) {
+        self.out.push(' ');
+        self.out.push_str(word);
+    }
+
+    fn label(&mut self, file : &AstFile, label : &Option<Symbol>) {
+        if let Some(label) = label {
+            self.word(&format!(":{}", file.get_str(*label)));
+        }
+    }
+
+    // An argument, item or constant: (head name type value)
+    fn arg(&mut self, file : &AstFile, head : &str, arg : &AstArg) {
+        self.out.push_str(&format!(" ({} {} {}", head, file.get_str(arg.get_name()), arg.get_data_type().name()));
+        if let Some(expr) = arg.get_expression() {
+            self.visit_expression(file, file.get_expression(expr));
+        }
+        self.out.push(')');
+    }
+}
+
+impl Visitor for SexpWriter {
+    fn visit_file(&mut self, file : &AstFile) {
+        self.open("file");
+        self.word(&format!("{:?}", file.get_name()));
+        walk_file(self, file);
+        self.close();
+    }
+
+    fn visit_import(&mut self, _file : &AstFile, path : &str) {
+        self.open("import");
+        self.word(path);
+        self.close();
+    }
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        self.open("struct");
+        self.word(file.get_str(s.get_name()));
+        walk_struct(self, file, s);
+        self.close();
+    }
+
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        self.arg(file, "item", item);
+    }
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        self.arg(file, "arg", arg);
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.open("const");
+        self.word(&format!("{} {}", file.get_str(c.get_name()), c.get_data_type().name()));
+        if let Some(expr) = c.get_expression() {
+            self.visit_expression(file, file.get_expression(expr));
+        }
+        self.close();
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        self.open("func");
+        self.word(file.get_str(func.get_name()));
+        self.word(func.get_data_type().name());
+
+        self.out.push_str(" (args");
+        for arg in func.get_args() {
+            self.visit_arg(file, arg);
+        }
+        self.out.push(')');
+
+        for c in func.get_consts() {
+            self.visit_const(file, c);
+        }
+        self.visit_block(file, func.get_block());
+        self.close();
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        match stmt.get_kind() {
+            AstStatementKind::Return(expr) => {
+                self.open("return");
+                if let Some(expr) = expr {
+                    self.visit_expression(file, file.get_expression(*expr));
+                }
+            },
+
+            AstStatementKind::VarDec { name, data_type, value } => {
+                self.open("var");
+                self.word(&format!("{} {}", file.get_str(*name), data_type.name()));
+                if let Some(value) = value {
+                    self.visit_expression(file, file.get_expression(*value));
+                }
+            },
+
+            AstStatementKind::ArrayDec { name, data_type, size } => {
+                self.open("array");
+                self.word(&format!("{} {}", file.get_str(*name), data_type.name()));
+                self.visit_expression(file, file.get_expression(*size));
+            },
+
+            AstStatementKind::StructDec { name, struct_name } => {
+                self.open("struct-var");
+                self.word(&format!("{} {}", file.get_str(*name), file.get_str(*struct_name)));
+            },
+
+            AstStatementKind::CallStmt { name, args } => {
+                self.open("call");
+                self.word(file.get_str(*name));
+                for arg in args {
+                    self.visit_expression(file, file.get_expression(*arg));
+                }
+            },
+
+            AstStatementKind::ExprStmt(expr) => {
+                self.open("expr");
+                self.visit_expression(file, file.get_expression(*expr));
+            },
+
+            AstStatementKind::While { label, cond, block } => {
+                self.open("while");
+                self.label(file, label);
+                self.visit_expression(file, file.get_expression(*cond));
+                self.visit_block(file, block);
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                self.open("loop");
+                self.label(file, label);
+                self.visit_block(file, block);
+            },
+
+            AstStatementKind::Repeat { label, block, cond } => {
+                self.open("repeat");
+                self.label(file, label);
+                self.visit_block(file, block);
+                self.open("until");
+                self.visit_expression(file, file.get_expression(*cond));
+                self.close();
+            },
+
+            AstStatementKind::Break(label) => {
+                self.open("break");
+                self.label(file, label);
+            },
+
+            AstStatementKind::Continue(label) => {
+                self.open("continue");
+                self.label(file, label);
+            },
+
+            AstStatementKind::If { branches, else_block } => {
+                self.open("if");
+                for br in branches {
+                    self.open("branch");
+                    self.visit_expression(file, file.get_expression(br.get_cond()));
+                    self.visit_block(file, br.get_block());
+                    self.close();
+                }
+
+                if let Some(block) = else_block {
+                    self.open("else");
+                    self.visit_block(file, block);
+                    self.close();
+                }
+            },
+
+            AstStatementKind::Match { expr, cases, default } => {
+                self.open("match");
+                self.visit_expression(file, file.get_expression(*expr));
+                for case in cases {
+                    self.open("case");
+                    self.out.push_str(" (values");
+                    for value in case.get_values() {
+                        self.visit_expression(file, file.get_expression(*value));
+                    }
+                    self.out.push(')');
+                    self.visit_block(file, case.get_block());
+                    self.close();
+                }
+
+                if let Some(block) = default {
+                    self.open("else");
+                    self.visit_block(file, block);
+                    self.close();
+                }
+            },
+        }
+        self.close();
+    }
+
+    // Expressions put a space in front of themselves
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        match expr.get_kind() {
+            AstExpressionKind::Id(name) => self.word(file.get_str(*name)),
+            AstExpressionKind::IntLiteral(val) => self.word(&val.to_string()),
+            AstExpressionKind::CharLiteral(val) => self.word(&format!("{:?}", val)),
+            AstExpressionKind::StringLiteral(val) => self.word(&format!("{:?}", file.get_str(*val))),
+            AstExpressionKind::BoolLiteral(val) => self.word(&val.to_string()),
+
+            AstExpressionKind::ArrayAcc { name, index } => {
+                self.out.push_str(&format!(" (index {}", file.get_str(*name)));
+                self.visit_expression(file, file.get_expression(*index));
+                self.out.push(')');
+            },
+
+            AstExpressionKind::StructAcc { name, member } => {
+                self.out.push_str(&format!(" (. {} {})", file.get_str(*name), file.get_str(*member)));
+            },
+
+            AstExpressionKind::Call { name, args } => {
+                self.out.push_str(&format!(" (call {}", file.get_str(*name)));
+                for arg in args {
+                    self.visit_expression(file, file.get_expression(*arg));
+                }
+                self.out.push(')');
+            },
+
+            AstExpressionKind::Cast { data_type, expr } => {
+                self.out.push_str(&format!(" (as {}", data_type.name()));
+                self.visit_expression(file, file.get_expression(*expr));
+                self.out.push(')');
+            },
+
+            AstExpressionKind::Binary { op, lhs, rhs } => {
+                self.out.push_str(&format!(" ({}", op.symbol()));
+                self.visit_expression(file, file.get_expression(*lhs));
+                self.visit_expression(file, file.get_expression(*rhs));
+                self.out.push(')');
+            },
+
+            AstExpressionKind::Assign { op, lhs, rhs } => {
+                self.out.push_str(&format!(" ({}", op.symbol()));
+                self.visit_expression(file, file.get_expression(*lhs));
+                self.visit_expression(file, file.get_expression(*rhs));
+                self.out.push(')');
+            },
+        }
+    }
+}
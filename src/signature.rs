@@ -0,0 +1,159 @@
+//
+// Function signatures
+//
+// Every function that can be called, with the types of its arguments and
+// what it returns. The functions of the program are known by the ID of
+// their node, like the resolver knows them. The runtime's functions are
+// listed in the table below, and are called by name; a function of the
+// program with the same name hides one of them.
+//
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::loader::Program;
+
+//
+// The functions the runtime provides
+// A variadic builtin takes any number of values after its arguments, each
+// of any type but void
+//
+pub struct Builtin {
+    pub name : &'static str,
+    pub args : &'static [(&'static str, DataType)],
+    pub variadic : bool,
+    pub data_type : DataType,
+}
+
+pub const BUILTINS : &[Builtin] = &[
+    Builtin { name : "print", args : &[], variadic : true, data_type : DataType::Void },
+    Builtin { name : "println", args : &[], variadic : true, data_type : DataType::Void },
+    Builtin { name : "get_char", args : &[], variadic : false, data_type : DataType::Char },
+    Builtin { name : "exit", args : &[("code", DataType::I32)], variadic : false, data_type : DataType::Void },
+];
+
+pub fn find_builtin(name : &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    name : String,
+    args : Vec<(String, DataType)>,
+    variadic : bool,
+    data_type : DataType,
+
+    // Where the function is declared, and None for the builtins
+    file : String,
+    span : Option<Span>,
+}
+
+impl Signature {
+    //
+    // Getter functions
+    //
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_args(&self) -> &Vec<(String, DataType)> {
+        &self.args
+    }
+
+    pub fn get_data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    // Whether any number of values can follow the arguments
+    pub fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    // The file the function is declared in; empty for the builtins
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn get_span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.span.is_none()
+    }
+}
+
+//
+// Signatures print the way the function is declared: "name(a : i32) -> i32"
+// The values a variadic builtin takes show as "..."
+//
+impl fmt::Display for Signature {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let mut args : Vec<String> = self.args.iter()
+            .map(|(name, data_type)| format!("{} : {}", name, data_type.name()))
+            .collect();
+        if self.variadic {
+            args.push("...".to_string());
+        }
+        write!(f, "{}({})", self.name, args.join(", "))?;
+
+        if self.data_type != DataType::Void {
+            write!(f, " -> {}", self.data_type.name())?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Signatures {
+    functions : HashMap<NodeId, Signature>,
+    builtins : HashMap<String, Signature>,
+}
+
+impl Signatures {
+    //
+    // Getter functions
+    //
+    pub fn get_function(&self, id : NodeId) -> Option<&Signature> {
+        self.functions.get(&id)
+    }
+
+    pub fn get_builtin(&self, name : &str) -> Option<&Signature> {
+        self.builtins.get(name)
+    }
+}
+
+//
+// Collects the signatures of every function of a program, and the builtins
+//
+pub fn signatures(program : &Program) -> Signatures {
+    let mut functions : HashMap<NodeId, Signature> = HashMap::new();
+    for file in program.get_files() {
+        for func in file.get_functions() {
+            functions.insert(func.get_id(), Signature {
+                name : file.get_str(func.get_name()).to_string(),
+                args : func.get_args().iter().map(|arg| (file.get_str(arg.get_name()).to_string(), arg.get_data_type())).collect(),
+                variadic : false,
+                data_type : func.get_data_type(),
+                file : file.get_name().to_string(),
+                span : Some(func.get_span()),
+            });
+        }
+    }
+
+    let mut builtins : HashMap<String, Signature> = HashMap::new();
+    for b in BUILTINS {
+        builtins.insert(b.name.to_string(), Signature {
+            name : b.name.to_string(),
+            args : b.args.iter().map(|(name, data_type)| (name.to_string(), *data_type)).collect(),
+            variadic : b.variadic,
+            data_type : b.data_type,
+            file : String::new(),
+            span : None,
+        });
+    }
+
+    Signatures {
+        functions : functions,
+        builtins : builtins,
+    }
+}
@@ -0,0 +1,124 @@
+//
+// Structure checks
+//
+// Each structure has to have distinct member names, and each member that is
+// accessed has to be a member of the structure the variable was declared
+// with. Undefined structure names are left to the resolver, and the types of
+// default values to the type checker.
+//
+// Members can only be of the built in types, so a structure can never
+// contain another one, let alone itself.
+//
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::resolve::*;
+use crate::visit::*;
+
+//
+// Checks every structure, and every member access, of a program
+//
+pub fn check(program : &Program, res : &Resolution) -> Vec<Diagnostic> {
+    let mut structs : HashMap<NodeId, (&AstStruct, &AstFile)> = HashMap::new();
+    for file in program.get_files() {
+        for s in file.get_structs() {
+            structs.insert(s.get_id(), (s, file));
+        }
+    }
+
+    let mut checker = StructChecker {
+        res : res,
+        structs : structs,
+        diagnostics : Vec::new(),
+        file_name : String::new(),
+    };
+
+    for file in program.get_files() {
+        checker.file_name = file.get_name().to_string();
+        checker.visit_file(file);
+    }
+    checker.diagnostics
+}
+
+struct StructChecker<'a> {
+    res : &'a Resolution,
+    // Each structure, with the file that declares it
+    structs : HashMap<NodeId, (&'a AstStruct, &'a AstFile)>,
+    diagnostics : Vec<Diagnostic>,
+    file_name : String,
+}
+
+impl StructChecker<'_> {
+    fn error(&mut self, span : Span, message : String, note : Option<String>) {
+        let mut d = diag_new(Level::Error, &self.file_name, Some(span), message);
+        if let Some(note) = note {
+            d.add_note(note);
+        }
+        self.diagnostics.push(d);
+    }
+
+    fn check_access(&mut self, expr : &AstExpression, name : &str, member : &str) {
+        let decl = match self.res.get_binding(expr.get_id()) {
+            Some(decl) => decl,
+            None => return,
+        };
+
+        // Anything else bound here is a plain value
+        let (s, file) = match self.res.get_type_binding(decl).and_then(|s| self.structs.get(&s)) {
+            Some(s) => *s,
+            None => {
+                if let Some(d) = self.res.get_decl(decl) {
+                    if d.get_kind() != DeclKind::StructVar {
+                        let message = format!("\"{}\" is {}, not a structure variable.", name, d.get_kind().a_name());
+                        self.error(expr.get_span(), message, None);
+                    }
+                }
+                return;
+            },
+        };
+
+        if s.get_items().iter().any(|item| file.get_str(item.get_name()) == member) {
+            return;
+        }
+
+        let mut names : Vec<&str> = Vec::new();
+        for item in s.get_items() {
+            let name = file.get_str(item.get_name());
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let note = if names.is_empty() {
+            "It has no members.".to_string()
+        } else {
+            format!("Its members are {}.", names.join(", "))
+        };
+        let message = format!("Structure \"{}\" has no member \"{}\".", file.get_str(s.get_name()), member);
+        self.error(expr.get_span(), message, Some(note));
+    }
+}
+
+impl Visitor for StructChecker<'_> {
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        let items = s.get_items();
+        for (i, item) in items.iter().enumerate() {
+            let name = item.get_name();
+            if let Some(first) = items[.. i].iter().find(|other| other.get_name() == name) {
+                let message = format!("Structure \"{}\" has more than one member \"{}\".",
+                    file.get_str(s.get_name()), file.get_str(name));
+                let note = format!("The first is at {}.", first.get_span());
+                self.error(item.get_span(), message, Some(note));
+            }
+        }
+        walk_struct(self, file, s);
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        if let AstExpressionKind::StructAcc { name, member } = expr.get_kind() {
+            self.check_access(expr, file.get_str(*name), file.get_str(*member));
+        }
+        walk_expression(self, file, expr);
+    }
+}
@@ -0,0 +1,657 @@
+//
+// Type checking
+//
+// Works out the type of every expression, and checks each place where a
+// type is expected: initializers, assignments, return values, conditions,
+// case values, the operands of operators and the arguments of calls. It
+// runs on a resolved program, and finds the types of names through their
+// declarations, and those of calls through the signatures.
+//
+// Integers follow a few rules, so that no value changes without a cast:
+//
+// - A literal takes on the integer type expected of it if it fits; if not,
+//   or if nothing is expected, it is the first of i32, i64 and u64 it fits.
+// - A value converts by itself only to a type that holds all of its values:
+//   a wider type of the same sign, or a wider signed type for an unsigned
+//   value. Anything else is narrowing, and needs a cast.
+// - The operands of an arithmetic operator or a comparison meet at the
+//   smallest type that holds both, so i32 + u8 is i32 and u32 < i32
+//   compares as i64. Nothing holds both u64 and a signed type.
+//
+// An expression whose type can't be known (an undefined name, a structure
+// variable used as a value) has no type, and isn't checked any further, so
+// one mistake only gets reported once.
+//
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diag::*;
+use crate::loader::Program;
+use crate::resolve::*;
+use crate::signature::*;
+use crate::visit::*;
+
+pub struct TypeInfo {
+    types : HashMap<NodeId, DataType>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl TypeInfo {
+    //
+    // Getter functions
+    //
+    pub fn get_type(&self, id : NodeId) -> Option<DataType> {
+        self.types.get(&id).copied()
+    }
+
+    pub fn get_types(&self) -> &HashMap<NodeId, DataType> {
+        &self.types
+    }
+
+    pub fn get_diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+}
+
+//
+// Checks every file of a program
+//
+pub fn check(program : &Program, res : &Resolution) -> TypeInfo {
+    let mut structs : HashMap<NodeId, (&AstStruct, &AstFile)> = HashMap::new();
+    for file in program.get_files() {
+        for s in file.get_structs() {
+            structs.insert(s.get_id(), (s, file));
+        }
+    }
+
+    let mut checker = TypeChecker {
+        res : res,
+        structs : structs,
+        sigs : signatures(program),
+        info : TypeInfo {
+            types : HashMap::new(),
+            diagnostics : Vec::new(),
+        },
+        file : program.get_root(),
+        func_name : String::new(),
+        return_type : DataType::Void,
+    };
+
+    for file in program.get_files() {
+        checker.file = file;
+        checker.visit_file(file);
+    }
+    checker.info
+}
+
+pub fn is_integer(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::I8 | DataType::U8
+        | DataType::I16 | DataType::U16
+        | DataType::I32 | DataType::U32
+        | DataType::I64 | DataType::U64 => true,
+        _ => false,
+    }
+}
+
+pub fn is_signed(data_type : &DataType) -> bool {
+    matches!(data_type, DataType::I8 | DataType::I16 | DataType::I32 | DataType::I64)
+}
+
+// The width of an integer type in bits
+pub fn int_bits(data_type : &DataType) -> u32 {
+    match data_type {
+        DataType::I8 | DataType::U8 => 8,
+        DataType::I16 | DataType::U16 => 16,
+        DataType::I32 | DataType::U32 => 32,
+        DataType::I64 | DataType::U64 => 64,
+        _ => 0,
+    }
+}
+
+// The largest value of an integer type; literals can't be negative
+pub fn int_max(data_type : &DataType) -> u64 {
+    let bits = int_bits(data_type);
+    if is_signed(data_type) {
+        (1 << (bits - 1)) - 1
+    } else {
+        u64::MAX >> (64 - bits)
+    }
+}
+
+// The type of a literal that has no type to take on
+pub fn literal_type(value : u64) -> DataType {
+    if value <= int_max(&DataType::I32) {
+        DataType::I32
+    } else if value <= int_max(&DataType::I64) {
+        DataType::I64
+    } else {
+        DataType::U64
+    }
+}
+
+//
+// Whether every value of one integer type is a value of the other
+//
+pub fn widens(from : &DataType, to : &DataType) -> bool {
+    if !is_integer(from) || !is_integer(to) {
+        return false;
+    }
+
+    match (is_signed(from), is_signed(to)) {
+        (true, false) => false,
+        (false, true) => int_bits(to) > int_bits(from),
+        _ => int_bits(to) >= int_bits(from),
+    }
+}
+
+//
+// The smallest integer type that holds all the values of both
+//
+pub fn common_type(a : &DataType, b : &DataType) -> Option<DataType> {
+    if widens(a, b) {
+        return Some(*b);
+    } else if widens(b, a) {
+        return Some(*a);
+    } else if !is_integer(a) || !is_integer(b) {
+        return None;
+    }
+
+    // One is signed and the other unsigned but at least as wide
+    let bits = int_bits(a).max(int_bits(b));
+    match bits {
+        8 => Some(DataType::I16),
+        16 => Some(DataType::I32),
+        32 => Some(DataType::I64),
+        _ => None,
+    }
+}
+
+// "1 argument", "2 arguments"
+fn count(n : usize, one : &str, many : &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, one)
+    } else {
+        format!("{} {}", n, many)
+    }
+}
+
+// Types that can be cast to one another
+fn is_scalar(data_type : &DataType) -> bool {
+    is_integer(data_type) || *data_type == DataType::Char || *data_type == DataType::Bool
+}
+
+struct TypeChecker<'a> {
+    res : &'a Resolution,
+    structs : HashMap<NodeId, (&'a AstStruct, &'a AstFile)>,
+    sigs : Signatures,
+    info : TypeInfo,
+
+    // Where we are, for the diagnostics and return statements
+    file : &'a AstFile,
+    func_name : String,
+    return_type : DataType,
+}
+
+impl TypeChecker<'_> {
+    fn error(&mut self, span : Span, message : String) {
+        let d = diag_new(Level::Error, self.file.get_name(), Some(span), message);
+        self.info.diagnostics.push(d);
+    }
+
+    // Whether a value of one type can go where the other is expected
+    fn compatible(&self, expected : &DataType, actual : &DataType) -> bool {
+        expected == actual || widens(actual, expected)
+    }
+
+    //
+    // Checks an expression against the type expected of it
+    //
+    fn expect(&mut self, expr : &AstExpression, expected : &DataType) {
+        if let Some(actual) = self.expr(expr, Some(expected)) {
+            if self.compatible(expected, &actual) {
+                return;
+            }
+
+            let message = match expr.get_kind() {
+                AstExpressionKind::IntLiteral(value) if is_integer(expected) => {
+                    format!("Integer literal {} doesn't fit in {}, which holds at most {}.", value, expected.name(), int_max(expected))
+                },
+
+                _ if is_integer(expected) && is_integer(&actual) => {
+                    format!("Converting {} to {} can lose data; use a cast.", actual.name(), expected.name())
+                },
+
+                _ => format!("Expected {}, found {}.", expected.name(), actual.name()),
+            };
+            self.error(expr.get_span(), message);
+        }
+    }
+
+    fn expect_bool(&mut self, expr : &AstExpression, what : &str) {
+        if let Some(actual) = self.expr(expr, Some(&DataType::Bool)) {
+            if actual != DataType::Bool {
+                self.error(expr.get_span(), format!("The condition of {} must be bool, found {}.", what, actual.name()));
+            }
+        }
+    }
+
+    fn expect_integer(&mut self, expr : &AstExpression, what : &str) {
+        if let Some(actual) = self.expr(expr, None) {
+            if !is_integer(&actual) {
+                self.error(expr.get_span(), format!("{} must be an integer, found {}.", what, actual.name()));
+            }
+        }
+    }
+
+    // The declaration a name was bound to by the resolver
+    fn decl(&self, id : NodeId) -> Option<&Decl> {
+        self.res.get_binding(id).and_then(|decl| self.res.get_decl(decl))
+    }
+
+    // Whether a name stands for a whole array or structure variable, which
+    // can't be used or assigned as one value
+    fn whole_kind(&self, id : NodeId) -> Option<DeclKind> {
+        let kind = self.decl(id)?.get_kind();
+        match kind {
+            DeclKind::Array | DeclKind::StructVar => Some(kind),
+            _ => None,
+        }
+    }
+
+    // The type of a name used as a value
+    fn value_type(&mut self, expr : &AstExpression, name : &str) -> Option<DataType> {
+        if let Some(kind) = self.whole_kind(expr.get_id()) {
+            self.error(expr.get_span(), format!("\"{}\" is {}, not a single value.", name, kind.a_name()));
+            return None;
+        }
+
+        let decl = self.decl(expr.get_id())?;
+        if decl.get_kind().is_value() {
+            Some(decl.get_data_type())
+        } else {
+            None
+        }
+    }
+
+    // The type of an element of an array
+    fn element_type(&mut self, expr : &AstExpression, name : &str) -> Option<DataType> {
+        let decl = self.decl(expr.get_id())?;
+        let (kind, data_type) = (decl.get_kind(), decl.get_data_type());
+        match kind {
+            DeclKind::Array => Some(data_type),
+            _ if kind.is_value() => {
+                self.error(expr.get_span(), format!("\"{}\" is {}, not an array.", name, kind.a_name()));
+                None
+            },
+            _ => None,
+        }
+    }
+
+    // The type of a member of a structure variable
+    fn member_type(&self, id : NodeId, member : &str) -> Option<DataType> {
+        let var = self.res.get_binding(id)?;
+        let (s, file) = self.structs.get(&self.res.get_type_binding(var)?)?;
+        let item = s.get_items().iter().find(|item| file.get_str(item.get_name()) == member)?;
+        Some(item.get_data_type())
+    }
+
+    //
+    // Works out the type of an expression
+    // An integer literal takes on the integer type expected of it, if any
+    //
+    fn expr(&mut self, expr : &AstExpression, expected : Option<&DataType>) -> Option<DataType> {
+        let file = self.file;
+        let e = |r : &ExprRef| file.get_expression(*r);
+        let data_type = match expr.get_kind() {
+            AstExpressionKind::IntLiteral(value) => match expected {
+                Some(t) if is_integer(t) && *value <= int_max(t) => Some(*t),
+                _ => Some(literal_type(*value)),
+            },
+
+            AstExpressionKind::CharLiteral(_) => Some(DataType::Char),
+            AstExpressionKind::StringLiteral(_) => Some(DataType::String),
+            AstExpressionKind::BoolLiteral(_) => Some(DataType::Bool),
+
+            AstExpressionKind::Id(name) => self.value_type(expr, file.get_str(*name)),
+
+            AstExpressionKind::ArrayAcc { name, index } => {
+                self.expect_integer(e(index), "An array index");
+                self.element_type(expr, file.get_str(*name))
+            },
+
+            AstExpressionKind::StructAcc { member, .. } => self.member_type(expr.get_id(), file.get_str(*member)),
+
+            AstExpressionKind::Call { name, args } => {
+                let name = file.get_str(*name);
+                let data_type = self.call(expr.get_id(), expr.get_span(), name, args);
+                if data_type == Some(DataType::Void) {
+                    self.error(expr.get_span(), format!("Function \"{}\" returns nothing, so it can't be used as a value.", name));
+                    None
+                } else {
+                    data_type
+                }
+            },
+
+            AstExpressionKind::Cast { data_type, expr : inner } => {
+                match self.expr(e(inner), None) {
+                    Some(from) if !(is_scalar(&from) && is_scalar(data_type)) && from != *data_type => {
+                        self.error(expr.get_span(), format!("Can't cast {} to {}.", from.name(), data_type.name()));
+                        None
+                    },
+
+                    _ => Some(*data_type),
+                }
+            },
+
+            AstExpressionKind::Binary { op, lhs, rhs } => self.binary(expr, *op, e(lhs), e(rhs), expected),
+
+            AstExpressionKind::Assign { op, lhs, rhs } => {
+                let (lhs, rhs) = (e(lhs), e(rhs));
+                let target = match (lhs.get_kind(), self.whole_kind(lhs.get_id())) {
+                    (AstExpressionKind::Id(name), Some(kind)) => {
+                        let message = format!("\"{}\" is {}, and can't be assigned to as a whole.", file.get_str(*name), kind.a_name());
+                        self.error(lhs.get_span(), message);
+                        None
+                    },
+
+                    // A constant keeps the value it was declared with
+                    (AstExpressionKind::Id(name), None) if self.decl(lhs.get_id()).is_some_and(|d| d.get_kind() == DeclKind::Const) => {
+                        self.error(lhs.get_span(), format!("\"{}\" is a constant, and can't be assigned to.", file.get_str(*name)));
+                        self.expr(lhs, None)
+                    },
+
+                    _ => self.expr(lhs, None),
+                };
+                match &target {
+                    Some(t) if *op != AssignOp::Assign && !is_integer(t) => {
+                        self.error(expr.get_span(), format!("Operator \"{}\" needs an integer, found {}.", op.symbol(), t.name()));
+                        self.expr(rhs, None);
+                    },
+
+                    // Like a plain shift, the amount can be any integer
+                    _ if matches!(op, AssignOp::Lsh | AssignOp::Rsh) => self.expect_integer(rhs, "A shift amount"),
+
+                    Some(t) => self.expect(rhs, t),
+                    None => { self.expr(rhs, None); },
+                }
+                target
+            },
+        };
+
+        if let Some(t) = &data_type {
+            self.info.types.insert(expr.get_id(), *t);
+        }
+        data_type
+    }
+
+    //
+    // Works out the type of a binary operator
+    // The side that isn't a literal goes first, so the literal can take on
+    // its type
+    //
+    fn binary(&mut self, expr : &AstExpression, op : BinaryOp, lhs : &AstExpression, rhs : &AstExpression,
+              expected : Option<&DataType>) -> Option<DataType> {
+        let lhs_literal = matches!(lhs.get_kind(), AstExpressionKind::IntLiteral(_));
+
+        // Arithmetic passes on the type expected of it; comparisons don't
+        let hint = match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod
+            | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Lsh | BinaryOp::Rsh
+            | BinaryOp::Range => expected.copied(),
+            _ => None,
+        };
+
+        let (a, b) = if lhs_literal {
+            let b = self.expr(rhs, hint.as_ref());
+            let a = self.expr(lhs, b.as_ref().or(hint.as_ref()));
+            (a, b)
+        } else {
+            let a = self.expr(lhs, hint.as_ref());
+            let b = self.expr(rhs, a.as_ref().or(hint.as_ref()));
+            (a, b)
+        };
+
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                // Comparisons are bool whatever the operands are
+                return match op {
+                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Le
+                    | BinaryOp::LGAnd | BinaryOp::LGOr => Some(DataType::Bool),
+                    _ => None,
+                };
+            },
+        };
+
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod
+            | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                if !is_integer(&a) || !is_integer(&b) {
+                    self.operand_error(expr, op, "integer operands", &a, &b);
+                    return None;
+                }
+                self.meet(expr, op, &a, &b)
+            },
+
+            // The shift amount can be any integer
+            BinaryOp::Lsh | BinaryOp::Rsh => {
+                if !is_integer(&a) || !is_integer(&b) {
+                    self.operand_error(expr, op, "integer operands", &a, &b);
+                    return None;
+                }
+                Some(a)
+            },
+
+            // Integers compare by value, whatever their types
+            BinaryOp::Eq | BinaryOp::Ne => {
+                if is_integer(&a) && is_integer(&b) {
+                    self.meet(expr, op, &a, &b);
+                } else if a != b {
+                    self.operand_error(expr, op, "operands of the same type", &a, &b);
+                }
+                Some(DataType::Bool)
+            },
+
+            BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Le => {
+                if is_integer(&a) && is_integer(&b) {
+                    self.meet(expr, op, &a, &b);
+                } else if a != DataType::Char || b != DataType::Char {
+                    self.operand_error(expr, op, "two integers or two characters", &a, &b);
+                }
+                Some(DataType::Bool)
+            },
+
+            BinaryOp::LGAnd | BinaryOp::LGOr => {
+                if a != DataType::Bool || b != DataType::Bool {
+                    self.operand_error(expr, op, "bool operands", &a, &b);
+                }
+                Some(DataType::Bool)
+            },
+
+            BinaryOp::Range => {
+                if is_integer(&a) && is_integer(&b) {
+                    self.meet(expr, op, &a, &b)
+                } else {
+                    if a != b {
+                        self.operand_error(expr, op, "ends of the same type", &a, &b);
+                    }
+                    Some(a)
+                }
+            },
+        }
+    }
+
+    // The type two integer operands meet at
+    fn meet(&mut self, expr : &AstExpression, op : BinaryOp, a : &DataType, b : &DataType) -> Option<DataType> {
+        let data_type = common_type(a, b);
+        if data_type.is_none() {
+            let message = format!("Operator \"{}\" mixes {} and {}, and no integer type holds both; use a cast.",
+                op.symbol(), a.name(), b.name());
+            self.error(expr.get_span(), message);
+        }
+        data_type
+    }
+
+    fn operand_error(&mut self, expr : &AstExpression, op : BinaryOp, needs : &str, a : &DataType, b : &DataType) {
+        let message = format!("Operator \"{}\" needs {}, found {} and {}.", op.symbol(), needs, a.name(), b.name());
+        self.error(expr.get_span(), message);
+    }
+
+    // The signature of the function a call is to
+    fn signature(&self, id : NodeId, name : &str) -> Option<Signature> {
+        match self.res.get_binding(id) {
+            Some(decl) => self.sigs.get_function(decl).cloned(),
+            None => self.sigs.get_builtin(name).cloned(),
+        }
+    }
+
+    //
+    // Checks the arguments of a call against the function, and returns
+    // what the function returns
+    //
+    fn call(&mut self, id : NodeId, span : Span, name : &str, args : &Vec<ExprRef>) -> Option<DataType> {
+        let file = self.file;
+        let sig = match self.signature(id, name) {
+            Some(sig) => sig,
+            None => {
+                for arg in args {
+                    self.expr(file.get_expression(*arg), None);
+                }
+                return None;
+            },
+        };
+
+        let params = sig.get_args();
+        let fits = if sig.is_variadic() { args.len() >= params.len() } else { args.len() == params.len() };
+        if !fits {
+            let message = format!("Function \"{}\" takes {}{}, but {} given.", name,
+                if sig.is_variadic() { "at least " } else { "" },
+                count(params.len(), "argument", "arguments"), count(args.len(), "was", "were"));
+            let note = match sig.get_span() {
+                Some(at) => format!("It is declared as {} at {}:{}.", sig, sig.get_file(), at),
+                None => format!("It is the builtin {}.", sig),
+            };
+
+            let mut d = diag_new(Level::Error, file.get_name(), Some(span), message);
+            d.add_note(note);
+            self.info.diagnostics.push(d);
+
+            for arg in args {
+                self.expr(file.get_expression(*arg), None);
+            }
+        } else {
+            for (arg, (_, data_type)) in args.iter().zip(params) {
+                self.expect(file.get_expression(*arg), data_type);
+            }
+
+            // The values after the arguments can be of any type
+            for arg in &args[params.len() ..] {
+                self.expr(file.get_expression(*arg), None);
+            }
+        }
+        Some(sig.get_data_type())
+    }
+
+    // Constants, structure defaults and arguments with a value
+    fn check_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        if let Some(value) = arg.get_expression() {
+            self.expect(file.get_expression(value), &arg.get_data_type());
+        }
+    }
+}
+
+impl Visitor for TypeChecker<'_> {
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        self.check_arg(file, item);
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.check_arg(file, c);
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        self.func_name = file.get_str(func.get_name()).to_string();
+        self.return_type = func.get_data_type();
+        walk_function(self, file, func);
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        let e = |r : &ExprRef| file.get_expression(*r);
+        match stmt.get_kind() {
+            AstStatementKind::Return(value) => {
+                let return_type = self.return_type;
+                match value {
+                    Some(value) if return_type == DataType::Void => {
+                        self.expr(e(value), None);
+                        let message = format!("Function \"{}\" returns nothing, but a value is returned.", self.func_name);
+                        self.error(stmt.get_span(), message);
+                    },
+
+                    Some(value) => self.expect(e(value), &return_type),
+
+                    None if return_type != DataType::Void => {
+                        let message = format!("Function \"{}\" must return a value of type {}.", self.func_name, return_type.name());
+                        self.error(stmt.get_span(), message);
+                    },
+
+                    None => {},
+                }
+            },
+
+            AstStatementKind::VarDec { data_type, value : Some(value), .. } => self.expect(e(value), data_type),
+            AstStatementKind::VarDec { value : None, .. } => {},
+            AstStatementKind::ArrayDec { size, .. } => self.expect_integer(e(size), "An array size"),
+            AstStatementKind::StructDec { .. } => {},
+
+            AstStatementKind::CallStmt { name, args } => {
+                self.call(stmt.get_id(), stmt.get_span(), file.get_str(*name), args);
+            },
+
+            AstStatementKind::ExprStmt(expr) => { self.expr(e(expr), None); },
+
+            AstStatementKind::While { cond, block, .. } => {
+                self.expect_bool(e(cond), "a while loop");
+                self.visit_block(file, block);
+            },
+
+            AstStatementKind::Loop { block, .. } => self.visit_block(file, block),
+
+            AstStatementKind::Repeat { block, cond, .. } => {
+                self.visit_block(file, block);
+                self.expect_bool(e(cond), "a repeat loop");
+            },
+
+            AstStatementKind::Break(_) | AstStatementKind::Continue(_) => {},
+
+            AstStatementKind::If { branches, else_block } => {
+                for br in branches {
+                    self.expect_bool(e(&br.get_cond()), "an if");
+                    self.visit_block(file, br.get_block());
+                }
+
+                if let Some(block) = else_block {
+                    self.visit_block(file, block);
+                }
+            },
+
+            // The case values have to be of the type being matched
+            AstStatementKind::Match { expr, cases, default } => {
+                let data_type = self.expr(e(expr), None);
+                for case in cases {
+                    for value in case.get_values() {
+                        match &data_type {
+                            Some(t) => self.expect(e(value), t),
+                            None => { self.expr(e(value), None); },
+                        }
+                    }
+                    self.visit_block(file, case.get_block());
+                }
+
+                if let Some(block) = default {
+                    self.visit_block(file, block);
+                }
+            },
+        }
+    }
+}
@@ -1,289 +1,355 @@
 use crate::ast::*;
+use crate::intern::Symbol;
+use crate::visit::*;
 
-pub fn unwrite(file : AstFile) {
-    for path in file.get_imports() {
-        print!("import ");
-        for c in path.chars() {
-            if c == '/' { print!("."); }
-            else { print!("{}", c); }
+pub fn unwrite(file : &AstFile) {
+    print!("{}", render(file));
+}
+
+// Returns the source instead of printing it
+pub fn render(file : &AstFile) -> String {
+    let mut unwriter = Unwriter {
+        out : String::new(),
+        indent : 0,
+    };
+    unwriter.visit_file(file);
+    unwriter.out
+}
+
+//
+// Turns the tree back into source
+// The indent is where the statements of the current block go
+//
+struct Unwriter {
+    out : String,
+    indent : i32,
+}
+
+impl Unwriter {
+    fn write(&mut self, text : &str) {
+        self.out.push_str(text);
+    }
+
+    fn line(&mut self, text : &str) {
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn print_indent(&mut self, extra : i32) {
+        for _i in 0 .. self.indent + extra {
+            self.out.push(' ');
         }
-        println!(";");
     }
 
-    for s in file.get_structs() {
-        unwrite_structure(s);
+    fn unwrite_block(&mut self, file : &AstFile, block : &Vec<StmtRef>, extra : i32) {
+        self.indent += extra;
+        self.visit_block(file, block);
+        self.indent -= extra;
     }
-    
-    for c in file.get_consts() {
-        print!("const {} : ", c.get_name());
-        unwrite_data_type(&c.get_data_type());
-        print!(" := ");
-        unwrite_expression(c.get_expression(), false);
-        println!(";");
+
+    fn unwrite_label(&mut self, file : &AstFile, label : &Option<Symbol>) {
+        if let Some(label) = label {
+            self.write(&format!("{}: ", file.get_str(*label)));
+        }
     }
-    
-    for func in file.get_functions() {
-        unwrite_function(func);
+
+    fn unwrite_expression(&mut self, file : &AstFile, expr : ExprRef) {
+        self.visit_expression(file, file.get_expression(expr));
     }
-}
 
-fn unwrite_structure(s : &AstStruct) {
-    println!("struct {} is", s.get_name());
-    for item in s.get_items() {
-        print!("    {} : ", item.get_name());
-        unwrite_data_type(&item.get_data_type());
-        print!(" := ");
-        unwrite_expression(item.get_expression(), true);
-        println!(";");
+    // Puts an operator in parentheses
+    fn unwrite_grouped(&mut self, file : &AstFile, expr : ExprRef) {
+        let expr = file.get_expression(expr);
+        match expr.get_kind() {
+            AstExpressionKind::Binary { .. } | AstExpressionKind::Assign { .. } => {
+                self.write("(");
+                self.visit_expression(file, expr);
+                self.write(")");
+            },
+
+            _ => self.visit_expression(file, expr),
+        }
     }
-    println!("end");
-}
 
-fn unwrite_function(func : &AstFunction) {
-    print!("func {}", func.get_name());
-    let args = func.get_args();
-    if args.len() > 0 {
-        print!("(");
+    fn unwrite_list(&mut self, file : &AstFile, list : &Vec<ExprRef>) {
         let mut index : usize = 0;
-        for arg in args {
-            print!("{} : ", arg.get_name());
-            unwrite_data_type(&arg.get_data_type());
-            if index + 1 < args.len() {
-                print!(", ");
+        for item in list {
+            self.unwrite_expression(file, *item);
+            if index + 1 < list.len() {
+                self.write(", ");
             }
             index += 1;
         }
-        print!(")");
-    }
-    
-    if func.get_data_type() != DataType::Void {
-        print!(" -> ");
-        unwrite_data_type(&func.get_data_type());
     }
-    println!(" is");
-    
-    for c in func.get_consts() {
-        print!("    ");
-        print!("const {} : ", c.get_name());
-        unwrite_data_type(&c.get_data_type());
-        print!(" := ");
-        unwrite_expression(c.get_expression(), false);
-        println!(";");
-    }
-    
-    unwrite_block(func.get_block(), 0);
-    println!("end");
 }
 
-fn unwrite_block(block : &AstStatement, indent : i32) {
-    for stmt in block.get_statements() {
-        unwrite_statement(&stmt, indent+4);
+impl Visitor for Unwriter {
+    fn visit_import(&mut self, _file : &AstFile, path : &str) {
+        self.write("import ");
+        for c in path.chars() {
+            if c == '/' { self.write("."); }
+            else { self.out.push(c); }
+        }
+        self.line(";");
     }
-    
-    /*for _i in 0 .. indent {
-        print!(" ");
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        self.line(&format!("struct {} is", file.get_str(s.get_name())));
+        walk_struct(self, file, s);
+        self.line("end");
     }
-    println!("end");*/
-}
 
-fn unwrite_statement(stmt : &AstStatement, indent : i32) {
-    for _i in 0 .. indent {
-        print!(" ");
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        self.write(&format!("    {} : ", file.get_str(item.get_name())));
+        self.visit_data_type(&item.get_data_type());
+        if let Some(expr) = item.get_expression() {
+            self.write(" := ");
+            self.unwrite_expression(file, expr);
+        }
+        self.line(";");
     }
-    
-    match stmt.get_type() {
-        AstType::Return => {
-            print!("return");
-            let expr = stmt.get_expression();
-            if expr.get_type() != AstType::None {
-                print!(" ");
-                unwrite_expression(expr, false);
-            } 
-            println!(";");
-        },
-    
-        AstType::VarDec => {
-            print!("var {} : ", stmt.get_name());
-            unwrite_data_type(&stmt.get_data_type());
-            print!(" := ");
-            unwrite_expression(stmt.get_expression(), true);
-            println!(";");
-        },
-        
-        AstType::ArrayDec => {
-            print!("var {} : ", stmt.get_name());
-            unwrite_data_type(&stmt.get_data_type());
-            print!("[");
-            unwrite_expression(stmt.get_expression(), false);
-            println!("];");
-        },
-        
-        AstType::StructDec => {
-            print!("struct {} : ", stmt.get_name());
-            unwrite_expression(stmt.get_expression(), false);
-            println!(";");
-        },
-        
-        AstType::ExprStmt => {
-            unwrite_expression(stmt.get_expression(), false);
-            println!(";");
-        },
-    
-        AstType::CallStmt => {
-            print!("{}", stmt.get_name());
-            //if stmt.expr.ast_type != AstType::ExprList {
-                print!("(");
-                unwrite_expression(stmt.get_expression(), false);
-                print!(")");
-            /*} else {
-                unwrite_expression(&stmt.expr);
-            }*/
-            println!(";");
-        },
-        
-        AstType::While => {
-            print!("while ");
-            unwrite_expression(stmt.get_expression(), false);
-            println!(" do");
-            
-            unwrite_block(stmt.get_block(), indent);
-            for _i in 0 .. indent { print!(" "); }
-            println!("end");
-        },
-        
-        AstType::If | AstType::Elif => {
-            if stmt.get_type() == AstType::Elif { print!("elif "); }
-            else { print!("if "); }
-            unwrite_expression(stmt.get_expression(), false);
-            println!(" then");
-            
-            unwrite_block(stmt.get_block(), indent);
-            
-            for br in stmt.get_branches() {
-                unwrite_statement(br, indent);
-            }
-            
-            if stmt.get_type() == AstType::If {
-                for _i in 0 .. indent { print!(" "); }
-                println!("end");
-            }
-        },
-        
-        AstType::Else => {
-            println!("else");
-            unwrite_block(stmt.get_block(), indent);
-        },
-        
-        AstType::Break => println!("break;"),
-        AstType::Continue => println!("continue;"),
-        
-        _ => { println!(""); },
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        self.write(&format!("{} : ", file.get_str(arg.get_name())));
+        self.visit_data_type(&arg.get_data_type());
     }
-}
 
-fn unwrite_expression(expr : &AstExpression, ignore_lval : bool) {
-    match expr.get_type() {
-        //
-        // Binary operators
-        //
-        AstType::Assign => {
-            if !ignore_lval {
-                unwrite_expression(expr.get_lval(), false);
-                print!(" := ");
-            }
-            unwrite_expression(expr.get_rval(), false);
-        },
-        
-        AstType::Add | AstType::Sub
-        | AstType::Mul | AstType::Div | AstType::Mod
-        | AstType::And | AstType::Or | AstType::Xor 
-        | AstType::Eq | AstType::Ne
-        | AstType::Gt | AstType::Ge | AstType::Lt | AstType::Le 
-        | AstType::LGAnd | AstType::LGOr => {
-            unwrite_expression(expr.get_lval(), false);
-            match expr.get_type() {
-                AstType::Add => print!(" + "),
-                AstType::Sub => print!(" - "),
-                AstType::Mul => print!(" * "),
-                AstType::Div => print!(" / "),
-                AstType::Mod => print!(" % "),
-                AstType::And => print!(" & "),
-                AstType::Or => print!(" | "),
-                AstType::Xor => print!(" ^ "),
-                
-                AstType::Eq => print!(" = "),
-                AstType::Ne => print!(" != "),
-                AstType::Gt => print!(" > "),
-                AstType::Ge => print!(" >= "),
-                AstType::Lt => print!(" < "),
-                AstType::Le => print!(" <= "),
-                
-                AstType::LGAnd => print!(" && "),
-                AstType::LGOr => print!(" || "),
-                
-                _ => {},
-            }
-            unwrite_expression(expr.get_rval(), false);
-        },
-        
-        //
-        // Literals and primary expressions
-        //
-        AstType::Id => print!("{}", expr.get_name()),
-        AstType::IntLiteral => print!("{}", expr.get_int()),
-        AstType::StringLiteral => print!("{:?}", expr.get_string()),
-        AstType::CharLiteral => print!("{:?}", expr.get_char()),
-        AstType::BoolLiteral(val) => print!("{}", val),
-        
-        AstType::ArrayAcc => {
-            print!("{}[", expr.get_name());
-            unwrite_expression(expr.get_arg(), false);
-            print!("]");
-        },
-        
-        AstType::StructAcc => {
-            print!("{}.", expr.get_name());
-            unwrite_expression(expr.get_arg(), false);
-        },
-        
-        //
-        // Generic expressions
-        //
-        AstType::ExprList => {
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        self.print_indent(0);
+        self.write(&format!("const {} : ", file.get_str(c.get_name())));
+        self.visit_data_type(&c.get_data_type());
+        if let Some(expr) = c.get_expression() {
+            self.write(" := ");
+            self.unwrite_expression(file, expr);
+        }
+        self.line(";");
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        self.write(&format!("func {}", file.get_str(func.get_name())));
+        let args = func.get_args();
+        if args.len() > 0 {
+            self.write("(");
             let mut index : usize = 0;
-            for item in expr.get_list() {
-                unwrite_expression(&item, false);
-                if index + 1 < expr.get_list_size() {
-                    print!(", ");
+            for arg in args {
+                self.visit_arg(file, arg);
+                if index + 1 < args.len() {
+                    self.write(", ");
                 }
                 index += 1;
             }
-        },
-        
-        AstType::Call => {
-            print!("{}(", expr.get_name());
-            unwrite_expression(expr.get_arg(), false);
-            print!(")");
-        },
-        
-        _ => {},
+            self.write(")");
+        }
+
+        if func.get_data_type() != DataType::Void {
+            self.write(" -> ");
+            self.visit_data_type(&func.get_data_type());
+        }
+        self.line(" is");
+
+        self.indent += 4;
+        for c in func.get_consts() {
+            self.visit_const(file, c);
+        }
+        self.visit_block(file, func.get_block());
+        self.indent -= 4;
+        self.line("end");
     }
-}
 
-fn unwrite_data_type(data_type : &DataType) {
-    match &data_type {
-        DataType::Void => print!("void"),
-        DataType::I8 => print!("i8"),
-        DataType::U8 => print!("u8"),
-        DataType::I16 => print!("i16"),
-        DataType::U16 => print!("u16"),
-        DataType::I32 => print!("i32"),
-        DataType::U32 => print!("u32"),
-        DataType::I64 => print!("i64"),
-        DataType::U64 => print!("u64"),
-        DataType::String => print!("string"),
-        DataType::Char => print!("char"),
-        DataType::Bool => print!("bool"),
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        self.print_indent(0);
+
+        match stmt.get_kind() {
+            AstStatementKind::Return(expr) => {
+                self.write("return");
+                if let Some(expr) = expr {
+                    self.write(" ");
+                    self.unwrite_expression(file, *expr);
+                }
+                self.line(";");
+            },
+
+            AstStatementKind::VarDec { name, data_type, value } => {
+                self.write(&format!("var {} : ", file.get_str(*name)));
+                self.visit_data_type(data_type);
+                if let Some(value) = value {
+                    self.write(" := ");
+                    self.unwrite_expression(file, *value);
+                }
+                self.line(";");
+            },
+
+            AstStatementKind::ArrayDec { name, data_type, size } => {
+                self.write(&format!("var {} : ", file.get_str(*name)));
+                self.visit_data_type(data_type);
+                self.write("[");
+                self.unwrite_expression(file, *size);
+                self.line("];");
+            },
+
+            AstStatementKind::StructDec { name, struct_name } => {
+                self.line(&format!("struct {} : {};", file.get_str(*name), file.get_str(*struct_name)));
+            },
+
+            AstStatementKind::ExprStmt(expr) => {
+                self.unwrite_expression(file, *expr);
+                self.line(";");
+            },
+
+            AstStatementKind::CallStmt { name, args } => {
+                self.write(&format!("{}(", file.get_str(*name)));
+                self.unwrite_list(file, args);
+                self.line(");");
+            },
+
+            AstStatementKind::While { label, cond, block } => {
+                self.unwrite_label(file, label);
+                self.write("while ");
+                self.unwrite_expression(file, *cond);
+                self.line(" do");
+
+                self.unwrite_block(file, block, 4);
+                self.print_indent(0);
+                self.line("end");
+            },
+
+            AstStatementKind::Loop { label, block } => {
+                self.unwrite_label(file, label);
+                self.line("loop");
+
+                self.unwrite_block(file, block, 4);
+                self.print_indent(0);
+                self.line("end");
+            },
+
+            AstStatementKind::Repeat { label, block, cond } => {
+                self.unwrite_label(file, label);
+                self.line("repeat");
+
+                self.unwrite_block(file, block, 4);
+                self.print_indent(0);
+                self.write("until ");
+                self.unwrite_expression(file, *cond);
+                self.line(";");
+            },
+
+            AstStatementKind::If { branches, else_block } => {
+                let mut index : usize = 0;
+                for br in branches {
+                    if index > 0 {
+                        self.print_indent(0);
+                        self.write("elif ");
+                    } else {
+                        self.write("if ");
+                    }
+                    self.unwrite_expression(file, br.get_cond());
+                    self.line(" then");
+
+                    self.unwrite_block(file, br.get_block(), 4);
+                    index += 1;
+                }
+
+                if let Some(block) = else_block {
+                    self.print_indent(0);
+                    self.line("else");
+                    self.unwrite_block(file, block, 4);
+                }
+
+                self.print_indent(0);
+                self.line("end");
+            },
+
+            AstStatementKind::Match { expr, cases, default } => {
+                self.write("match ");
+                self.unwrite_expression(file, *expr);
+                self.line(" is");
+
+                for case in cases {
+                    self.print_indent(4);
+                    self.write("case ");
+                    self.unwrite_list(file, case.get_values());
+                    self.line(" then");
+                    self.unwrite_block(file, case.get_block(), 8);
+                }
+
+                if let Some(block) = default {
+                    self.print_indent(4);
+                    self.line("else");
+                    self.unwrite_block(file, block, 8);
+                }
+
+                self.print_indent(0);
+                self.line("end");
+            },
+
+            AstStatementKind::Break(label) | AstStatementKind::Continue(label) => {
+                if let AstStatementKind::Break(_) = stmt.get_kind() { self.write("break"); }
+                else { self.write("continue"); }
+                if let Some(label) = label {
+                    self.write(&format!(" {}", file.get_str(*label)));
+                }
+                self.line(";");
+            },
+        }
     }
-}
 
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        match expr.get_kind() {
+            //
+            // Operators
+            //
+            AstExpressionKind::Assign { op, lhs, rhs } => {
+                self.unwrite_expression(file, *lhs);
+                self.write(&format!(" {} ", op.symbol()));
+                self.unwrite_expression(file, *rhs);
+            },
+
+            // Operators group to the right, so an operator on the left
+            // needs its parentheses back
+            AstExpressionKind::Binary { op, lhs, rhs } => {
+                self.unwrite_grouped(file, *lhs);
+                self.write(&format!(" {} ", op.symbol()));
+                self.unwrite_expression(file, *rhs);
+            },
+
+            //
+            // Literals and primary expressions
+            //
+            AstExpressionKind::Id(name) => self.write(file.get_str(*name)),
+            AstExpressionKind::IntLiteral(val) => self.write(&val.to_string()),
+            AstExpressionKind::StringLiteral(val) => self.write(&format!("{:?}", file.get_str(*val))),
+            AstExpressionKind::CharLiteral(val) => self.write(&format!("{:?}", val)),
+            AstExpressionKind::BoolLiteral(val) => self.write(&val.to_string()),
+
+            AstExpressionKind::ArrayAcc { name, index } => {
+                self.write(&format!("{}[", file.get_str(*name)));
+                self.unwrite_expression(file, *index);
+                self.write("]");
+            },
+
+            AstExpressionKind::StructAcc { name, member } => {
+                self.write(&format!("{}.{}", file.get_str(*name), file.get_str(*member)));
+            },
+
+            //
+            // Generic expressions
+            //
+            AstExpressionKind::Call { name, args } => {
+                self.write(&format!("{}(", file.get_str(*name)));
+                self.unwrite_list(file, args);
+                self.write(")");
+            },
+
+            AstExpressionKind::Cast { data_type, expr } => {
+                // The cast binds tighter than any operator, so keep the grouping
+                self.unwrite_grouped(file, *expr);
+                self.write(" as ");
+                self.visit_data_type(data_type);
+            },
+        }
+    }
+
+    fn visit_data_type(&mut self, data_type : &DataType) {
+        self.write(data_type.name());
+    }
+}
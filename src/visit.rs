@@ -0,0 +1,402 @@
+//
+// Walking the AST
+//
+// A Visitor looks at the tree, and a MutVisitor can change it in place, including
+// replacing whole nodes. Every visit function walks the children of its node by
+// default, so an implementation only has to override the nodes it cares about.
+// An override that still wants the children calls the matching walk function.
+//
+// The file is passed along with every node, since the children of a node are
+// handles into the file and its names are symbols the file resolves.
+//
+use std::mem;
+
+use crate::ast::*;
+
+pub trait Visitor {
+    fn visit_file(&mut self, file : &AstFile) {
+        walk_file(self, file);
+    }
+
+    fn visit_import(&mut self, _file : &AstFile, _path : &str) {}
+
+    fn visit_struct(&mut self, file : &AstFile, s : &AstStruct) {
+        walk_struct(self, file, s);
+    }
+
+    // Structure items, function arguments and constants are all arguments
+    fn visit_item(&mut self, file : &AstFile, item : &AstArg) {
+        walk_arg(self, file, item);
+    }
+
+    fn visit_arg(&mut self, file : &AstFile, arg : &AstArg) {
+        walk_arg(self, file, arg);
+    }
+
+    fn visit_const(&mut self, file : &AstFile, c : &AstArg) {
+        walk_arg(self, file, c);
+    }
+
+    fn visit_function(&mut self, file : &AstFile, func : &AstFunction) {
+        walk_function(self, file, func);
+    }
+
+    fn visit_block(&mut self, file : &AstFile, block : &Vec<StmtRef>) {
+        walk_block(self, file, block);
+    }
+
+    fn visit_statement(&mut self, file : &AstFile, stmt : &AstStatement) {
+        walk_statement(self, file, stmt);
+    }
+
+    fn visit_expression(&mut self, file : &AstFile, expr : &AstExpression) {
+        walk_expression(self, file, expr);
+    }
+
+    fn visit_data_type(&mut self, _data_type : &DataType) {}
+}
+
+pub trait MutVisitor {
+    fn visit_file(&mut self, file : &mut AstFile) {
+        walk_file_mut(self, file);
+    }
+
+    // The declarations are taken out of the file while they are visited
+    fn visit_struct(&mut self, file : &mut AstFile, s : &mut AstStruct) {
+        walk_struct_mut(self, file, s);
+    }
+
+    fn visit_item(&mut self, file : &mut AstFile, item : &mut AstArg) {
+        walk_arg_mut(self, file, item);
+    }
+
+    fn visit_arg(&mut self, file : &mut AstFile, arg : &mut AstArg) {
+        walk_arg_mut(self, file, arg);
+    }
+
+    fn visit_const(&mut self, file : &mut AstFile, c : &mut AstArg) {
+        walk_arg_mut(self, file, c);
+    }
+
+    fn visit_function(&mut self, file : &mut AstFile, func : &mut AstFunction) {
+        walk_function_mut(self, file, func);
+    }
+
+    // Statements can be added to or taken out of the block here
+    fn visit_block(&mut self, file : &mut AstFile, block : &mut Vec<StmtRef>) {
+        walk_block_mut(self, file, block);
+    }
+
+    // A node is replaced by changing it through the file
+    fn visit_statement(&mut self, file : &mut AstFile, stmt : StmtRef) {
+        walk_statement_mut(self, file, stmt);
+    }
+
+    fn visit_expression(&mut self, file : &mut AstFile, expr : ExprRef) {
+        walk_expression_mut(self, file, expr);
+    }
+}
+
+//
+// The walk functions for the visitor
+//
+pub fn walk_file<V : Visitor + ?Sized>(v : &mut V, file : &AstFile) {
+    for path in file.get_imports() {
+        v.visit_import(file, path);
+    }
+
+    for s in file.get_structs() {
+        v.visit_struct(file, s);
+    }
+
+    for c in file.get_consts() {
+        v.visit_const(file, c);
+    }
+
+    for func in file.get_functions() {
+        v.visit_function(file, func);
+    }
+}
+
+pub fn walk_struct<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, s : &AstStruct) {
+    for item in s.get_items() {
+        v.visit_item(file, item);
+    }
+}
+
+pub fn walk_arg<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, arg : &AstArg) {
+    v.visit_data_type(&arg.get_data_type());
+    if let Some(expr) = arg.get_expression() {
+        v.visit_expression(file, file.get_expression(expr));
+    }
+}
+
+pub fn walk_function<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, func : &AstFunction) {
+    for arg in func.get_args() {
+        v.visit_arg(file, arg);
+    }
+
+    v.visit_data_type(&func.get_data_type());
+
+    for c in func.get_consts() {
+        v.visit_const(file, c);
+    }
+
+    v.visit_block(file, func.get_block());
+}
+
+pub fn walk_block<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, block : &Vec<StmtRef>) {
+    for stmt in block {
+        v.visit_statement(file, file.get_statement(*stmt));
+    }
+}
+
+pub fn walk_statement<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, stmt : &AstStatement) {
+    match stmt.get_kind() {
+        AstStatementKind::Return(expr) => {
+            if let Some(expr) = expr {
+                v.visit_expression(file, file.get_expression(*expr));
+            }
+        },
+
+        AstStatementKind::VarDec { data_type, value, .. } => {
+            v.visit_data_type(data_type);
+            if let Some(value) = value {
+                v.visit_expression(file, file.get_expression(*value));
+            }
+        },
+
+        AstStatementKind::ArrayDec { data_type, size, .. } => {
+            v.visit_data_type(data_type);
+            v.visit_expression(file, file.get_expression(*size));
+        },
+
+        AstStatementKind::StructDec { .. } => {},
+
+        AstStatementKind::CallStmt { args, .. } => {
+            for arg in args {
+                v.visit_expression(file, file.get_expression(*arg));
+            }
+        },
+
+        AstStatementKind::ExprStmt(expr) => v.visit_expression(file, file.get_expression(*expr)),
+
+        AstStatementKind::While { cond, block, .. } => {
+            v.visit_expression(file, file.get_expression(*cond));
+            v.visit_block(file, block);
+        },
+
+        AstStatementKind::Loop { block, .. } => v.visit_block(file, block),
+
+        AstStatementKind::Repeat { block, cond, .. } => {
+            v.visit_block(file, block);
+            v.visit_expression(file, file.get_expression(*cond));
+        },
+
+        AstStatementKind::Break(_) | AstStatementKind::Continue(_) => {},
+
+        AstStatementKind::If { branches, else_block } => {
+            for br in branches {
+                v.visit_expression(file, file.get_expression(br.get_cond()));
+                v.visit_block(file, br.get_block());
+            }
+
+            if let Some(block) = else_block {
+                v.visit_block(file, block);
+            }
+        },
+
+        AstStatementKind::Match { expr, cases, default } => {
+            v.visit_expression(file, file.get_expression(*expr));
+
+            for case in cases {
+                for value in case.get_values() {
+                    v.visit_expression(file, file.get_expression(*value));
+                }
+                v.visit_block(file, case.get_block());
+            }
+
+            if let Some(block) = default {
+                v.visit_block(file, block);
+            }
+        },
+    }
+}
+
+pub fn walk_expression<V : Visitor + ?Sized>(v : &mut V, file : &AstFile, expr : &AstExpression) {
+    match expr.get_kind() {
+        AstExpressionKind::Id(_)
+        | AstExpressionKind::IntLiteral(_)
+        | AstExpressionKind::CharLiteral(_)
+        | AstExpressionKind::StringLiteral(_)
+        | AstExpressionKind::BoolLiteral(_)
+        | AstExpressionKind::StructAcc { .. } => {},
+
+        AstExpressionKind::ArrayAcc { index, .. } => v.visit_expression(file, file.get_expression(*index)),
+
+        AstExpressionKind::Call { args, .. } => {
+            for arg in args {
+                v.visit_expression(file, file.get_expression(*arg));
+            }
+        },
+
+        AstExpressionKind::Cast { data_type, expr } => {
+            v.visit_expression(file, file.get_expression(*expr));
+            v.visit_data_type(data_type);
+        },
+
+        AstExpressionKind::Binary { lhs, rhs, .. } | AstExpressionKind::Assign { lhs, rhs, .. } => {
+            v.visit_expression(file, file.get_expression(*lhs));
+            v.visit_expression(file, file.get_expression(*rhs));
+        },
+    }
+}
+
+//
+// The walk functions for the mutable visitor
+//
+pub fn walk_file_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile) {
+    let mut structs = mem::take(file.get_structs_mut());
+    for s in &mut structs {
+        v.visit_struct(file, s);
+    }
+    *file.get_structs_mut() = structs;
+
+    let mut consts = mem::take(file.get_consts_mut());
+    for c in &mut consts {
+        v.visit_const(file, c);
+    }
+    *file.get_consts_mut() = consts;
+
+    let mut functions = mem::take(file.get_functions_mut());
+    for func in &mut functions {
+        v.visit_function(file, func);
+    }
+    *file.get_functions_mut() = functions;
+}
+
+pub fn walk_struct_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, s : &mut AstStruct) {
+    for item in s.get_items_mut() {
+        v.visit_item(file, item);
+    }
+}
+
+pub fn walk_arg_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, arg : &mut AstArg) {
+    if let Some(expr) = arg.get_expression() {
+        v.visit_expression(file, expr);
+    }
+}
+
+pub fn walk_function_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, func : &mut AstFunction) {
+    for arg in func.get_args_mut() {
+        v.visit_arg(file, arg);
+    }
+
+    for c in func.get_consts_mut() {
+        v.visit_const(file, c);
+    }
+
+    v.visit_block(file, func.get_block_mut());
+}
+
+pub fn walk_block_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, block : &mut Vec<StmtRef>) {
+    for stmt in block {
+        v.visit_statement(file, *stmt);
+    }
+}
+
+// The kind is copied out while its children are visited, and the blocks
+// are put back afterwards since the visitor may have changed them
+pub fn walk_statement_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, stmt : StmtRef) {
+    let mut kind = file.get_statement(stmt).get_kind().clone();
+    match &mut kind {
+        AstStatementKind::Return(expr) => {
+            if let Some(expr) = expr {
+                v.visit_expression(file, *expr);
+            }
+        },
+
+        AstStatementKind::VarDec { value, .. } => {
+            if let Some(value) = value {
+                v.visit_expression(file, *value);
+            }
+        },
+        AstStatementKind::ArrayDec { size, .. } => v.visit_expression(file, *size),
+        AstStatementKind::StructDec { .. } => {},
+
+        AstStatementKind::CallStmt { args, .. } => {
+            for arg in args {
+                v.visit_expression(file, *arg);
+            }
+        },
+
+        AstStatementKind::ExprStmt(expr) => v.visit_expression(file, *expr),
+
+        AstStatementKind::While { cond, block, .. } => {
+            v.visit_expression(file, *cond);
+            v.visit_block(file, block);
+        },
+
+        AstStatementKind::Loop { block, .. } => v.visit_block(file, block),
+
+        AstStatementKind::Repeat { block, cond, .. } => {
+            v.visit_block(file, block);
+            v.visit_expression(file, *cond);
+        },
+
+        AstStatementKind::Break(_) | AstStatementKind::Continue(_) => {},
+
+        AstStatementKind::If { branches, else_block } => {
+            for br in branches {
+                v.visit_expression(file, br.get_cond());
+                v.visit_block(file, br.get_block_mut());
+            }
+
+            if let Some(block) = else_block {
+                v.visit_block(file, block);
+            }
+        },
+
+        AstStatementKind::Match { expr, cases, default } => {
+            v.visit_expression(file, *expr);
+
+            for case in cases {
+                for value in case.get_values() {
+                    v.visit_expression(file, *value);
+                }
+                v.visit_block(file, case.get_block_mut());
+            }
+
+            if let Some(block) = default {
+                v.visit_block(file, block);
+            }
+        },
+    }
+    *file.get_statement_mut(stmt).get_kind_mut() = kind;
+}
+
+pub fn walk_expression_mut<V : MutVisitor + ?Sized>(v : &mut V, file : &mut AstFile, expr : ExprRef) {
+    match file.get_expression(expr).get_kind().clone() {
+        AstExpressionKind::Id(_)
+        | AstExpressionKind::IntLiteral(_)
+        | AstExpressionKind::CharLiteral(_)
+        | AstExpressionKind::StringLiteral(_)
+        | AstExpressionKind::BoolLiteral(_)
+        | AstExpressionKind::StructAcc { .. } => {},
+
+        AstExpressionKind::ArrayAcc { index, .. } => v.visit_expression(file, index),
+
+        AstExpressionKind::Call { args, .. } => {
+            for arg in args {
+                v.visit_expression(file, arg);
+            }
+        },
+
+        AstExpressionKind::Cast { expr, .. } => v.visit_expression(file, expr),
+
+        AstExpressionKind::Binary { lhs, rhs, .. } | AstExpressionKind::Assign { lhs, rhs, .. } => {
+            v.visit_expression(file, lhs);
+            v.visit_expression(file, rhs);
+        },
+    }
+}
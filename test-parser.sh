@@ -9,7 +9,7 @@ do
     echo `basename $f .tl`
 
     NAME=`basename $f`
-    cargo run $f > /tmp/$NAME
+    cargo run -- -I test/lib $f > /tmp/$NAME
     
     EXPECTED=`cat $f`
     ACTUAL=`cat /tmp/$NAME`
@@ -35,6 +35,172 @@ do
     fi
 done
 
+echo ""
+echo "Running JSON round-trip test..."
+echo ""
+
+for f in test/parser/*
+do
+    echo `basename $f .tl`
+
+    NAME=`basename $f .tl`
+    cargo run -- -I test/lib --ast=json $f > /tmp/$NAME.json
+    cargo run -- -I test/lib /tmp/$NAME.json > /tmp/$NAME.json.tl
+    diff $f /tmp/$NAME.json.tl
+
+    if [[ $? == 0 ]] ; then
+        echo "Pass"
+        echo ""
+    else
+        echo "Fail"
+        echo ""
+        exit 1
+    fi
+done
+
+echo ""
+echo "Running bad JSON test..."
+echo ""
+
+for f in test/json/*.json
+do
+    NAME=`basename $f .json`
+    echo $NAME
+
+    cargo run -q -- $f > /tmp/$NAME.json.out 2> /dev/null
+    STATUS=$?
+    diff test/json/$NAME.out /tmp/$NAME.json.out
+
+    if [[ $? == 0 && $STATUS == 1 ]] ; then
+        echo "Pass"
+        echo ""
+    else
+        echo "Fail"
+        echo ""
+        exit 1
+    fi
+done
+
+echo ""
+echo "Running AST view test..."
+echo ""
+
+for f in test/ast/*.tl
+do
+    for FORMAT in dot sexp
+    do
+        NAME=`basename $f .tl`
+        echo "$NAME.$FORMAT"
+
+        cargo run -- -I test/lib --ast=$FORMAT $f > /tmp/$NAME.$FORMAT
+        diff test/ast/$NAME.$FORMAT /tmp/$NAME.$FORMAT
+
+        if [[ $? == 0 ]] ; then
+            echo "Pass"
+            echo ""
+        else
+            echo "Fail"
+            echo ""
+            exit 1
+        fi
+    done
+done
+
+echo ""
+echo "Running diff test..."
+echo ""
+
+for f in test/diff/*.old.tl
+do
+    NAME=`basename $f .old.tl`
+    echo $NAME
+
+    cargo run -q -- diff $f test/diff/$NAME.new.tl > /tmp/$NAME.diff 2> /dev/null
+    STATUS=$?
+    diff test/diff/$NAME.out /tmp/$NAME.diff
+    DIFF=$?
+
+    # A file that doesn't parse fails the command
+    EXPECTED=0
+    if grep -q ": Error: " test/diff/$NAME.out ; then
+        EXPECTED=1
+    fi
+
+    if [[ $DIFF == 0 && $STATUS == $EXPECTED ]] ; then
+        echo "Pass"
+        echo ""
+    else
+        echo "Fail"
+        echo ""
+        exit 1
+    fi
+done
+
+echo ""
+echo "Running check test..."
+echo ""
+
+for f in test/check/*.tl
+do
+    NAME=`basename $f .tl`
+    echo $NAME
+
+    cargo run -q -- check -I test/lib $f > /tmp/$NAME.check 2> /dev/null
+    STATUS=$?
+    diff test/check/$NAME.out /tmp/$NAME.check
+    DIFF=$?
+
+    # The check fails exactly when it reports an error
+    EXPECTED=0
+    if grep -q ": Error: \|^Error: " test/check/$NAME.out ; then
+        EXPECTED=1
+    fi
+
+    if [[ $DIFF == 0 && $STATUS == $EXPECTED ]] ; then
+        echo "Pass"
+        echo ""
+    else
+        echo "Fail"
+        echo ""
+        exit 1
+    fi
+done
+
+echo "Running lint test..."
+echo ""
+
+for f in test/lint/*.tl
+do
+    NAME=`basename $f .tl`
+    echo $NAME
+
+    # A test can give the levels of its lints in a file of its own
+    ARGS=""
+    if [[ -f test/lint/$NAME.args ]] ; then
+        ARGS=`cat test/lint/$NAME.args`
+    fi
+
+    cargo run -q -- lint -I test/lib $ARGS $f > /tmp/$NAME.lint 2> /dev/null
+    STATUS=$?
+    diff test/lint/$NAME.out /tmp/$NAME.lint
+    DIFF=$?
+
+    # Only a denied lint fails
+    EXPECTED=0
+    if grep -q ": Error: " test/lint/$NAME.out ; then
+        EXPECTED=1
+    fi
+
+    if [[ $DIFF == 0 && $STATUS == $EXPECTED ]] ; then
+        echo "Pass"
+        echo ""
+    else
+        echo "Fail"
+        echo ""
+        exit 1
+    fi
+done
+
 echo ""
 echo "Done"
 echo ""